@@ -378,6 +378,19 @@ typedef struct {
 	Bool valid;
 } topt_SpanResult;
 
+// topt_TraceContextResult is returned when reading the trace context of a span.
+// Fields:
+//   - trace_id_upper: The upper 64 bits of the 128-bit trace ID (0 for 64-bit trace IDs).
+//   - trace_id_lower: The lower 64 bits of the trace ID.
+//   - span_id: The ID of the span.
+//   - valid: A Bool indicating whether the span was found.
+typedef struct {
+	Uint64 trace_id_upper;
+	Uint64 trace_id_lower;
+	topt_TslvId span_id;
+	Bool valid;
+} topt_TraceContextResult;
+
 // topt_MockSpan represents a mock span for testing purposes.
 // Fields:
 //   - span_id: The ID of the span.
@@ -412,8 +425,11 @@ import "C"
 import (
 	"bytes"
 	"context"
+	"encoding/binary"
 	"encoding/json"
 	"os"
+	"runtime"
+	"strconv"
 	"sync"
 	"sync/atomic"
 	"time"
@@ -456,6 +472,8 @@ type (
 		canShutdown    atomic.Bool // indicate if the library can be shut down
 		client         net.Client  // client to send code coverage payloads
 
+		coverageSendFailed atomic.Bool // indicate if a code coverage payload failed to be sent since the last flush
+
 		sessionMutex sync.RWMutex                        // mutex to protect the session map
 		sessions     map[uint64]civisibility.TestSession // map of test sessions
 		moduleMutex  sync.RWMutex                        // mutex to protect the modules map
@@ -471,6 +489,12 @@ type (
 	}
 )
 
+// nativeVersion is the version of the library, stamped by the release build with -ldflags "-X main.nativeVersion=<tag>".
+var nativeVersion = "dev"
+
+// nativeVersionCString is nativeVersion as a C string, allocated once and never freed.
+var nativeVersionCString = sync.OnceValue(func() *C.char { return C.CString(nativeVersion) })
+
 var exports = exportData{
 	client:   net.NewClientForCodeCoverage(),
 	sessions: make(map[uint64]civisibility.TestSession),
@@ -572,6 +596,12 @@ func topt_initialize(options C.topt_InitOptions) C.Bool {
 			os.Setenv(C.GoString(keyValue.key), C.GoString(keyValue.value))
 		}
 	}
+	// The Go runtime reads GOMAXPROCS when the library is loaded, so a value set by the options is applied here.
+	if value, ok := os.LookupEnv("GOMAXPROCS"); ok {
+		if maxProcs, err := strconv.Atoi(value); err == nil && maxProcs > 0 {
+			runtime.GOMAXPROCS(maxProcs)
+		}
+	}
 	if options.working_directory != nil {
 		wd := C.GoString(options.working_directory)
 		if wd != "" {
@@ -630,6 +660,18 @@ func topt_shutdown() C.Bool {
 	return toBool(true)
 }
 
+// topt_get_version returns the version of the library.
+//
+// Returns:
+//   - *C.char: The release tag the library was built from, or "dev" for a local build. The string is owned by the library and must not be freed.
+//
+// It can be called before topt_initialize, so bindings can check the version of the library they are linked against.
+//
+//export topt_get_version
+func topt_get_version() *C.char {
+	return nativeVersionCString()
+}
+
 // topt_get_settings retrieves the current configuration and feature flags of the library.
 //
 // Returns:
@@ -867,8 +909,27 @@ func topt_send_code_coverage_payload(coverages *C.topt_TestCoverage, coverages_l
 	jsonbytes, err := json.Marshal(&coveragePayload)
 	if err == nil {
 		encodedBuf.Write(jsonbytes)
-		exports.client.SendCoveragePayloadWithFormat(encodedBuf, net.FormatJSON)
+		err = exports.client.SendCoveragePayloadWithFormat(encodedBuf, net.FormatJSON)
+	}
+	if err != nil {
+		exports.coverageSendFailed.Store(true)
+	}
+}
+
+// topt_flush sends the finished spans and reports whether the code coverage payloads were sent.
+//
+// Returns:
+//   - C.Bool: True if every code coverage payload since the previous flush was sent, false if one failed or if the library isn't initialized.
+//
+// Code coverage payloads are sent as soon as topt_send_code_coverage_payload is called, which doesn't report errors; this is the point where they surface.
+//
+//export topt_flush
+func topt_flush() C.Bool {
+	if !exports.canShutdown.Load() {
+		return toBool(false)
 	}
+	ddtracer.Flush()
+	return toBool(!exports.coverageSendFailed.Swap(false))
 }
 
 // topt_get_test_management_tests retrieves a list of tests managed by the test management system.
@@ -1493,6 +1554,28 @@ func topt_test_close(test_id C.topt_TestId, options C.topt_TestCloseOptions) C.B
 	return toBool(false)
 }
 
+// topt_test_discard forgets a test without closing it, so it is never reported.
+//
+// Parameters:
+//   - test_id: The ID of the test to discard.
+//
+// Returns:
+//   - C.Bool: True if the test was found and discarded, false otherwise.
+//
+// The test span is never finished, so it is never sent. The test can't be used afterwards,
+// as if it was closed.
+//
+//export topt_test_discard
+func topt_test_discard(test_id C.topt_TestId) C.Bool {
+	exports.testMutex.Lock()
+	defer exports.testMutex.Unlock()
+	if _, ok := exports.tests[uint64(test_id)]; ok {
+		delete(exports.tests, uint64(test_id))
+		return toBool(true)
+	}
+	return toBool(false)
+}
+
 // topt_test_set_string_tag adds a string tag to a test.
 //
 // Parameters:
@@ -1737,6 +1820,31 @@ func getContext(tslv_id C.topt_TslvId) context.Context {
 	return context.Background()
 }
 
+// topt_get_trace_context gets the trace context of an open session, module, suite, test, or span.
+//
+// Parameters:
+//   - tslv_id: The ID of the session, module, suite, test, or span.
+//
+// Returns:
+//   - topt_TraceContextResult: The 128-bit trace ID and the span ID, with a validity flag set to false if the ID wasn't found.
+//
+// The context can be propagated to another process to continue the trace.
+//
+//export topt_get_trace_context
+func topt_get_trace_context(tslv_id C.topt_TslvId) C.topt_TraceContextResult {
+	if span, ok := ddtracer.SpanFromContext(getContext(tslv_id)); ok {
+		spanContext := span.Context()
+		traceID := spanContext.TraceIDBytes()
+		return C.topt_TraceContextResult{
+			trace_id_upper: C.Uint64(binary.BigEndian.Uint64(traceID[:8])),
+			trace_id_lower: C.Uint64(binary.BigEndian.Uint64(traceID[8:])),
+			span_id:        C.topt_TslvId(spanContext.SpanID()),
+			valid:          toBool(true),
+		}
+	}
+	return C.topt_TraceContextResult{valid: toBool(false)}
+}
+
 // topt_span_create creates a new generic span as a child of a session, module, suite, test, or another span.
 //
 // Parameters:
@@ -0,0 +1,86 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Test creation benchmark
+//!
+//! Times the creation of 1000 tests with the mock tracer, one `create_test` call per test and
+//! with a single `create_tests` call, then of tests with a source and tags, set one by one
+//! after `create_test` or through `TestBuilder`. Run with `cargo bench --bench test_creation`;
+//! like the tests, it needs the native library.
+use std::time::{Duration, Instant};
+use test_optimization_sdk::*;
+
+/// Number of tests created per run
+const TESTS: usize = 1000;
+
+/// Number of timed runs of each variant, the fastest one is reported
+const RUNS: usize = 5;
+
+/// Times the fastest of `RUNS` runs of a function creating `TESTS` tests in a new suite
+fn bench(name: &str, module: &TestModule, create: impl Fn(&TestSuite, &[String]) -> Vec<Test>) {
+    let names: Vec<String> = (0..TESTS).map(|index| format!("{name} {index}")).collect();
+    let mut fastest = Duration::MAX;
+    for _ in 0..RUNS {
+        let suite = module.create_test_suite(name);
+        let started_at = Instant::now();
+        let tests = create(&suite, &names);
+        fastest = fastest.min(started_at.elapsed());
+        for test in &tests {
+            test.close(TestStatus::Pass);
+        }
+        suite.close();
+    }
+    println!(
+        "{name}: {TESTS} tests in {fastest:?} ({:?} per test)",
+        fastest / TESTS as u32
+    );
+}
+
+fn main() {
+    assert!(TestOptimization::init_mock());
+    let session = TestSession::create(Some("cargo bench"), None::<&str>);
+    let module = session.create_module("test_creation", "cargo-bench", "1.0");
+
+    bench("create_test", &module, |suite, names| {
+        names.iter().map(|name| suite.create_test(name)).collect()
+    });
+    bench("create_tests", &module, |suite, names| suite.create_tests(names));
+
+    // The suite category is overwritten by the test, which the builder sets only once
+    let with_inherited_tag = |suite: &TestSuite| {
+        suite.set_inherited_tag("test.category", "integration");
+    };
+    bench("create_test with setters", &module, |suite, names| {
+        with_inherited_tag(suite);
+        names
+            .iter()
+            .map(|name| {
+                let test = suite.create_test(name);
+                test.set_test_source("src/lib.rs", &10, &20);
+                test.set_tag("test.category", "unit");
+                test.set_number_tag("test.weight", 3.0);
+                test
+            })
+            .collect()
+    });
+    bench("test_builder", &module, |suite, names| {
+        with_inherited_tag(suite);
+        names
+            .iter()
+            .map(|name| {
+                suite
+                    .test_builder(name)
+                    .source("src/lib.rs", 10..=20)
+                    .tag("test.category", "unit")
+                    .number_tag("test.weight", 3.0)
+                    .start()
+            })
+            .collect()
+    });
+
+    module.close();
+    session.close(0);
+    TestOptimization::shutdown();
+}
@@ -3,16 +3,24 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/).
 // Copyright 2025 Datadog, Inc.
 
-use std::path::{Path};
+use std::path::{Path, PathBuf};
 use std::{env, fs, io, process};
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::time::Duration;
 use ureq::AsSendBody;
 
 const TEST_OPTIMIZATION_SDK_SKIP_NATIVE_INSTALL: &str = "TEST_OPTIMIZATION_SDK_SKIP_NATIVE_INSTALL";
 const TEST_OPTIMIZATION_SDK_NATIVE_SEARCH_PATH: &str = "TEST_OPTIMIZATION_SDK_NATIVE_SEARCH_PATH";
+const TEST_OPTIMIZATION_SDK_HEADER_PATH: &str = "TEST_OPTIMIZATION_SDK_HEADER_PATH";
+const TEST_OPTIMIZATION_SDK_ARTIFACT_NAME: &str = "TEST_OPTIMIZATION_SDK_ARTIFACT_NAME";
+const TEST_OPTIMIZATION_SDK_DOWNLOAD_TIMEOUT_SECS: &str = "TEST_OPTIMIZATION_SDK_DOWNLOAD_TIMEOUT_SECS";
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(300);
 const TEST_OPTIMIZATION_DEV_MODE: &str = "TEST_OPTIMIZATION_DEV_MODE";
-const TEST_OPTIMIZATION_DOWNLOAD_URL_FORMAT: &str = "https://github.com/DataDog/test-optimization-native/releases/download/v0.0.4-preview/";
+const TEST_OPTIMIZATION_DOWNLOAD_URL_FORMAT: &str = "https://github.com/DataDog/test-optimization-native/releases/download/";
+const TEST_OPTIMIZATION_NATIVE_VERSION: &str = "v0.0.4-preview";
+const TEST_OPTIMIZATION_SDK_VERBOSE: &str = "TEST_OPTIMIZATION_SDK_VERBOSE";
 
 fn main() {
     let target = env::var("TARGET").expect("Cargo did not provide TARGET");
@@ -20,59 +28,99 @@ fn main() {
     let platform = if target.contains("apple-darwin") { "macos" } else if target.contains("windows") { "windows" } else if target.contains("linux") { "linux" } else { panic!("Unsupported platform: {}", target) };
     let arch = if target.contains("aarch64") { "arm64" } else { "x64" };
 
-    let lib_name = if platform == "macos" {
-        format!("{}-libtestoptimization-static.zip", platform)
-    } else {
-        format!("{}-{}-libtestoptimization-static.zip", platform, arch)
-    };
+    let lib_name = artifact_name(platform, arch);
+    println!("cargo:rerun-if-env-changed={}", TEST_OPTIMIZATION_SDK_VERBOSE);
+    emit_rustc_version();
 
     // Check for dev mode first (highest priority)
     if env::var(TEST_OPTIMIZATION_DEV_MODE).is_ok() {
-        link_from_dev_output(platform, arch);
+        emit_native_version("dev", dev_commit().as_deref());
+        let dev_output_path = link_from_dev_output(platform, arch);
+        expose_header(&dev_output_path, &out_dir);
         other_links(&target);
         return;
     }
 
     // Check for custom native library search path
     if let Ok(search_path) = env::var(TEST_OPTIMIZATION_SDK_NATIVE_SEARCH_PATH) {
+        // The version of a user-supplied library is unknown, it's only read from the library at init
+        emit_native_version("custom", None);
         link_from_search_path(platform, &lib_name, &search_path);
+        expose_header(Path::new(&search_path), &out_dir);
     } else {
+        // The downloaded library is the pinned release
+        emit_native_version(TEST_OPTIMIZATION_NATIVE_VERSION, None);
         let lib_dir = Path::new(&out_dir);
 
         // Check if library files already exist
-        let has_library = match platform {
-            "windows" => lib_dir.join("testoptimization.lib").exists(),
-            "linux" => lib_dir.join("libtestoptimization.a").exists(),
-            "macos" => lib_dir.join("libtestoptimization.a").exists(),
-            _ => false,
-        };
+        let has_library = lib_dir.join(static_library_file(platform)).exists();
 
         if !has_library {
-            // Skip download if explicitly disabled
+            // Skip download if explicitly disabled, nothing is linked then so say which file is missing
             if env::var(TEST_OPTIMIZATION_SDK_SKIP_NATIVE_INSTALL).is_ok() {
-                println!("cargo:warning=Skipping native library installation as {} is set", TEST_OPTIMIZATION_SDK_SKIP_NATIVE_INSTALL);
+                println!(
+                    "cargo:warning=Skipping native library installation as {} is set, but {} is missing from {}: linking against the crate will fail",
+                    TEST_OPTIMIZATION_SDK_SKIP_NATIVE_INSTALL,
+                    static_library_file(platform),
+                    lib_dir.display()
+                );
+                expose_header(lib_dir, &out_dir);
                 return;
             }
 
             download_library(&out_dir, &lib_name, &lib_dir);
         }
+        assert_library_exists(
+            platform,
+            lib_dir,
+            &format!(
+                "the {} archive of the {} release doesn't contain it. Delete {} to download it again, or set {} to a directory containing it",
+                lib_name,
+                TEST_OPTIMIZATION_NATIVE_VERSION,
+                lib_dir.display(),
+                TEST_OPTIMIZATION_SDK_NATIVE_SEARCH_PATH
+            ),
+        );
 
         println!("cargo:rustc-link-search=native={}", lib_dir.display());
         println!("cargo:rustc-link-lib=static=testoptimization");
+        expose_header(lib_dir, &out_dir);
     }
 
     other_links(&target);
 }
 
+// Informational messages are printed as cargo warnings only when TEST_OPTIMIZATION_SDK_VERBOSE
+// is set; genuine warnings (e.g. a missing dev mode library) always use cargo:warning directly.
+fn info(message: String) {
+    if env::var_os(TEST_OPTIMIZATION_SDK_VERBOSE).is_some() {
+        println!("cargo:warning={}", message);
+    }
+}
+
+fn artifact_name(platform: &str, arch: &str) -> String {
+    // Mirrors may store the artifacts under other names, {platform} and {arch} are substituted
+    println!("cargo:rerun-if-env-changed={}", TEST_OPTIMIZATION_SDK_ARTIFACT_NAME);
+    if let Ok(pattern) = env::var(TEST_OPTIMIZATION_SDK_ARTIFACT_NAME) {
+        return pattern.replace("{platform}", platform).replace("{arch}", arch);
+    }
+    if platform == "macos" {
+        format!("{}-libtestoptimization-static.zip", platform)
+    } else {
+        format!("{}-{}-libtestoptimization-static.zip", platform, arch)
+    }
+}
+
 fn download_library(out_dir: &str, lib_name: &str, lib_dir: &Path) {
     // Get the folder
-    let url = format!("{}{}", TEST_OPTIMIZATION_DOWNLOAD_URL_FORMAT, lib_name);
+    let url = format!("{}{}/{}", TEST_OPTIMIZATION_DOWNLOAD_URL_FORMAT, TEST_OPTIMIZATION_NATIVE_VERSION, lib_name);
     let lib_zip_path = Path::new(out_dir).join("libtestoptimization.zip");
 
     // Download and extract library only if it doesn't exist
-    println!("cargo:warning=Downloading native library from: {}", url);
+    info(format!("Downloading native library from: {}", url));
 
-    let mut response = ureq::get(&url)
+    let mut response = download_agent()
+        .get(&url)
         .call()
         .unwrap_or_else(|e| {
             eprintln!("Failed to download native library: {}", e);
@@ -90,6 +138,27 @@ fn download_library(out_dir: &str, lib_name: &str, lib_dir: &Path) {
     extract_zip(&lib_zip_path, lib_dir).expect("Failed to decompress native library");
 }
 
+fn download_agent() -> ureq::Agent {
+    // A single override bounds each phase of the download, a hung connection fails the build
+    println!("cargo:rerun-if-env-changed={}", TEST_OPTIMIZATION_SDK_DOWNLOAD_TIMEOUT_SECS);
+    let (connect_timeout, read_timeout) = match env::var(TEST_OPTIMIZATION_SDK_DOWNLOAD_TIMEOUT_SECS) {
+        Ok(value) => {
+            let timeout = value.trim().parse::<u64>().map(Duration::from_secs).unwrap_or_else(|_| {
+                eprintln!("Invalid {}: {:?}, expected a number of seconds", TEST_OPTIMIZATION_SDK_DOWNLOAD_TIMEOUT_SECS, value);
+                process::exit(1);
+            });
+            (timeout, timeout)
+        }
+        Err(_) => (DEFAULT_CONNECT_TIMEOUT, DEFAULT_READ_TIMEOUT),
+    };
+    ureq::Agent::config_builder()
+        .timeout_connect(Some(connect_timeout))
+        .timeout_recv_response(Some(read_timeout))
+        .timeout_recv_body(Some(read_timeout))
+        .build()
+        .into()
+}
+
 fn extract_zip(zip_path: &Path, target_dir: &Path) -> io::Result<()> {
     let file = File::open(zip_path)?;
     let mut archive = zip::ZipArchive::new(io::BufReader::new(file))?;
@@ -116,33 +185,43 @@ fn extract_zip(zip_path: &Path, target_dir: &Path) -> io::Result<()> {
 fn link_from_search_path(platform: &str, lib_name: &str, search_path: &str) {
     let search_path = Path::new(search_path);
 
-    // First check for already extracted library files
-    let has_library = match platform {
-        "windows" => search_path.join("testoptimization.lib").exists(),
-        "linux" => search_path.join("libtestoptimization.a").exists(),
-        "macos" => search_path.join("libtestoptimization.a").exists(),
-        _ => false,
-    };
-
-    if has_library {
-        println!("cargo:warning=Using custom native library search path: {}", search_path.display());
-        println!("cargo:rustc-link-search=native={}", search_path.display());
-        println!("cargo:rustc-link-lib=static=testoptimization");
-        return;
-    }
-
+    // Extract the release archive unless the library files are already there
+    let has_library = search_path.join(static_library_file(platform)).exists();
     let lib_zip_path = search_path.join(&lib_name);
-    if lib_zip_path.exists() {
-        println!("cargo:warning=Found .zip file in custom search path, extracting...[{}]", lib_zip_path.display());
+    if !has_library && lib_zip_path.exists() {
+        info(format!("Found .zip file in custom search path, extracting...[{}]", lib_zip_path.display()));
         extract_zip(&lib_zip_path, &search_path)
             .expect("Failed to decompress native library from custom search path");
-        println!("cargo:warning=Using custom native library search path: {}", search_path.display());
-        println!("cargo:rustc-link-search=native={}", search_path.display());
-        println!("cargo:rustc-link-lib=static=testoptimization");
+    }
+
+    assert_library_exists(
+        platform,
+        search_path,
+        &format!(
+            "put the library or the {} release archive there, or unset {} to download it",
+            lib_name, TEST_OPTIMIZATION_SDK_NATIVE_SEARCH_PATH
+        ),
+    );
+    info(format!("Using custom native library search path: {}", search_path.display()));
+    println!("cargo:rustc-link-search=native={}", search_path.display());
+    println!("cargo:rustc-link-lib=static=testoptimization");
+}
+
+// The static library the link directives refer to, as named in the release archives
+fn static_library_file(platform: &str) -> &'static str {
+    if platform == "windows" { "testoptimization.lib" } else { "libtestoptimization.a" }
+}
+
+// Fails the build with an actionable message when the static library is missing, instead of
+// emitting link directives that end in an opaque linker error
+fn assert_library_exists(platform: &str, lib_dir: &Path, how_to_fix: &str) {
+    let library_file = static_library_file(platform);
+    if !lib_dir.join(library_file).is_file() {
+        panic!("The native library {} was not found in {}: {}", library_file, lib_dir.display(), how_to_fix);
     }
 }
 
-fn link_from_dev_output(platform: &str, arch: &str) {
+fn link_from_dev_output(platform: &str, arch: &str) -> PathBuf {
     // Construct the dev output folder name based on platform and arch
     let folder_name = if platform == "macos" {
         format!("{}-libtestoptimization-static", platform)
@@ -155,17 +234,13 @@ fn link_from_dev_output(platform: &str, arch: &str) {
     let dev_output_path = Path::new("../../../dev-output").join(&folder_name);
 
     // Check if the library files exist
-    let has_library = match platform {
-        "windows" => dev_output_path.join("testoptimization.lib").exists(),
-        "linux" => dev_output_path.join("libtestoptimization.a").exists(), 
-        "macos" => dev_output_path.join("libtestoptimization.a").exists(),
-        _ => false,
-    };
+    let has_library = dev_output_path.join(static_library_file(platform)).exists();
 
     if has_library {
-        println!("cargo:warning=Using dev mode native library from: {}", dev_output_path.display());
+        info(format!("Using dev mode native library from: {}", dev_output_path.display()));
         println!("cargo:rustc-link-search=native={}", dev_output_path.display());
         println!("cargo:rustc-link-lib=static=testoptimization");
+        dev_output_path
     } else {
         println!("cargo:warning=Dev mode enabled but library not found at: {}", dev_output_path.display());
         println!("cargo:warning=Please run the localdev.sh script to build the native libraries first");
@@ -173,32 +248,125 @@ fn link_from_dev_output(platform: &str, arch: &str) {
     }
 }
 
+#[cfg_attr(not(feature = "regenerate-bindings"), allow(unused_variables))]
+fn expose_header(lib_dir: &Path, out_dir: &str) {
+    // The release archives ship the cgo generated header next to the library
+    println!("cargo:rerun-if-env-changed={}", TEST_OPTIMIZATION_SDK_HEADER_PATH);
+    let header_dir = env::var(TEST_OPTIMIZATION_SDK_HEADER_PATH)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| lib_dir.to_path_buf());
+    let header_path = header_dir.join("libtestoptimization.h");
+
+    if header_path.exists() {
+        // Available to dependent build scripts as DEP_TESTOPTIMIZATION_INCLUDE
+        println!("cargo:include={}", header_dir.display());
+    }
+
+    #[cfg(feature = "regenerate-bindings")]
+    generate_bindings(&header_path, out_dir);
+}
+
+#[cfg(feature = "regenerate-bindings")]
+fn generate_bindings(header_path: &Path, out_dir: &str) {
+    if !header_path.exists() {
+        eprintln!("The regenerate-bindings feature is enabled but the header was not found at: {}", header_path.display());
+        eprintln!("Set {} to the directory containing libtestoptimization.h", TEST_OPTIMIZATION_SDK_HEADER_PATH);
+        process::exit(1);
+    }
+
+    println!("cargo:rerun-if-changed={}", header_path.display());
+    info(format!("Generating native bindings from: {}", header_path.display()));
+    let bindings = bindgen::Builder::default()
+        .header(header_path.to_string_lossy())
+        .allowlist_function("topt_.*")
+        .allowlist_type("topt_.*")
+        .allowlist_var("topt_.*")
+        .generate()
+        .expect("Failed to generate native bindings");
+    bindings
+        .write_to_file(Path::new(out_dir).join("bindings.rs"))
+        .expect("Failed to write native bindings");
+}
+
+fn emit_native_version(version: &str, commit: Option<&str>) {
+    // Exposed to the crate through env! so it can be reported at runtime
+    println!("cargo:rustc-env=TEST_OPTIMIZATION_NATIVE_VERSION={}", version);
+    println!("cargo:rustc-env=TEST_OPTIMIZATION_NATIVE_COMMIT={}", commit.unwrap_or(""));
+}
+
+fn emit_rustc_version() {
+    // Fallback of the runtime version when rustc_version_runtime fails at runtime
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .and_then(|output| output.split_whitespace().nth(1).map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=TEST_OPTIMIZATION_SDK_RUSTC_VERSION={}", version);
+}
+
+fn dev_commit() -> Option<String> {
+    // In dev mode the native library is built from this checkout, so its commit is the repo HEAD
+    let output = process::Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir("../../..")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?;
+    Some(commit.trim().to_string())
+}
+
+// System libraries the Go c-archive depends on, as (kind, name) link directives.
+// The Go runtime needs pthread (and dl on glibc older than 2.34, where it is a separate
+// library), the math functions of cgo need m, and the net package needs resolv.
+const LINUX_LINKS: &[(&str, &str)] = &[
+    ("dylib", "resolv"),
+    ("dylib", "pthread"),
+    ("dylib", "dl"),
+    ("dylib", "m"),
+];
+// On macOS pthread, dl and m are part of libSystem, linked by default
+const MACOS_LINKS: &[(&str, &str)] = &[
+    ("dylib", "resolv"),
+    ("framework", "CoreFoundation"),
+    ("framework", "IOKit"),
+    ("framework", "Security"),
+];
+
 fn other_links(target: &str) {
-    if !target.contains("windows") {
-        // Link to the dynamic dependency
-        println!("cargo:rustc-link-lib=dylib=resolv");
+    let links = if target.contains("apple-darwin") {
+        MACOS_LINKS
+    } else if target.contains("linux") {
+        LINUX_LINKS
     } else {
-        // Windows version requires cc as a build-dependency
-        #[cfg(target_os = "windows")]
+        // Windows version requires cc as a build-dependency; the Go runtime loads its
+        // system DLLs at runtime, and the import libraries are linked by std
         configure_windows();
-    }
+        &[]
+    };
 
-    // If we are in osx, we need to add a couple of frameworks
-    if target.contains("apple-darwin") {
-        println!("cargo:rustc-link-lib=framework=CoreFoundation");
-        println!("cargo:rustc-link-lib=framework=IOKit");
-        println!("cargo:rustc-link-lib=framework=Security");
+    for (kind, name) in links {
+        println!("cargo:rustc-link-lib={}={}", kind, name);
     }
 }
 
-#[cfg(target_os = "windows")]
 fn configure_windows() {
-    // Windows target
+    // Checked on the target, not on the host the build script runs on, so cross builds get
+    // the shim too; cgo.c starts the amd64 Go runtime, which doesn't exist on other archs
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows")
+        || env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("x86_64")
+    {
+        return;
+    }
     println!("cargo::rerun-if-changed=src/test_optimization/lib/cgo.c");
+    // Nothing references the initializer of cgo.c, so it's linked whole to be kept
     cc::Build::new()
         .file("src/test_optimization/lib/cgo.c")
+        .link_lib_modifier("+whole-archive")
         .compile("cgo");
-
-    // Link to the lib
-    println!("cargo:rustc-link-lib=static=cgo");
 }
@@ -0,0 +1,71 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Expansion of the `#[dd_test]` attribute
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Error, FnArg, ItemFn, Pat, ReturnType};
+
+/// Attributes that already make a function a test, so `#[test]` must not be added
+const TEST_ATTRIBUTES: [&str; 3] = ["test", "rstest", "test_case"];
+
+/// Expands `#[dd_test]` on a function into a test run through `TestSuite::run_test`
+pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(Error::new(attr.span(), "#[dd_test] doesn't take arguments"));
+    }
+    let function: ItemFn = syn::parse2(item)?;
+    if let Some(asyncness) = &function.sig.asyncness {
+        return Err(Error::new(asyncness.span(), "#[dd_test] doesn't support async functions"));
+    }
+
+    let mut names = Vec::new();
+    let mut arguments = Vec::new();
+    for input in &function.sig.inputs {
+        match input {
+            FnArg::Typed(argument) => {
+                if let Pat::Ident(pattern) = argument.pat.as_ref() {
+                    names.push(pattern.ident.to_string());
+                    arguments.push(pattern.ident.clone());
+                }
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "#[dd_test] can't be used on methods"));
+            }
+        }
+    }
+
+    let is_test = function.attrs.iter().any(|attribute| {
+        attribute
+            .path()
+            .segments
+            .last()
+            .is_some_and(|segment| TEST_ATTRIBUTES.iter().any(|name| segment.ident == name))
+    });
+    let test_attribute = (!is_test).then(|| quote!(#[test]));
+
+    let ItemFn { attrs, vis, sig, block } = &function;
+    let test_name = sig.ident.to_string();
+    let closure_output = match &sig.output {
+        ReturnType::Default => quote!(),
+        ReturnType::Type(arrow, output) => quote!(#arrow #output),
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #test_attribute
+        #vis #sig {
+            let __dd_parameters = ::test_optimization_sdk::TestParameters::from_debug(&[
+                #((#names, &#arguments as &dyn ::std::fmt::Debug)),*
+            ]);
+            ::test_optimization_sdk::TestSession::global_suite(
+                ::core::env!("CARGO_PKG_NAME"),
+                ::core::module_path!(),
+            )
+            .run_test(#test_name, &__dd_parameters, move || #closure_output #block)
+        }
+    })
+}
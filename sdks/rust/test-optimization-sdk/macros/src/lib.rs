@@ -0,0 +1,46 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Attribute macros for Datadog's test optimization sdk
+//!
+//! These are re-exported by `test-optimization-sdk` with its `macros` feature,
+//! which the generated code refers to as `::test_optimization_sdk`.
+use proc_macro::TokenStream;
+
+mod dd_test;
+#[cfg(test)]
+mod tests;
+
+/// Reports a `#[test]` function as a Datadog test
+///
+/// The test is created in the suite of the global session named after the module path
+/// (`TestSession::global_suite` with the crate name as the module), named after the
+/// function, and run with `TestSuite::run_test`: it passes when the body returns (or
+/// returns `Ok`), fails when it returns `Err` or panics, and is skipped without running
+/// when the Intelligent Test Runner can skip it.
+///
+/// `#[test]` is added unless the function already has a `test`, `rstest` or `test_case`
+/// attribute. The arguments of `rstest`/`test-case` style cases are reported as the test
+/// parameters with their `Debug` representation, so `#[dd_test]` must come first:
+///
+/// ```ignore
+/// #[dd_test]
+/// fn adds() {
+///     assert_eq!(1 + 1, 2);
+/// }
+///
+/// #[dd_test]
+/// #[rstest]
+/// #[case(1, 2)]
+/// fn doubles(#[case] input: i32, #[case] expected: i32) -> Result<(), String> {
+///     (input * 2 == expected).then_some(()).ok_or_else(|| format!("{} * 2", input))
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn dd_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    dd_test::expand(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
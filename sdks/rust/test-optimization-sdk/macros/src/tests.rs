@@ -0,0 +1,61 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+use crate::dd_test::expand;
+use quote::quote;
+
+fn expanded(item: proc_macro2::TokenStream) -> String {
+    expand(quote!(), item).unwrap().to_string()
+}
+
+#[test]
+fn dd_test_adds_the_test_attribute_and_runs_the_body() {
+    let expansion = expanded(quote! {
+        fn adds() {
+            assert_eq!(1 + 1, 2);
+        }
+    });
+    assert!(expansion.starts_with("# [test] fn adds ()"));
+    assert!(expansion.contains(". run_test (\"adds\" , & __dd_parameters , move || {"));
+    assert!(expansion.contains("TestParameters :: from_debug (& [])"));
+}
+
+#[test]
+fn dd_test_keeps_the_result_of_the_body() {
+    let expansion = expanded(quote! {
+        fn parses() -> Result<(), std::num::ParseIntError> {
+            "42".parse::<i32>()?;
+            Ok(())
+        }
+    });
+    assert!(expansion.contains("move || -> Result < () , std :: num :: ParseIntError > {"));
+}
+
+#[test]
+fn dd_test_captures_rstest_arguments_as_parameters() {
+    let expansion = expanded(quote! {
+        #[rstest]
+        #[case(1, 2)]
+        fn doubles(#[case] input: i32, #[case] expected: i32) {
+            assert_eq!(input * 2, expected);
+        }
+    });
+    assert!(!expansion.contains("# [test]"));
+    assert!(expansion.starts_with("# [rstest] # [case (1 , 2)] fn doubles (# [case] input : i32"));
+    assert!(expansion.contains(
+        "(\"input\" , & input as & dyn :: std :: fmt :: Debug) , (\"expected\" , & expected as & dyn :: std :: fmt :: Debug)"
+    ));
+}
+
+#[test]
+fn dd_test_rejects_unsupported_functions() {
+    let error = |item| expand(quote!(), item).unwrap_err().to_string();
+    assert_eq!(error(quote!(async fn waits() {})), "#[dd_test] doesn't support async functions");
+    assert_eq!(error(quote!(fn method(&self) {})), "#[dd_test] can't be used on methods");
+    assert_eq!(
+        expand(quote!(retries = 3), quote!(fn adds() {})).unwrap_err().to_string(),
+        "#[dd_test] doesn't take arguments"
+    );
+}
@@ -8,3 +8,5 @@ mod test_optimization;
 mod tests;
 
 pub use test_optimization::*;
+#[cfg(feature = "macros")]
+pub use test_optimization_sdk_macros::dd_test;
@@ -0,0 +1,267 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Analysis module for previewing the decisions of test optimization without reporting
+//!
+//! A session started with `TestSession::init_analysis` runs on the mock tracer, so the spans
+//! stay in memory and nothing is sent to the backend. The settings, known tests, skippable
+//! tests and test management tests are fetched once when the analysis starts, and each test
+//! closed afterwards is matched against them to record what a real run would have done.
+use crate::test_optimization::registry;
+use crate::test_optimization::*;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents what test optimization would have done with a test, in an analysis session
+pub struct AnalyzedTest {
+    /// The name of the module of the test
+    #[allow(dead_code)]
+    pub module_name: String,
+    /// The name of the suite of the test
+    #[allow(dead_code)]
+    pub suite_name: String,
+    /// The name of the test
+    #[allow(dead_code)]
+    pub test_name: String,
+    /// Whether the Intelligent Test Runner would have skipped the test
+    #[allow(dead_code)]
+    pub would_skip: bool,
+    /// Whether the test is new, so early flake detection would have retried it
+    #[allow(dead_code)]
+    pub would_retry_as_new: bool,
+    /// Whether the test is quarantined, so its failures wouldn't fail the session
+    #[allow(dead_code)]
+    pub quarantined: bool,
+    /// Whether the test is disabled, so it wouldn't have run
+    #[allow(dead_code)]
+    pub disabled: bool,
+    /// Whether the test is attempted to be fixed, so it would have been retried
+    #[allow(dead_code)]
+    pub attempt_to_fix: bool,
+}
+
+#[derive(Debug, Clone)]
+/// Represents the decisions of an analysis session, see `TestSession::init_analysis`
+pub struct AnalysisSummary {
+    /// The settings fetched when the analysis started
+    #[allow(dead_code)]
+    pub settings: Settings,
+    /// The tests closed so far, in close order
+    #[allow(dead_code)]
+    pub tests: Vec<AnalyzedTest>,
+}
+
+impl AnalysisSummary {
+    /// Gets the number of tests the Intelligent Test Runner would have skipped
+    #[allow(dead_code)]
+    pub fn would_skip_count(&self) -> usize {
+        self.tests.iter().filter(|test| test.would_skip).count()
+    }
+
+    /// Gets the number of new tests early flake detection would have retried
+    #[allow(dead_code)]
+    pub fn would_retry_as_new_count(&self) -> usize {
+        self.tests.iter().filter(|test| test.would_retry_as_new).count()
+    }
+
+    /// Gets the number of tests with a test management flag (quarantined, disabled or
+    /// attempted to be fixed)
+    #[allow(dead_code)]
+    pub fn test_management_count(&self) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.quarantined || test.disabled || test.attempt_to_fix)
+            .count()
+    }
+}
+
+/// What a real run would have used, fetched once when the analysis starts
+struct AnalysisData {
+    /// The settings of the session
+    settings: Settings,
+    /// The known tests, by module and suite
+    known_tests: HashMap<String, HashMap<String, Vec<String>>>,
+    /// The skippable tests, by suite and test
+    skippable_tests: HashMap<String, HashMap<String, Vec<SkippableTest>>>,
+    /// The test management tests, by module, suite and test
+    test_management_tests: HashMap<String, HashMap<String, HashMap<String, TestManagementTest>>>,
+}
+
+impl AnalysisData {
+    /// Matches a test against the data to decide what a real run would have done with it
+    fn analyze(&self, module_name: String, suite_name: &str, test_name: &str) -> AnalyzedTest {
+        let settings = &self.settings;
+        let is_known = self
+            .known_tests
+            .get(&module_name)
+            .and_then(|suites| suites.get(suite_name))
+            .is_some_and(|tests| tests.iter().any(|name| name == test_name));
+        let test_management_test = self
+            .test_management_tests
+            .get(&module_name)
+            .and_then(|suites| suites.get(suite_name))
+            .and_then(|tests| tests.get(test_name))
+            .filter(|_| settings.test_management.enabled);
+        AnalyzedTest {
+            would_skip: settings.itr_enabled
+                && settings.tests_skipping
+                && self
+                    .skippable_tests
+                    .get(suite_name)
+                    .is_some_and(|tests| tests.contains_key(test_name)),
+            would_retry_as_new: settings.known_tests_enabled
+                && settings.early_flake_detection.enabled
+                && !is_known,
+            quarantined: test_management_test.is_some_and(|test| test.quarantined),
+            disabled: test_management_test.is_some_and(|test| test.disabled),
+            attempt_to_fix: test_management_test.is_some_and(|test| test.attempt_to_fix),
+            module_name,
+            suite_name: suite_name.to_string(),
+            test_name: test_name.to_string(),
+        }
+    }
+}
+
+/// Data of the analysis, set when it starts
+static ANALYSIS_DATA: OnceLock<AnalysisData> = OnceLock::new();
+
+/// Tests analyzed so far
+static ANALYZED_TESTS: Mutex<Vec<AnalyzedTest>> = Mutex::new(Vec::new());
+
+/// Fetches the data the decisions are made from, starting the analysis
+pub(in crate::test_optimization) fn start() {
+    ANALYSIS_DATA.get_or_init(|| AnalysisData {
+        settings: TestOptimization::get_settings(),
+        known_tests: TestOptimization::get_known_tests(),
+        skippable_tests: TestOptimization::get_skippable_tests(),
+        test_management_tests: TestOptimization::get_test_management_tests(),
+    });
+}
+
+/// Records the decisions for a test being closed, if an analysis is running
+pub(in crate::test_optimization) fn record_test(test: &Test) {
+    let Some(data) = ANALYSIS_DATA.get() else {
+        return;
+    };
+    let module_name = registry::name(test.module_id).unwrap_or_default();
+    let analyzed_test = data.analyze(module_name, &test.suite_name, &test.name);
+    ANALYZED_TESTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(analyzed_test);
+}
+
+/// Gets the summary of the analysis, `None` if none is running
+pub(in crate::test_optimization) fn summary() -> Option<AnalysisSummary> {
+    let data = ANALYSIS_DATA.get()?;
+    Some(AnalysisSummary {
+        settings: data.settings.clone(),
+        tests: ANALYZED_TESTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis_data(settings: Settings) -> AnalysisData {
+        let known_tests = HashMap::from([(
+            "my-module".to_string(),
+            HashMap::from([("my suite".to_string(), vec!["known".to_string()])]),
+        )]);
+        let skippable_test = SkippableTest {
+            suite_name: "my suite".to_string(),
+            test_name: "skippable".to_string(),
+            parameters: String::new(),
+            custom_configurations_json: String::new(),
+        };
+        let skippable_tests = HashMap::from([(
+            "my suite".to_string(),
+            HashMap::from([("skippable".to_string(), vec![skippable_test])]),
+        )]);
+        let test_management_test = TestManagementTest {
+            module_name: "my-module".to_string(),
+            suite_name: "my suite".to_string(),
+            test_name: "known".to_string(),
+            quarantined: true,
+            disabled: false,
+            attempt_to_fix: true,
+        };
+        let test_management_tests = HashMap::from([(
+            "my-module".to_string(),
+            HashMap::from([(
+                "my suite".to_string(),
+                HashMap::from([("known".to_string(), test_management_test)]),
+            )]),
+        )]);
+        AnalysisData { settings, known_tests, skippable_tests, test_management_tests }
+    }
+
+    fn settings(enabled: bool) -> Settings {
+        Settings {
+            code_coverage: false,
+            early_flake_detection: EfDSettings {
+                enabled,
+                slow_test_retries: EfdSlowTestRetriesSettings {
+                    five_m: 0,
+                    thirty_s: 0,
+                    ten_s: 0,
+                    five_s: 0,
+                },
+                faulty_session_threshold: 30,
+            },
+            flaky_test_retries_enabled: false,
+            itr_enabled: enabled,
+            require_git: false,
+            tests_skipping: enabled,
+            known_tests_enabled: enabled,
+            test_management: TestManagementSettings { enabled, attempt_to_fix_retries: 0 },
+        }
+    }
+
+    fn summarize(data: &AnalysisData) -> AnalysisSummary {
+        AnalysisSummary {
+            settings: data.settings.clone(),
+            tests: ["known", "skippable", "new"]
+                .into_iter()
+                .map(|test_name| data.analyze("my-module".to_string(), "my suite", test_name))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn tests_are_matched_against_the_analysis_data() {
+        let data = analysis_data(settings(true));
+        let summary = summarize(&data);
+        assert_eq!(
+            summary.tests[0],
+            AnalyzedTest {
+                module_name: "my-module".to_string(),
+                suite_name: "my suite".to_string(),
+                test_name: "known".to_string(),
+                would_skip: false,
+                would_retry_as_new: false,
+                quarantined: true,
+                disabled: false,
+                attempt_to_fix: true,
+            }
+        );
+        assert!(summary.tests[1].would_skip && summary.tests[1].would_retry_as_new);
+        assert!(!summary.tests[2].would_skip && summary.tests[2].would_retry_as_new);
+        assert_eq!(summary.would_skip_count(), 1);
+        assert_eq!(summary.would_retry_as_new_count(), 2);
+        assert_eq!(summary.test_management_count(), 1);
+
+        let data = analysis_data(settings(false));
+        let summary = summarize(&data);
+        assert_eq!(summary.would_skip_count(), 0);
+        assert_eq!(summary.would_retry_as_new_count(), 0);
+        assert_eq!(summary.test_management_count(), 0);
+    }
+}
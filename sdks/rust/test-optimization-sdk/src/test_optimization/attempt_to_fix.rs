@@ -0,0 +1,167 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Attempt-to-fix module for running the executions of a test flagged as attempted to be fixed
+//!
+//! Datadog expects an attempt-to-fix run as a series of test spans with the same name, all
+//! children of the suite span: there is no parent span grouping them, the backend groups the
+//! executions by test. The first execution is the original test, and each retry is a new test
+//! created in the same suite. Every execution has `test.test_management.is_attempt_to_fix`
+//! set to `"true"`, the retries also have `test.is_retry`, `test.retry_attempt`,
+//! `test.retry_original_status` and `test.retry_reason` set to `"attempt_to_fix"`, and only
+//! the last execution carries the verdict: `test.test_management.attempt_to_fix_passed`, plus
+//! `test.has_failed_all_retries` when every execution failed.
+//!
+//! The executions are siblings, not children of a parent attempt-to-fix span. A test span
+//! under another test span isn't reported as a test execution, so a parent span would hide
+//! the retries from the backend; `AttemptToFixSession` only groups them on the SDK side.
+use crate::test_optimization::*;
+use std::ops::Deref;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents the outcome of an attempt-to-fix run, see `Test::begin_attempt_to_fix`
+pub struct AttemptToFixVerdict {
+    /// The statuses of the executions, the original test first
+    #[allow(dead_code)]
+    pub statuses: Vec<TestStatus>,
+}
+
+impl AttemptToFixVerdict {
+    /// Whether every execution passed, so the fix works
+    #[allow(dead_code)]
+    pub fn passed(&self) -> bool {
+        !self.statuses.is_empty() && self.statuses.iter().all(|status| *status == TestStatus::Pass)
+    }
+
+    /// Whether every execution failed
+    #[allow(dead_code)]
+    pub fn failed_all(&self) -> bool {
+        !self.statuses.is_empty() && self.statuses.iter().all(|status| *status == TestStatus::Fail)
+    }
+}
+
+#[derive(Debug)]
+/// Represents the executions of a test being attempted to be fixed
+///
+/// Dereferences to the running execution. `next_attempt` ends it and starts a retry, and
+/// `finish` ends the last one with the verdict; dropping the session without calling
+/// `finish` (an early return or a panic) ends the running execution as `Fail`.
+pub struct AttemptToFixSession {
+    /// The running execution, `None` once finished
+    current: Option<Test>,
+    /// Number of retries that can still be started
+    retries_left: u32,
+    /// Statuses of the ended executions
+    statuses: Vec<TestStatus>,
+}
+
+impl AttemptToFixSession {
+    /// Gets the number of executions started so far, the original test included
+    #[allow(dead_code)]
+    pub fn attempts(&self) -> usize {
+        self.statuses.len() + usize::from(self.current.is_some())
+    }
+
+    /// Gets the number of retries that can still be started
+    #[allow(dead_code)]
+    pub fn retries_left(&self) -> u32 {
+        self.retries_left
+    }
+
+    /// Ends the running execution with a status and starts the next retry
+    ///
+    /// Returns `None` without ending the running execution once no retry is left, so it can
+    /// still be ended with `finish`.
+    #[allow(dead_code)]
+    pub fn next_attempt(&mut self, status: TestStatus) -> Option<&Test> {
+        if self.retries_left == 0 {
+            return None;
+        }
+        let previous = self.current.take()?;
+        previous.close(status);
+        self.statuses.push(status);
+        self.retries_left -= 1;
+
+        let retry = previous.get_suite().create_test(&*previous.name);
+        if let Some(parameters) = previous.get_string_tag(tags::TEST_PARAMETERS) {
+            retry.set_string_tag(tags::TEST_PARAMETERS, parameters);
+        }
+        retry.set_string_tag(tags::TEST_MANAGEMENT_IS_ATTEMPT_TO_FIX, "true");
+        retry.mark_retry(self.statuses.len() as i32, self.statuses[0]);
+        retry.set_retry_reason(RetryReason::AttemptToFix);
+        self.current = Some(retry);
+        self.current.as_ref()
+    }
+
+    /// Ends the last execution with a status, setting the verdict on it
+    #[allow(dead_code)]
+    pub fn finish(mut self, status: TestStatus) -> AttemptToFixVerdict {
+        self.end(status)
+    }
+
+    /// Ends the running execution with the verdict tags, if it wasn't already
+    fn end(&mut self, status: TestStatus) -> AttemptToFixVerdict {
+        if let Some(last) = self.current.take() {
+            self.statuses.push(status);
+            let verdict = AttemptToFixVerdict {
+                statuses: self.statuses.clone(),
+            };
+            last.set_string_tag(
+                tags::TEST_MANAGEMENT_ATTEMPT_TO_FIX_PASSED,
+                verdict.passed().to_string(),
+            );
+            if verdict.failed_all() {
+                last.set_string_tag(tags::TEST_HAS_FAILED_ALL_RETRIES, "true");
+            }
+            last.close(status);
+        }
+        AttemptToFixVerdict {
+            statuses: self.statuses.clone(),
+        }
+    }
+}
+
+impl Deref for AttemptToFixSession {
+    type Target = Test;
+
+    fn deref(&self) -> &Test {
+        self.current.as_ref().unwrap()
+    }
+}
+
+impl Drop for AttemptToFixSession {
+    fn drop(&mut self) {
+        self.end(TestStatus::Fail);
+    }
+}
+
+impl Test {
+    /// Begins an attempt-to-fix run with this test as the first execution
+    ///
+    /// The number of retries is the `attempt_to_fix_retries` of the test management settings.
+    /// Tags this test as attempted to be fixed. Each retry is a new test in the suite of this
+    /// test, a sibling of it rather than a child: no parent span is created, see the module
+    /// documentation for the shape of the spans. Use it for the tests flagged `attempt_to_fix` in
+    /// `TestOptimization::get_test_management_tests`, before closing this test.
+    #[allow(dead_code)]
+    pub fn begin_attempt_to_fix(&self) -> AttemptToFixSession {
+        let retries = TestOptimization::get_settings()
+            .test_management
+            .attempt_to_fix_retries;
+        self.begin_attempt_to_fix_with_retries(u32::try_from(retries).unwrap_or(0))
+    }
+
+    /// Begins an attempt-to-fix run with this test as the first execution and a given number
+    /// of retries
+    #[allow(dead_code)]
+    pub fn begin_attempt_to_fix_with_retries(&self, retries: u32) -> AttemptToFixSession {
+        self.set_string_tag(tags::TEST_MANAGEMENT_IS_ATTEMPT_TO_FIX, "true");
+        AttemptToFixSession {
+            current: Some(self.clone()),
+            retries_left: retries,
+            statuses: Vec::new(),
+        }
+    }
+}
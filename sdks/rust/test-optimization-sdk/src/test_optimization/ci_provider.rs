@@ -0,0 +1,167 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! CI provider module for detecting CI tags from environment variables
+//!
+//! The native library fills the CI tags for the providers it knows; this module
+//! provides a data-driven Rust-side detector so providers can be added without
+//! waiting for a native release.
+use crate::test_optimization::tags::*;
+use std::collections::HashMap;
+use std::env;
+
+#[derive(Debug, Clone, Copy)]
+/// Describes how to detect a CI provider and read its tags from the environment
+pub struct CiProviderSpec {
+    /// Value of the `ci.provider.name` tag
+    pub name: &'static str,
+    /// Environment variable whose presence identifies the provider
+    pub detect_var: &'static str,
+    /// Required value of `detect_var`, or `None` if any value identifies the provider
+    pub detect_value: Option<&'static str>,
+    /// Pairs of tag key and the environment variable providing its value
+    pub tags: &'static [(&'static str, &'static str)],
+}
+
+/// Known CI providers, checked in order
+pub const CI_PROVIDERS: &[CiProviderSpec] = &[
+    CiProviderSpec {
+        name: "github",
+        detect_var: "GITHUB_ACTIONS",
+        detect_value: None,
+        tags: &[
+            (CI_PIPELINE_ID, "GITHUB_RUN_ID"),
+            (CI_PIPELINE_NAME, "GITHUB_WORKFLOW"),
+            (CI_PIPELINE_NUMBER, "GITHUB_RUN_NUMBER"),
+            (CI_JOB_NAME, "GITHUB_JOB"),
+            (CI_WORKSPACE_PATH, "GITHUB_WORKSPACE"),
+            (GIT_COMMIT_SHA, "GITHUB_SHA"),
+        ],
+    },
+    CiProviderSpec {
+        name: "gitlab",
+        detect_var: "GITLAB_CI",
+        detect_value: None,
+        tags: &[
+            (CI_PIPELINE_ID, "CI_PIPELINE_ID"),
+            (CI_PIPELINE_NAME, "CI_PROJECT_PATH"),
+            (CI_PIPELINE_NUMBER, "CI_PIPELINE_IID"),
+            (CI_PIPELINE_URL, "CI_PIPELINE_URL"),
+            (CI_JOB_NAME, "CI_JOB_NAME"),
+            (CI_JOB_URL, "CI_JOB_URL"),
+            (CI_STAGE_NAME, "CI_JOB_STAGE"),
+            (CI_WORKSPACE_PATH, "CI_PROJECT_DIR"),
+            (GIT_BRANCH, "CI_COMMIT_BRANCH"),
+            (GIT_COMMIT_SHA, "CI_COMMIT_SHA"),
+            (GIT_REPOSITORY_URL, "CI_REPOSITORY_URL"),
+        ],
+    },
+    CiProviderSpec {
+        name: "circleci",
+        detect_var: "CIRCLECI",
+        detect_value: None,
+        tags: &[
+            (CI_PIPELINE_ID, "CIRCLE_WORKFLOW_ID"),
+            (CI_PIPELINE_NAME, "CIRCLE_PROJECT_REPONAME"),
+            (CI_JOB_NAME, "CIRCLE_JOB"),
+            (CI_JOB_URL, "CIRCLE_BUILD_URL"),
+            (CI_WORKSPACE_PATH, "CIRCLE_WORKING_DIRECTORY"),
+            (GIT_BRANCH, "CIRCLE_BRANCH"),
+            (GIT_COMMIT_SHA, "CIRCLE_SHA1"),
+            (GIT_REPOSITORY_URL, "CIRCLE_REPOSITORY_URL"),
+        ],
+    },
+    CiProviderSpec {
+        name: "buildkite",
+        detect_var: "BUILDKITE",
+        detect_value: None,
+        tags: &[
+            (CI_PIPELINE_ID, "BUILDKITE_BUILD_ID"),
+            (CI_PIPELINE_NAME, "BUILDKITE_PIPELINE_SLUG"),
+            (CI_PIPELINE_NUMBER, "BUILDKITE_BUILD_NUMBER"),
+            (CI_PIPELINE_URL, "BUILDKITE_BUILD_URL"),
+            (CI_JOB_NAME, "BUILDKITE_LABEL"),
+            (CI_NODE_NAME, "BUILDKITE_AGENT_NAME"),
+            (CI_WORKSPACE_PATH, "BUILDKITE_BUILD_CHECKOUT_PATH"),
+            (GIT_BRANCH, "BUILDKITE_BRANCH"),
+            (GIT_COMMIT_SHA, "BUILDKITE_COMMIT"),
+            (GIT_REPOSITORY_URL, "BUILDKITE_REPO"),
+        ],
+    },
+    CiProviderSpec {
+        name: "woodpecker",
+        detect_var: "CI",
+        detect_value: Some("woodpecker"),
+        tags: &[
+            (CI_PIPELINE_NAME, "CI_REPO"),
+            (CI_PIPELINE_NUMBER, "CI_PIPELINE_NUMBER"),
+            (CI_PIPELINE_URL, "CI_PIPELINE_URL"),
+            (CI_JOB_NAME, "CI_STEP_NAME"),
+            (CI_JOB_URL, "CI_STEP_URL"),
+            (CI_WORKSPACE_PATH, "CI_WORKSPACE"),
+            (GIT_BRANCH, "CI_COMMIT_BRANCH"),
+            (GIT_COMMIT_SHA, "CI_COMMIT_SHA"),
+            (GIT_REPOSITORY_URL, "CI_REPO_CLONE_URL"),
+        ],
+    },
+    CiProviderSpec {
+        name: "jenkins",
+        detect_var: "JENKINS_URL",
+        detect_value: None,
+        tags: &[
+            (CI_PIPELINE_ID, "BUILD_TAG"),
+            (CI_PIPELINE_NAME, "JOB_NAME"),
+            (CI_PIPELINE_NUMBER, "BUILD_NUMBER"),
+            (CI_PIPELINE_URL, "BUILD_URL"),
+            (CI_NODE_NAME, "NODE_NAME"),
+            (CI_WORKSPACE_PATH, "WORKSPACE"),
+            (GIT_BRANCH, "GIT_BRANCH"),
+            (GIT_COMMIT_SHA, "GIT_COMMIT"),
+            (GIT_REPOSITORY_URL, "GIT_URL"),
+        ],
+    },
+];
+
+#[derive(Debug, Clone)]
+/// Represents the Rust-side CI provider detector
+pub struct CiProvider;
+
+impl CiProvider {
+    /// Detects the CI tags of the current process environment using the known providers
+    ///
+    /// Returns an empty map when no provider is detected.
+    #[allow(dead_code)]
+    pub fn detect_tags() -> HashMap<String, String> {
+        Self::detect_tags_with(CI_PROVIDERS, |name| env::var(name).ok())
+    }
+
+    /// Detects the CI tags using the given providers and environment lookup
+    ///
+    /// The first provider whose detection variable matches is used; tags whose
+    /// variable is unset or empty are omitted.
+    #[allow(dead_code)]
+    pub fn detect_tags_with(
+        providers: &[CiProviderSpec],
+        env_lookup: impl Fn(&str) -> Option<String>,
+    ) -> HashMap<String, String> {
+        let mut tags = HashMap::new();
+        let provider = providers.iter().find(|provider| {
+            match (env_lookup(provider.detect_var), provider.detect_value) {
+                (Some(value), Some(expected)) => value == expected,
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        });
+        if let Some(provider) = provider {
+            tags.insert(CI_PROVIDER_NAME.to_string(), provider.name.to_string());
+            for (tag, var) in provider.tags {
+                if let Some(value) = env_lookup(var).filter(|value| !value.is_empty()) {
+                    tags.insert(tag.to_string(), value);
+                }
+            }
+        }
+        tags
+    }
+}
@@ -0,0 +1,287 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Coverage module for building code coverage payloads
+//!
+//! This module provides a builder for coverage payloads scoped to a test, a suite
+//! (e.g. coverage of its setup and teardown) or a whole session, and a batch sending many
+//! payloads at once with their filenames interned.
+use crate::test_optimization::lib::*;
+use crate::test_optimization::mock_tracer::mock_tracer_options;
+use crate::test_optimization::sdk_metrics;
+use crate::test_optimization::stats;
+use crate::test_optimization::utils::CloseFlag;
+use crate::test_optimization::*;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CString};
+use std::ptr::null_mut;
+
+#[derive(Debug, Clone)]
+/// Builds a code coverage payload with an explicit scope and sends it
+///
+/// The scope is set by the constructor, which only allows the combinations the backend
+/// attributes: a session (`for_session`), a suite of a session (`for_suite`), or a test of
+/// a suite of a session (`for_test`). A test is never attributed without its suite.
+///
+/// ```ignore
+/// CoverageBuilder::for_suite(&suite)
+///     .file("src/fixtures.rs")
+///     .file_with_bitmap("src/db.rs", &[0b0000_0110])
+///     .send();
+/// ```
+pub struct CoverageBuilder {
+    /// The session the coverage belongs to
+    session_id: u64,
+    /// The suite the coverage belongs to, 0 for session coverage
+    suite_id: u64,
+    /// The test the coverage belongs to, 0 for suite or session coverage
+    test_id: u64,
+    /// The closed flag of the handle the coverage belongs to
+    closed: CloseFlag,
+    /// The covered files with their optional line bitmaps, in order
+    files: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl CoverageBuilder {
+    /// Creates a builder for coverage of a test, like `Test::set_coverage_data`
+    #[allow(dead_code)]
+    pub fn for_test(test: &Test) -> Self {
+        Self::with_scope(test.session_id, test.suite_id, test.test_id, &test.closed)
+    }
+
+    /// Creates a builder for coverage of a suite not attributed to one of its tests
+    #[allow(dead_code)]
+    pub fn for_suite(suite: &TestSuite) -> Self {
+        Self::with_scope(suite.session_id, suite.suite_id, 0, &suite.closed)
+    }
+
+    /// Creates a builder for coverage of a session not attributed to a suite
+    #[allow(dead_code)]
+    pub fn for_session(session: &TestSession) -> Self {
+        Self::with_scope(session.session_id, 0, 0, &session.closed)
+    }
+
+    /// Creates a builder for the given scope
+    fn with_scope(session_id: u64, suite_id: u64, test_id: u64, closed: &CloseFlag) -> Self {
+        Self {
+            session_id,
+            suite_id,
+            test_id,
+            closed: closed.clone(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds a covered file
+    #[allow(dead_code)]
+    pub fn file(mut self, file: impl AsRef<str>) -> Self {
+        self.files.push((file.as_ref().to_string(), None));
+        self
+    }
+
+    /// Adds covered files
+    #[allow(dead_code)]
+    pub fn files(mut self, files: &[impl AsRef<str>]) -> Self {
+        self.files
+            .extend(files.iter().map(|file| (file.as_ref().to_string(), None)));
+        self
+    }
+
+    /// Adds a covered file with the bitmap of its covered lines (bit `n` for line `n + 1`)
+    #[allow(dead_code)]
+    pub fn file_with_bitmap(mut self, file: impl AsRef<str>, bitmap: &[u8]) -> Self {
+        self.files
+            .push((file.as_ref().to_string(), Some(bitmap.to_vec())));
+        self
+    }
+
+    /// Sends the coverage payload to the native library
+    ///
+    /// Returns `false` without sending anything when coverage isn't captured (see
+    /// `MockTracerOptions::capture_coverage`), or when the test, suite or session it belongs
+    /// to is closed.
+    #[allow(dead_code)]
+    pub fn send(&self) -> bool {
+        let mut batch = CoverageBatch::new();
+        batch.add(self);
+        batch.send() == 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Represents the filename bytes of a coverage batch, with and without interning
+pub struct CoverageBatchSize {
+    /// The bytes of the distinct filenames, each allocated once for the whole batch
+    #[allow(dead_code)]
+    pub interned_bytes: usize,
+    /// The bytes the filenames would take with one allocation per payload file
+    #[allow(dead_code)]
+    pub expanded_bytes: usize,
+}
+
+#[derive(Debug)]
+/// Represents a coverage payload of a batch, its files referring to the interned filenames
+struct InternedCoverage {
+    /// The session the coverage belongs to
+    session_id: u64,
+    /// The suite the coverage belongs to, 0 for session coverage
+    suite_id: u64,
+    /// The test the coverage belongs to, 0 for suite or session coverage
+    test_id: u64,
+    /// The closed flag of the handle the coverage belongs to
+    closed: CloseFlag,
+    /// The covered files as filename indexes with their optional line bitmaps, in order
+    files: Vec<(usize, Option<Vec<u8>>)>,
+}
+
+#[derive(Debug, Default)]
+/// Collects coverage payloads and sends them to the native library in a single call
+///
+/// Each distinct filename is converted to a C string once for the whole batch and the
+/// payloads refer to it by index, so thousands of tests covering the same files don't
+/// allocate and copy the same paths over and over.
+///
+/// The native coverage format has no interned form: `topt_send_code_coverage_payload` reads
+/// a filename per covered file, and the payload sent to the agent repeats it for each test.
+/// The interning therefore reduces what the SDK builds and hands over the FFI boundary,
+/// not the bytes sent to the agent. `size` measures the difference.
+///
+/// ```ignore
+/// let mut batch = CoverageBatch::new();
+/// for (test, files) in &covered {
+///     batch.add(&CoverageBuilder::for_test(test).files(files));
+/// }
+/// batch.send();
+/// ```
+pub struct CoverageBatch {
+    /// The distinct filenames, in order of first use
+    filenames: Vec<CString>,
+    /// The index in `filenames` of each filename
+    filename_indexes: HashMap<String, usize>,
+    /// The payloads, in order
+    payloads: Vec<InternedCoverage>,
+    /// The filename bytes with one allocation per payload file
+    expanded_bytes: usize,
+}
+
+impl CoverageBatch {
+    /// Creates an empty batch
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a coverage payload to the batch, interning its filenames
+    #[allow(dead_code)]
+    pub fn add(&mut self, coverage: &CoverageBuilder) -> &mut Self {
+        let files = coverage
+            .files
+            .iter()
+            .map(|(file, bitmap)| (self.intern(file), bitmap.clone()))
+            .collect();
+        self.payloads.push(InternedCoverage {
+            session_id: coverage.session_id,
+            suite_id: coverage.suite_id,
+            test_id: coverage.test_id,
+            closed: coverage.closed.clone(),
+            files,
+        });
+        self
+    }
+
+    /// Gets the number of payloads in the batch
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.payloads.len()
+    }
+
+    /// Gets whether the batch has no payloads
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.payloads.is_empty()
+    }
+
+    /// Gets the filename bytes of the batch, with and without interning
+    #[allow(dead_code)]
+    pub fn size(&self) -> CoverageBatchSize {
+        CoverageBatchSize {
+            interned_bytes: self
+                .filenames
+                .iter()
+                .map(|filename| filename.as_bytes_with_nul().len())
+                .sum(),
+            expanded_bytes: self.expanded_bytes,
+        }
+    }
+
+    /// Sends the payloads of the batch to the native library in a single call
+    ///
+    /// Returns the number of payloads sent, 0 when the batch is empty or coverage isn't
+    /// captured (see `MockTracerOptions::capture_coverage`). The payloads of a test, suite or
+    /// session closed by then are skipped, as the native library has freed its handle.
+    #[allow(dead_code)]
+    pub fn send(&self) -> usize {
+        if self.payloads.is_empty() || !mock_tracer_options().capture_coverage {
+            return 0;
+        }
+        let payloads: Vec<&InternedCoverage> = self
+            .payloads
+            .iter()
+            .filter(|payload| !payload.closed.is_closed())
+            .collect();
+        if payloads.is_empty() {
+            return 0;
+        }
+        // Keep the file arrays alive until the payloads are sent
+        let mut coverage_files: Vec<Vec<topt_TestCoverageFile>> = payloads
+            .iter()
+            .map(|payload| {
+                payload
+                    .files
+                    .iter()
+                    .map(|(index, bitmap)| topt_TestCoverageFile {
+                        filename: self.filenames[*index].as_ptr() as *mut c_char,
+                        bitmap: bitmap
+                            .as_ref()
+                            .map_or(null_mut(), |bitmap| bitmap.as_ptr() as *mut c_void),
+                        bitmap_len: bitmap.as_ref().map_or(0, Vec::len),
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut coverage_data: Vec<topt_TestCoverage> = payloads
+            .iter()
+            .zip(coverage_files.iter_mut())
+            .map(|(payload, files)| topt_TestCoverage {
+                session_id: payload.session_id,
+                suite_id: payload.suite_id,
+                test_id: payload.test_id,
+                files: files.as_mut_ptr(),
+                files_len: files.len(),
+            })
+            .collect();
+        sdk_metrics::time_ffi_call("topt_send_code_coverage_payload", || unsafe {
+            // Send the code coverage payloads
+            topt_send_code_coverage_payload(coverage_data.as_mut_ptr(), coverage_data.len());
+        });
+        for _ in &coverage_data {
+            stats::record_coverage_payload();
+        }
+        coverage_data.len()
+    }
+
+    /// Gets the index of a filename, converting it to a C string on first use
+    fn intern(&mut self, file: &str) -> usize {
+        // Each file would otherwise be a C string of its own, with its nul terminator
+        self.expanded_bytes += file.len() + 1;
+        if let Some(index) = self.filename_indexes.get(file) {
+            return *index;
+        }
+        self.filenames.push(CString::new(file).unwrap());
+        self.filename_indexes
+            .insert(file.to_string(), self.filenames.len() - 1);
+        self.filenames.len() - 1
+    }
+}
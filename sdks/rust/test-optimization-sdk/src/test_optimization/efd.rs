@@ -0,0 +1,264 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Early flake detection module for tracking the new tests of a session
+//!
+//! Tracking is opt-in: it starts on the first `TestSession::efd_status` call of a session,
+//! which fetches the settings and, when early flake detection is enabled, the known tests.
+//! From then on, each test created in the session is looked up in the known tests: a test
+//! the backend doesn't know is new and counts towards the faulty session detection. A session
+//! with too many new tests (e.g. a renamed module) is faulty, and early flake detection is off
+//! for the rest of the session. Sessions that never ask for their status cost nothing per test
+//! created, and the state of a session is dropped when it closes.
+use crate::test_optimization::registry;
+use crate::test_optimization::utils::CloseFlag;
+use crate::test_optimization::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents the live state of early flake detection in a session, see `TestSession::efd_status`
+pub struct EfdStatus {
+    /// Whether new tests should be retried: early flake detection and known tests are
+    /// enabled in the settings, and the session isn't faulty
+    #[allow(dead_code)]
+    pub enabled: bool,
+    /// Whether the session has too many new tests, which turns early flake detection off
+    #[allow(dead_code)]
+    pub faulty: bool,
+    /// Number of distinct new tests created in the session since it is tracked
+    #[allow(dead_code)]
+    pub new_tests_seen: u64,
+    /// Number of known tests, the base of the faulty session threshold
+    #[allow(dead_code)]
+    pub known_tests: u64,
+    /// The `faulty_session_threshold` of the settings, a percentage of the known tests
+    #[allow(dead_code)]
+    pub threshold: i32,
+}
+
+impl EfDSettings {
+    /// Whether a session with the given number of new and known tests is faulty
+    ///
+    /// `faulty_session_threshold` is a ratio, not a count: it is the percentage of the known
+    /// tests the new tests can reach, so the session is faulty once `new_tests` is strictly
+    /// greater than `faulty_session_threshold`% of `known_tests` (a negative threshold counts
+    /// as 0). Without known tests, any new test makes the session faulty.
+    #[allow(dead_code)]
+    pub fn is_faulty_session(&self, new_tests: u64, known_tests: u64) -> bool {
+        let threshold = u64::try_from(self.faulty_session_threshold).unwrap_or(0);
+        u128::from(new_tests) * 100 > u128::from(threshold) * u128::from(known_tests)
+    }
+}
+
+/// Early flake detection state of a session
+struct EfdState {
+    /// The early flake detection settings
+    settings: EfDSettings,
+    /// Whether early flake detection and known tests are enabled in the settings
+    active: bool,
+    /// The known tests, as `(module, suite, test)`
+    known_tests: HashSet<(String, String, String)>,
+    /// The new tests created so far, as `(module, suite, test)`, with their number of
+    /// creations not discarded
+    new_tests: HashMap<(String, String, String), usize>,
+    /// Whether the session was found faulty, which it stays
+    faulty: bool,
+}
+
+impl EfdState {
+    /// Fetches the settings and, if early flake detection is active, the known tests
+    fn fetch() -> Self {
+        let settings = TestOptimization::get_settings();
+        let active = settings.early_flake_detection.enabled && settings.known_tests_enabled;
+        let known_tests = if active {
+            TestOptimization::get_known_tests()
+                .into_iter()
+                .flat_map(|(module, suites)| {
+                    suites.into_iter().flat_map(move |(suite, tests)| {
+                        let module = module.clone();
+                        tests.into_iter().map(move |test| (module.clone(), suite.clone(), test))
+                    })
+                })
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        Self {
+            settings: settings.early_flake_detection,
+            active,
+            known_tests,
+            new_tests: HashMap::new(),
+            faulty: false,
+        }
+    }
+
+    /// Gets the status of early flake detection
+    fn status(&self) -> EfdStatus {
+        EfdStatus {
+            enabled: self.active && !self.faulty,
+            faulty: self.faulty,
+            new_tests_seen: self.new_tests.len() as u64,
+            known_tests: self.known_tests.len() as u64,
+            threshold: self.settings.faulty_session_threshold,
+        }
+    }
+}
+
+/// Early flake detection states of the tracked sessions, by session ID
+static EFD_STATES: Mutex<BTreeMap<u64, EfdState>> = Mutex::new(BTreeMap::new());
+
+/// Number of tracked sessions, so the tests of untracked ones don't take the lock
+static TRACKED_SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Locks the states, recovering them if a panicking thread poisoned the lock
+fn lock_states() -> MutexGuard<'static, BTreeMap<u64, EfdState>> {
+    EFD_STATES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Gets the key of a test in the known tests, as `(module, suite, test)`
+fn test_key(test: &Test) -> (String, String, String) {
+    let module_name = registry::name(test.module_id).unwrap_or_default();
+    (module_name, test.suite_name.to_string(), test.name.to_string())
+}
+
+/// Starts tracking a session, unless it already is, and gets its status
+fn track(session_id: u64, state: EfdState) -> EfdStatus {
+    lock_states()
+        .entry(session_id)
+        .or_insert_with(|| {
+            TRACKED_SESSIONS.fetch_add(1, Ordering::Relaxed);
+            state
+        })
+        .status()
+}
+
+/// Counts a test created in a tracked session if it isn't known
+fn record_created(session_id: u64, key: (String, String, String)) {
+    let mut states = lock_states();
+    let Some(state) = states.get_mut(&session_id) else {
+        return;
+    };
+    if !state.active || state.known_tests.contains(&key) {
+        return;
+    }
+    *state.new_tests.entry(key).or_default() += 1;
+    let (new_tests, known_tests) = (state.new_tests.len(), state.known_tests.len());
+    state.faulty |= state
+        .settings
+        .is_faulty_session(new_tests as u64, known_tests as u64);
+}
+
+/// Uncounts a test discarded in a tracked session; a session found faulty stays faulty
+fn record_discarded(session_id: u64, key: &(String, String, String)) {
+    let mut states = lock_states();
+    let Some(state) = states.get_mut(&session_id) else {
+        return;
+    };
+    if let Some(creations) = state.new_tests.get_mut(key) {
+        *creations -= 1;
+        if *creations == 0 {
+            state.new_tests.remove(key);
+        }
+    }
+}
+
+/// Records a test being created, counting it as new if its session is tracked and it isn't known
+pub(in crate::test_optimization) fn record_test_created(test: &Test) {
+    if TRACKED_SESSIONS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    record_created(test.session_id, test_key(test));
+}
+
+/// Records a test being discarded, which no longer counts as new
+pub(in crate::test_optimization) fn record_test_discarded(test: &Test) {
+    if TRACKED_SESSIONS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    record_discarded(test.session_id, &test_key(test));
+}
+
+/// Gets the status of early flake detection in a session, starting to track it if needed
+///
+/// The settings and known tests are fetched without holding the lock, since they may be
+/// requested from the backend. A closed session isn't tracked again.
+pub(in crate::test_optimization) fn status(session_id: u64, closed: &CloseFlag) -> EfdStatus {
+    if let Some(state) = lock_states().get(&session_id) {
+        return state.status();
+    }
+    let state = EfdState::fetch();
+    if closed.is_closed() {
+        return state.status();
+    }
+    track(session_id, state)
+}
+
+/// Stops tracking a closed session
+pub(in crate::test_optimization) fn forget_session(session_id: u64) {
+    if lock_states().remove(&session_id).is_some() {
+        TRACKED_SESSIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(test: &str) -> (String, String, String) {
+        ("module".to_string(), "suite".to_string(), test.to_string())
+    }
+
+    #[test]
+    fn a_tracked_session_turns_faulty_as_new_tests_are_created() {
+        let session_id = u64::MAX;
+        record_created(session_id, key("untracked"));
+        let status = track(
+            session_id,
+            EfdState {
+                settings: EfDSettings {
+                    enabled: true,
+                    slow_test_retries: EfdSlowTestRetriesSettings { five_m: 0, thirty_s: 0, ten_s: 0, five_s: 0 },
+                    faulty_session_threshold: 50,
+                },
+                active: true,
+                known_tests: HashSet::from([key("known_a"), key("known_b")]),
+                new_tests: HashMap::new(),
+                faulty: false,
+            },
+        );
+        assert!(status.enabled && !status.faulty);
+        assert_eq!((status.new_tests_seen, status.known_tests), (0, 2));
+
+        record_created(session_id, key("known_a"));
+        record_created(session_id, key("new_a"));
+        record_created(session_id, key("new_a"));
+        let status = status_of(session_id);
+        assert!(status.enabled && !status.faulty);
+        assert_eq!(status.new_tests_seen, 1);
+
+        // 2 new tests are over 50% of the 2 known tests
+        record_created(session_id, key("new_b"));
+        let status = status_of(session_id);
+        assert!(!status.enabled && status.faulty);
+        assert_eq!(status.new_tests_seen, 2);
+
+        // discarding uncounts a test once all its creations are discarded, the session stays faulty
+        record_discarded(session_id, &key("new_a"));
+        assert_eq!(status_of(session_id).new_tests_seen, 2);
+        record_discarded(session_id, &key("new_a"));
+        let status = status_of(session_id);
+        assert!(!status.enabled && status.faulty);
+        assert_eq!(status.new_tests_seen, 1);
+
+        forget_session(session_id);
+        assert!(!lock_states().contains_key(&session_id));
+    }
+
+    fn status_of(session_id: u64) -> EfdStatus {
+        lock_states()[&session_id].status()
+    }
+}
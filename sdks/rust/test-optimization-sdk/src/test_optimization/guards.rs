@@ -0,0 +1,111 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Guards module for scoped suites and tests
+//!
+//! This module provides RAII guards that tie the close status of a suite or a
+//! test to the scope exit: `finish` closes with an explicit status, while
+//! dropping the guard without finishing records a failure.
+use crate::test_optimization::*;
+use std::ops::Deref;
+
+#[derive(Debug)]
+/// Represents a test suite that is closed when the guard goes out of scope
+///
+/// ```ignore
+/// let suite = module.create_test_suite_guard("my_suite");
+/// let test = suite.create_test_guard("my_test");
+/// // ... run the test ...
+/// test.finish(TestStatus::Pass);
+/// suite.finish(TestStatus::Pass);
+/// ```
+pub struct SuiteGuard {
+    /// The guarded suite, `None` once finished
+    suite: Option<TestSuite>,
+}
+impl SuiteGuard {
+    /// Creates a guard for the given suite
+    #[allow(dead_code)]
+    pub fn new(suite: TestSuite) -> Self {
+        Self { suite: Some(suite) }
+    }
+
+    /// Closes the guarded suite with the given status, see `TestSuite::close_with_status`
+    #[allow(dead_code)]
+    pub fn finish(mut self, status: TestStatus) -> bool {
+        self.suite.take().unwrap().close_with_status(status)
+    }
+}
+
+impl Deref for SuiteGuard {
+    type Target = TestSuite;
+
+    fn deref(&self) -> &TestSuite {
+        self.suite.as_ref().unwrap()
+    }
+}
+
+impl Drop for SuiteGuard {
+    fn drop(&mut self) {
+        if let Some(suite) = self.suite.take() {
+            suite.close_with_status(TestStatus::Fail);
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Represents a test that is closed when the guard goes out of scope
+///
+/// Dropping the guard without calling `finish` (an early return or a panic
+/// while running the test) closes the test as `Fail`.
+pub struct TestGuard {
+    /// The guarded test, `None` once finished
+    test: Option<Test>,
+}
+impl TestGuard {
+    /// Creates a guard for the given test
+    #[allow(dead_code)]
+    pub fn new(test: Test) -> Self {
+        Self { test: Some(test) }
+    }
+
+    /// Closes the guarded test with the given status
+    #[allow(dead_code)]
+    pub fn finish(mut self, status: TestStatus) -> bool {
+        self.test.take().unwrap().close(status)
+    }
+}
+
+impl Deref for TestGuard {
+    type Target = Test;
+
+    fn deref(&self) -> &Test {
+        self.test.as_ref().unwrap()
+    }
+}
+
+impl Drop for TestGuard {
+    fn drop(&mut self) {
+        if let Some(test) = self.test.take() {
+            test.close(TestStatus::Fail);
+        }
+    }
+}
+
+impl TestModule {
+    /// Create a new test suite wrapped in a guard
+    #[allow(dead_code)]
+    pub fn create_test_suite_guard(&self, name: impl AsRef<str>) -> SuiteGuard {
+        SuiteGuard::new(self.create_test_suite(name))
+    }
+}
+
+impl TestSuite {
+    /// Creates a new test wrapped in a guard
+    #[allow(dead_code)]
+    pub fn create_test_guard(&self, name: impl AsRef<str>) -> TestGuard {
+        TestGuard::new(self.create_test(name))
+    }
+}
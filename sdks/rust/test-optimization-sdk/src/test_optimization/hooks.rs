@@ -0,0 +1,98 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Hooks module for callbacks invoked on test events
+//!
+//! This module keeps the callbacks registered with `TestOptimization::on_test_closed`
+//! so test results can be fanned out to other sinks (e.g. JUnit XML) without
+//! wrapping every call site.
+use crate::test_optimization::registry::OpenHandle;
+use crate::test_optimization::*;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+/// Represents a test that has just been closed, as passed to the `on_test_closed` callbacks
+pub struct ClosedTestInfo {
+    /// The ID of the test
+    #[allow(dead_code)]
+    pub test_id: u64,
+    /// The name of the test
+    #[allow(dead_code)]
+    pub name: String,
+    /// The name of the suite, if it was still open when the test was closed
+    #[allow(dead_code)]
+    pub suite_name: Option<String>,
+    /// The status the test was closed with
+    #[allow(dead_code)]
+    pub status: TestStatus,
+    /// The skip reason, if the test was closed with one
+    #[allow(dead_code)]
+    pub skip_reason: Option<String>,
+    /// The time between the creation of the test and its close
+    #[allow(dead_code)]
+    pub duration: Duration,
+    /// String tags set through the SDK
+    #[allow(dead_code)]
+    pub string_tags: HashMap<String, String>,
+    /// Numeric tags set through the SDK
+    #[allow(dead_code)]
+    pub number_tags: HashMap<String, f64>,
+}
+
+/// Callback invoked when a test is closed
+type TestClosedCallback = Box<dyn Fn(&ClosedTestInfo) + Send + Sync>;
+
+/// Callbacks registered with `TestOptimization::on_test_closed`
+static TEST_CLOSED_CALLBACKS: RwLock<Vec<TestClosedCallback>> = RwLock::new(Vec::new());
+
+/// Invokes the `on_test_closed` callbacks for a test removed from the registry
+pub(in crate::test_optimization) fn notify_test_closed(
+    test_id: u64,
+    handle: Option<OpenHandle>,
+    status: TestStatus,
+    skip_reason: Option<&str>,
+) {
+    let callbacks = TEST_CLOSED_CALLBACKS
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+    if callbacks.is_empty() {
+        return;
+    }
+    // the registry only knows the handles created through the SDK
+    let Some(handle) = handle else {
+        return;
+    };
+    let info = ClosedTestInfo {
+        test_id,
+        suite_name: registry::name(handle.parent_id),
+        name: handle.name,
+        status,
+        skip_reason: skip_reason.map(str::to_string),
+        duration: handle.opened_at.elapsed(),
+        string_tags: handle.string_tags,
+        number_tags: handle.number_tags,
+    };
+    for callback in callbacks.iter() {
+        callback(&info);
+    }
+}
+
+impl TestOptimization {
+    /// Registers a callback invoked whenever a test is closed
+    ///
+    /// Callbacks are invoked synchronously, in registration order, on the thread closing the
+    /// test and after the native close; they may run concurrently when tests are closed
+    /// from several threads. Tags are the ones set through the SDK. A callback must not
+    /// register another callback, and a panic in a callback propagates to the closing thread.
+    #[allow(dead_code)]
+    pub fn on_test_closed(callback: impl Fn(&ClosedTestInfo) + Send + Sync + 'static) {
+        TEST_CLOSED_CALLBACKS
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Box::new(callback));
+    }
+}
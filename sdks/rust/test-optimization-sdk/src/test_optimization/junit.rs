@@ -0,0 +1,138 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! JUnit module for exporting the mock tracer spans as JUnit XML
+//!
+//! Test spans are the spans carrying a `test.name` tag; they are grouped into
+//! test suites by their `test.suite` tag, and their `test.status` tag decides
+//! whether a failure or skipped element is emitted.
+use crate::test_optimization::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Escapes a string for an XML attribute value
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            c if (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Gets the duration of a span, zero when it finished before it started
+fn span_duration(span: &MockSpan) -> Duration {
+    span.finish_time
+        .duration_since(span.start_time)
+        .unwrap_or_default()
+}
+
+/// Gets a string tag of a span, empty when missing
+fn tag<'a>(span: &'a MockSpan, key: &str) -> &'a str {
+    span.string_tags.get(key).map_or("", String::as_str)
+}
+
+impl MockTracer {
+    /// Exports the finished test spans as JUnit XML
+    #[allow(dead_code)]
+    pub fn to_junit_xml() -> String {
+        Self::spans_to_junit_xml(&Self::get_finished_spans())
+    }
+
+    /// Exports the test spans among the given spans as JUnit XML
+    ///
+    /// Suites are sorted by name and test cases by start time, so the output is stable.
+    /// Failed tests get a `failure` element with the error type and message of the span,
+    /// skipped tests a `skipped` element with the skip reason.
+    #[allow(dead_code)]
+    pub fn spans_to_junit_xml(spans: &[MockSpan]) -> String {
+        let mut suites: BTreeMap<&str, Vec<&MockSpan>> = BTreeMap::new();
+        for span in spans.iter().filter(|span| span.string_tags.contains_key(tags::TEST_NAME)) {
+            suites.entry(tag(span, tags::TEST_SUITE)).or_default().push(span);
+        }
+
+        let count = |tests: &[&MockSpan], status: &str| {
+            tests
+                .iter()
+                .filter(|span| tag(span, tags::TEST_STATUS) == status)
+                .count()
+        };
+        let total_time = |tests: &[&MockSpan]| {
+            tests
+                .iter()
+                .map(|span| span_duration(span))
+                .sum::<Duration>()
+                .as_secs_f64()
+        };
+
+        let all_tests: Vec<&MockSpan> = suites.values().flatten().copied().collect();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            all_tests.len(),
+            count(&all_tests, "fail"),
+            count(&all_tests, "skip"),
+            total_time(&all_tests)
+        );
+        for (suite_name, tests) in &mut suites {
+            tests.sort_by(|a, b| {
+                a.start_time
+                    .cmp(&b.start_time)
+                    .then_with(|| tag(a, tags::TEST_NAME).cmp(tag(b, tags::TEST_NAME)))
+            });
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                xml_escape(suite_name),
+                tests.len(),
+                count(tests, "fail"),
+                count(tests, "skip"),
+                total_time(tests)
+            );
+            for span in tests.iter() {
+                let _ = write!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                    xml_escape(tag(span, tags::TEST_NAME)),
+                    xml_escape(suite_name),
+                    span_duration(span).as_secs_f64()
+                );
+                match tag(span, tags::TEST_STATUS) {
+                    "fail" => {
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <failure type=\"{}\" message=\"{}\"/>\n    </testcase>",
+                            xml_escape(tag(span, tags::ERROR_TYPE)),
+                            xml_escape(tag(span, tags::ERROR_MESSAGE))
+                        );
+                    }
+                    "skip" => {
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <skipped message=\"{}\"/>\n    </testcase>",
+                            xml_escape(tag(span, tags::TEST_SKIP_REASON))
+                        );
+                    }
+                    _ => xml.push_str("/>\n"),
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
@@ -0,0 +1,124 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Lcov module for importing code coverage from lcov reports
+//!
+//! `cargo llvm-cov --lcov`, `grcov -t lcov` and `llvm-cov export -format=lcov` all write lcov
+//! reports, so this is the format supported; Cobertura XML isn't. Only the `SF`, `DA` and
+//! `end_of_record` lines are read: the covered lines of each file become a line bitmap, and
+//! function and branch records are ignored.
+use crate::test_optimization::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Represents the covered lines of an lcov report
+pub struct LcovReport {
+    /// The lines hit at least once, by source file as written in the report
+    #[allow(dead_code)]
+    pub files: BTreeMap<String, BTreeSet<u32>>,
+}
+
+impl LcovReport {
+    /// Parses the contents of an lcov report
+    ///
+    /// Lines that can't be parsed are skipped, and a file appearing in several records gets
+    /// the lines of all of them. Files without any line hit aren't kept.
+    #[allow(dead_code)]
+    pub fn parse(contents: &str) -> Self {
+        let mut files: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+        let mut current_file: Option<&str> = None;
+        for line in contents.lines().map(str::trim) {
+            if let Some(file) = line.strip_prefix("SF:") {
+                current_file = Some(file);
+            } else if line == "end_of_record" {
+                current_file = None;
+            } else if let (Some(file), Some(data)) = (current_file, line.strip_prefix("DA:")) {
+                let mut fields = data.split(',');
+                let line_number = fields.next().and_then(|value| value.parse::<u32>().ok());
+                let hits = fields.next().and_then(|value| value.parse::<u64>().ok());
+                if let (Some(line_number @ 1..), Some(1..)) = (line_number, hits) {
+                    files.entry(file.to_string()).or_default().insert(line_number);
+                }
+            }
+        }
+        Self { files }
+    }
+
+    /// Reads and parses an lcov report file
+    #[allow(dead_code)]
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Gets the bitmap of the covered lines of a file (bit `n` for line `n + 1`), `None` if
+    /// the file isn't in the report
+    #[allow(dead_code)]
+    pub fn bitmap(&self, file: &str) -> Option<Vec<u8>> {
+        let lines = self.files.get(file)?;
+        let mut bitmap = vec![0u8; lines.last().map_or(0, |last| (*last as usize).div_ceil(8))];
+        for line in lines {
+            let bit = (*line - 1) as usize;
+            bitmap[bit / 8] |= 1 << (bit % 8);
+        }
+        Some(bitmap)
+    }
+
+    /// Makes the absolute paths under `root` relative to it, like the paths of
+    /// `Test::set_coverage_data`; the lines of paths ending up the same are merged
+    #[allow(dead_code)]
+    pub fn relative_to(&self, root: &Path) -> Self {
+        let mut files: BTreeMap<String, BTreeSet<u32>> = BTreeMap::new();
+        for (file, lines) in &self.files {
+            let file = Path::new(file)
+                .strip_prefix(root)
+                .map_or_else(|_| file.clone(), |relative| relative.to_string_lossy().into_owned());
+            files.entry(file).or_default().extend(lines);
+        }
+        Self { files }
+    }
+}
+
+impl Test {
+    /// Sends the coverage of this test from an lcov report, for the files the test covers
+    ///
+    /// `files` are relative to `TestOptimization::resolved_working_directory`, like the paths
+    /// of `set_coverage_data`; the absolute paths of the report under it are made relative
+    /// to match them. Each file hit in the report is sent with its line bitmap, in a single
+    /// coverage payload, and files without any line hit in the report are skipped.
+    ///
+    /// Returns the number of files sent, 0 without sending anything when none of `files` was
+    /// hit. Fails if the report can't be read or the payload wasn't sent (coverage isn't
+    /// captured, see `MockTracerOptions::capture_coverage`).
+    #[allow(dead_code)]
+    pub fn set_coverage_from_lcov(
+        &self,
+        path: &Path,
+        files: &[impl AsRef<str>],
+    ) -> io::Result<usize> {
+        let mut report = LcovReport::read(path)?;
+        if let Some(root) = TestOptimization::resolved_working_directory() {
+            report = report.relative_to(&root);
+        }
+        let files: BTreeSet<&str> = files.iter().map(AsRef::as_ref).collect();
+        let mut coverage = CoverageBuilder::for_test(self);
+        let mut sent_files = 0;
+        for file in files {
+            if let Some(bitmap) = report.bitmap(file) {
+                coverage = coverage.file_with_bitmap(file, &bitmap);
+                sent_files += 1;
+            }
+        }
+        if sent_files == 0 {
+            return Ok(0);
+        }
+        if !coverage.send() {
+            return Err(io::Error::other("the coverage payload of the lcov report wasn't sent"));
+        }
+        Ok(sent_files)
+    }
+}
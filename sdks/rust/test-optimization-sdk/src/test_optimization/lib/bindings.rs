@@ -97,6 +97,7 @@ pub struct topt_InitOptions {
 }
 
 #[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct topt_UnixTime {
     pub sec: Uint64,
     pub nsec: Uint64,
@@ -234,6 +235,14 @@ pub struct topt_SpanResult {
     pub valid: Bool,
 }
 
+#[repr(C)]
+pub struct topt_TraceContextResult {
+    pub trace_id_upper: Uint64,
+    pub trace_id_lower: Uint64,
+    pub span_id: topt_TslvId,
+    pub valid: Bool,
+}
+
 #[repr(C)]
 pub struct topt_MockSpan {
     pub span_id: topt_TslvId,
@@ -252,10 +261,40 @@ pub struct topt_MockSpanArray {
     pub len: usize,
 }
 
+// Layout assertions
+//
+// These structs are passed by value or indexed in native arrays, so a mismatch with the
+// C layout is undefined behavior. Sizes are the ones of the C header on 64-bit targets
+// (the only ones the native library is built for); update them together with the header.
+// New native fields are expected to take over the reserved `unusedXX` slots instead of
+// growing the structs.
+#[cfg(target_pointer_width = "64")]
+const _: () = {
+    use std::mem::{offset_of, size_of};
+    assert!(size_of::<topt_KeyValuePair>() == 16);
+    assert!(size_of::<topt_KeyNumberPair>() == 16);
+    assert!(size_of::<topt_InitOptions>() == 96);
+    assert!(size_of::<topt_TestCloseOptions>() == 64);
+    assert!(size_of::<topt_SettingsEarlyFlakeDetection>() == 24);
+    assert!(size_of::<topt_SettingsTestManagement>() == 8);
+    assert!(size_of::<topt_SettingsResponse>() == 88);
+    assert!(offset_of!(topt_SettingsResponse, unused01) == 48);
+    assert!(size_of::<topt_FlakyTestRetriesSettings>() == 8);
+    assert!(size_of::<topt_KnownTest>() == 24);
+    assert!(size_of::<topt_SkippableTest>() == 32);
+    assert!(size_of::<topt_TestCoverageFile>() == 24);
+    assert!(size_of::<topt_TestCoverage>() == 40);
+    assert!(size_of::<topt_TestManagementTestProperties>() == 32);
+    assert!(size_of::<topt_SpanStartOptions>() == 56);
+    assert!(size_of::<topt_MockSpan>() == 96);
+};
+
 unsafe extern "C" {
     // Library initialization and shutdown functions
     pub fn topt_initialize(options: topt_InitOptions) -> Bool;
     pub fn topt_shutdown() -> Bool;
+    pub fn topt_flush() -> Bool;
+    pub fn topt_get_version() -> *const c_char;
 
     // Settings and configuration functions
     pub fn topt_get_settings() -> topt_SettingsResponse;
@@ -301,6 +340,7 @@ unsafe extern "C" {
     // Test functions
     pub fn topt_test_create(suite_id: topt_SuiteId, name: *mut c_char, start_time: *mut topt_UnixTime) -> topt_TestResult;
     pub fn topt_test_close(test_id: topt_TestId, options: topt_TestCloseOptions) -> Bool;
+    pub fn topt_test_discard(test_id: topt_TestId) -> Bool;
     pub fn topt_test_set_string_tag(test_id: topt_TestId, key: *mut c_char, value: *mut c_char) -> Bool;
     pub fn topt_test_set_number_tag(test_id: topt_TestId, key: *mut c_char, value: c_double) -> Bool;
     pub fn topt_test_set_error(test_id: topt_TestId, error_type: *mut c_char, error_message: *mut c_char, error_stacktrace: *mut c_char) -> Bool;
@@ -315,6 +355,7 @@ unsafe extern "C" {
     pub fn topt_span_set_string_tag(span_id: topt_TslvId, key: *mut c_char, value: *mut c_char) -> Bool;
     pub fn topt_span_set_number_tag(span_id: topt_TslvId, key: *mut c_char, value: c_double) -> Bool;
     pub fn topt_span_set_error(span_id: topt_TslvId, error_type: *mut c_char, error_message: *mut c_char, error_stacktrace: *mut c_char) -> Bool;
+    pub fn topt_get_trace_context(tslv_id: topt_TslvId) -> topt_TraceContextResult;
 
     // Debug mock tracer functions
     pub fn topt_debug_mock_tracer_reset() -> Bool;
@@ -3,6 +3,10 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/).
 // Copyright 2025 Datadog, Inc.
 
+// The Go runtime of the static library is started by _rt0_amd64_windows_lib, which the Go
+// linker registers in .ctors: the MinGW CRT runs it before main, but MSVC ignores .ctors, so
+// it's registered as a CRT initializer here. This is the only place starting the runtime,
+// starting it twice crashes it.
 #ifdef _MSC_VER
 __pragma(comment(lib, "legacy_stdio_definitions.lib"));
 
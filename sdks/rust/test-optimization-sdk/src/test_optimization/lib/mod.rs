@@ -3,9 +3,16 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/).
 // Copyright 2025 Datadog, Inc.
 
-mod cgo;
+#[cfg(not(feature = "regenerate-bindings"))]
 mod bindings;
+#[cfg(feature = "regenerate-bindings")]
+mod bindings {
+    #![allow(non_camel_case_types)]
+    #![allow(non_upper_case_globals)]
+    #![allow(non_snake_case)]
+    #![allow(dead_code)]
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
 
 pub(in crate::test_optimization) use bindings::*;
-#[cfg(target_os = "windows")]
-pub(in crate::test_optimization) use cgo::*;
+pub use bindings::topt_UnixTime;
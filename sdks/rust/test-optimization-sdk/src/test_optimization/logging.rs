@@ -0,0 +1,152 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Logging module for controlling the SDK and native library log output
+//!
+//! The native library has no log callback: its logger is configured from environment
+//! variables read on init, so the level and log directory are handed over as environment
+//! variables like the default service. The SDK's own messages honor the same level and can
+//! be written to any `Write` instead of stderr.
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Represents the verbosity of the SDK and native library logs, from quietest to noisiest
+///
+/// The native library logger only has a debug switch and a startup log switch, so below
+/// `Info` it still logs its own warnings and errors; `TestOptimization::set_log_directory`
+/// moves them out of stderr.
+pub enum LogLevel {
+    /// No SDK messages, and no native startup logs
+    Off,
+    /// SDK errors, and no native startup logs
+    Error,
+    /// SDK warnings and errors, and no native startup logs
+    Warn,
+    /// Like `Warn`, plus the startup configuration logged by the native library (the default)
+    Info,
+    /// Everything, including the native library debug logs (`DD_TRACE_DEBUG`)
+    Debug,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        })
+    }
+}
+
+/// Level set with `TestOptimization::set_log_level`, `None` to leave the environment as is
+static LOG_LEVEL: Mutex<Option<LogLevel>> = Mutex::new(None);
+
+/// Directory set with `TestOptimization::set_log_directory`
+static LOG_DIRECTORY: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Writer set with `TestOptimization::set_log_writer`, stderr when unset
+static LOG_WRITER: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Environment variable enabling the native library debug logs
+const DD_TRACE_DEBUG_ENV: &str = "DD_TRACE_DEBUG";
+/// Environment variable enabling the native library startup logs
+const DD_TRACE_STARTUP_LOGS_ENV: &str = "DD_TRACE_STARTUP_LOGS";
+/// Environment variable making the native library log to a file in a directory
+const DD_TRACE_LOG_DIRECTORY_ENV: &str = "DD_TRACE_LOG_DIRECTORY";
+
+/// Sets the log level
+pub(in crate::test_optimization) fn set_level(level: LogLevel) {
+    *LOG_LEVEL.lock().unwrap_or_else(|e| e.into_inner()) = Some(level);
+}
+
+/// Gets the log level, `Info` unless set
+pub(in crate::test_optimization) fn level() -> LogLevel {
+    LOG_LEVEL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .unwrap_or(LogLevel::Info)
+}
+
+/// Sets the directory of the native library log file
+pub(in crate::test_optimization) fn set_directory(directory: PathBuf) {
+    *LOG_DIRECTORY.lock().unwrap_or_else(|e| e.into_inner()) = Some(directory);
+}
+
+/// Sets the writer of the SDK messages
+pub(in crate::test_optimization) fn set_writer(writer: Box<dyn Write + Send>) {
+    *LOG_WRITER.lock().unwrap_or_else(|e| e.into_inner()) = Some(writer);
+}
+
+/// Gets the environment variables configuring the native library logger
+///
+/// Only what was set explicitly is returned, so `DD_TRACE_DEBUG` and friends set in the
+/// environment still apply when the level and directory were never set.
+pub(in crate::test_optimization) fn environment() -> Vec<(String, String)> {
+    let mut environment = Vec::new();
+    if let Some(level) = *LOG_LEVEL.lock().unwrap_or_else(|e| e.into_inner()) {
+        environment.push((DD_TRACE_DEBUG_ENV.to_string(), (level == LogLevel::Debug).to_string()));
+        environment
+            .push((DD_TRACE_STARTUP_LOGS_ENV.to_string(), (level >= LogLevel::Info).to_string()));
+    }
+    if let Some(directory) = LOG_DIRECTORY.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        environment.push((
+            DD_TRACE_LOG_DIRECTORY_ENV.to_string(),
+            directory.to_string_lossy().into_owned(),
+        ));
+    }
+    environment
+}
+
+/// Writes an SDK message at the given level, if the log level lets it through
+pub(in crate::test_optimization) fn write(level: LogLevel, message: fmt::Arguments) {
+    let max_level = self::level();
+    let mut writer = LOG_WRITER.lock().unwrap_or_else(|e| e.into_inner());
+    // A failing log writer has nowhere to report to, so the message is dropped
+    let _ = match writer.as_mut() {
+        Some(writer) => write_message(writer, max_level, level, message),
+        None => write_message(&mut io::stderr(), max_level, level, message),
+    };
+}
+
+/// Writes an SDK message at the given level to a writer, if the maximum level lets it through
+fn write_message(
+    writer: &mut dyn Write,
+    max_level: LogLevel,
+    level: LogLevel,
+    message: fmt::Arguments,
+) -> io::Result<()> {
+    if level == LogLevel::Off || level > max_level {
+        return Ok(());
+    }
+    writeln!(writer, "test-optimization-sdk: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_honor_the_log_level() {
+        let mut output = Vec::new();
+        write_message(&mut output, LogLevel::Off, LogLevel::Error, format_args!("hidden")).unwrap();
+        write_message(&mut output, LogLevel::Error, LogLevel::Warn, format_args!("hidden")).unwrap();
+        write_message(&mut output, LogLevel::Debug, LogLevel::Off, format_args!("hidden")).unwrap();
+        assert!(output.is_empty());
+        write_message(&mut output, LogLevel::Error, LogLevel::Error, format_args!("failed: {}", 1))
+            .unwrap();
+        write_message(&mut output, LogLevel::Debug, LogLevel::Info, format_args!("info")).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "test-optimization-sdk: failed: 1\ntest-optimization-sdk: info\n"
+        );
+        assert!(LogLevel::Warn < LogLevel::Info && LogLevel::Info < LogLevel::Debug);
+        assert_eq!(LogLevel::Debug.to_string(), "debug");
+    }
+}
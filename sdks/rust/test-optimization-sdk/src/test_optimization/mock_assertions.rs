@@ -0,0 +1,119 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Mock tracer assertions module for writing concise span expectations
+//!
+//! This module provides a small builder over the mock tracer finished spans,
+//! panicking with a readable diff when no span matches the expectations.
+use crate::test_optimization::mock_tracer::*;
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+/// Represents a set of expectations about a span recorded by the mock tracer
+pub struct SpanAssertion {
+    /// The operation name the span must have
+    operation_name: String,
+    /// The string tags the span must carry
+    string_tags: Vec<(String, String)>,
+    /// The numeric tags the span must carry
+    number_tags: Vec<(String, f64)>,
+}
+
+impl MockTracer {
+    /// Starts an assertion about a span with the given operation name
+    #[allow(dead_code)]
+    pub fn assert_span(operation_name: impl AsRef<str>) -> SpanAssertion {
+        SpanAssertion {
+            operation_name: operation_name.as_ref().to_string(),
+            string_tags: Vec::new(),
+            number_tags: Vec::new(),
+        }
+    }
+}
+
+impl SpanAssertion {
+    /// Expects the span to carry a string tag with the given value
+    #[allow(dead_code)]
+    pub fn with_tag(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.string_tags
+            .push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    /// Expects the span to carry a numeric tag with the given value
+    #[allow(dead_code)]
+    pub fn with_number_tag(mut self, key: impl AsRef<str>, value: f64) -> Self {
+        self.number_tags.push((key.as_ref().to_string(), value));
+        self
+    }
+
+    /// Asserts that a finished span matches all the expectations and returns it
+    ///
+    /// Panics listing the mismatches of every candidate span when none matches.
+    #[allow(dead_code)]
+    pub fn finished(self) -> MockSpan {
+        let spans = MockTracer::get_finished_spans();
+        let candidates: Vec<&MockSpan> = spans
+            .iter()
+            .filter(|span| span.operation_name == self.operation_name)
+            .collect();
+
+        if candidates.is_empty() {
+            let mut operation_names: Vec<&str> =
+                spans.iter().map(|span| span.operation_name.as_str()).collect();
+            operation_names.sort();
+            operation_names.dedup();
+            panic!(
+                "no finished span with operation name {:?}, found: {:?}",
+                self.operation_name, operation_names
+            );
+        }
+
+        let mut report = String::new();
+        for span in candidates {
+            let mismatches = self.mismatches(span);
+            if mismatches.is_empty() {
+                return span.clone();
+            }
+            let _ = writeln!(report, "span {} ({}):", span.span_id, span.operation_name);
+            for mismatch in mismatches {
+                let _ = writeln!(report, "  {}", mismatch);
+            }
+        }
+        panic!(
+            "no finished span with operation name {:?} matches the expectations\n{}",
+            self.operation_name, report
+        );
+    }
+
+    /// Returns a line per unmet expectation for the given span
+    fn mismatches(&self, span: &MockSpan) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        for (key, expected) in &self.string_tags {
+            match span.string_tags.get(key) {
+                Some(actual) if actual == expected => {}
+                Some(actual) => mismatches.push(format!(
+                    "- tag {:?}: expected {:?}, found {:?}",
+                    key, expected, actual
+                )),
+                None => mismatches.push(format!("- tag {:?}: expected {:?}, missing", key, expected)),
+            }
+        }
+        for (key, expected) in &self.number_tags {
+            match span.number_tags.get(key) {
+                Some(actual) if actual == expected => {}
+                Some(actual) => mismatches.push(format!(
+                    "- number tag {:?}: expected {}, found {}",
+                    key, expected, actual
+                )),
+                None => mismatches.push(format!(
+                    "- number tag {:?}: expected {}, missing",
+                    key, expected
+                )),
+            }
+        }
+        mismatches
+    }
+}
@@ -8,10 +8,83 @@
 //! This module provides functionality for creating and managing mock spans,
 //! setting tags, error information, and closing spans.
 use crate::test_optimization::lib::*;
+use crate::test_optimization::time::unix_to_system_time;
+use crate::test_optimization::{tags, TestStatus};
 use crate::test_optimization::utils::*;
 use std::collections::HashMap;
 use std::ffi::CStr;
-use std::time::{Duration, SystemTime};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Options the mock tracer was initialized with, unset means capturing everything
+static MOCK_TRACER_OPTIONS: OnceLock<MockTracerOptions> = OnceLock::new();
+
+/// Deterministic IDs by native ID, with the next ID to assign
+static DETERMINISTIC_IDS: Mutex<(u64, Option<HashMap<u64, u64>>)> = Mutex::new((1, None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents what the mock tracer captures, passed to `TestOptimization::init_mock_with_options`
+///
+/// Everything is captured by default. Spans themselves are always recorded by the native
+/// mock tracer, so for huge suites also call `MockTracer::reset` between batches.
+pub struct MockTracerOptions {
+    /// Whether span tags are read back with the spans (otherwise the tag maps are empty)
+    ///
+    /// This only filters on the Rust side, when the spans are read back: it never reaches the
+    /// native library, which still records every tag of the mock spans.
+    #[allow(dead_code)]
+    pub capture_tags: bool,
+    /// Whether code coverage payloads are sent to the native library
+    #[allow(dead_code)]
+    pub capture_coverage: bool,
+    /// Whether test logs are sent to the native library
+    #[allow(dead_code)]
+    pub capture_logs: bool,
+    /// Whether the span and trace IDs of the mock spans are replaced by a sequence starting
+    /// at 1, assigned in creation order, instead of the random native IDs
+    #[allow(dead_code)]
+    pub deterministic_ids: bool,
+}
+
+impl Default for MockTracerOptions {
+    fn default() -> Self {
+        Self {
+            capture_tags: true,
+            capture_coverage: true,
+            capture_logs: true,
+            deterministic_ids: false,
+        }
+    }
+}
+
+/// Stores the mock tracer options; only the first call has an effect, like the native init
+pub(in crate::test_optimization) fn set_mock_tracer_options(options: MockTracerOptions) {
+    let _ = MOCK_TRACER_OPTIONS.set(options);
+}
+
+/// Gets the mock tracer options, capturing everything when none were set
+pub(in crate::test_optimization) fn mock_tracer_options() -> MockTracerOptions {
+    MOCK_TRACER_OPTIONS.get().copied().unwrap_or_default()
+}
+
+/// Gets the deterministic ID of a native ID, assigning the next one on first sight
+///
+/// Returns the native ID unchanged when deterministic IDs are disabled, so outside of
+/// `init_mock_with_options` this never allocates.
+pub(in crate::test_optimization) fn deterministic_id(native_id: u64) -> u64 {
+    if native_id == 0 || !mock_tracer_options().deterministic_ids {
+        return native_id;
+    }
+    let mut guard = DETERMINISTIC_IDS.lock().unwrap_or_else(|e| e.into_inner());
+    let (next, ids) = &mut *guard;
+    *ids.get_or_insert_with(HashMap::new)
+        .entry(native_id)
+        .or_insert_with(|| {
+            let id = *next;
+            *next += 1;
+            id
+        })
+}
 
 #[derive(Debug, Clone)]
 /// Represents a mock span for testing and debugging purposes
@@ -42,6 +115,27 @@ pub struct MockSpan {
     pub number_tags: HashMap<String, f64>,
 }
 
+impl MockSpan {
+    /// Whether this span is a test span, i.e. carries the `test.name` tag
+    ///
+    /// Suite, module and session spans carry `test.status` too, but not `test.name`.
+    #[allow(dead_code)]
+    pub fn is_test(&self) -> bool {
+        self.string_tags.contains_key(tags::TEST_NAME)
+    }
+
+    /// Gets the status reported in the `test.status` tag (`"pass"`, `"fail"` or `"skip"`)
+    ///
+    /// The native library sets the tag when closing a test, and infers it for suites, modules
+    /// and sessions. Returns `None` for spans without the tag (e.g. custom spans).
+    #[allow(dead_code)]
+    pub fn test_status(&self) -> Option<TestStatus> {
+        self.string_tags
+            .get(tags::TEST_STATUS)
+            .and_then(|status| TestStatus::from_tag(status))
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Represents a mock tracer for testing and debugging purposes
 pub struct MockTracer;
@@ -85,9 +179,26 @@ impl MockTracer {
         }
     }
 
+    /// Gets the ID a handle ID is reported with in the mock spans
+    ///
+    /// With `MockTracerOptions::deterministic_ids`, handles are numbered from 1 in creation
+    /// order; otherwise the ID is returned unchanged.
+    #[allow(dead_code)]
+    pub fn span_id_of(handle_id: u64) -> u64 {
+        deterministic_id(handle_id)
+    }
+
+    /// Returns the first finished span with the given operation name, if any
+    #[allow(dead_code)]
+    pub fn find_finished_span(operation_name: impl AsRef<str>) -> Option<MockSpan> {
+        Self::get_finished_spans()
+            .into_iter()
+            .find(|span| span.operation_name == operation_name.as_ref())
+    }
+
     /// Converts a topt_UnixTime to a SystemTime
     fn convert_unix_time(ut: &topt_UnixTime) -> SystemTime {
-        SystemTime::UNIX_EPOCH + Duration::new(ut.sec, ut.nsec as u32)
+        unix_to_system_time(*ut)
     }
 
     /// Converts a C KeyValue array to a HashMap<String, String>
@@ -131,10 +242,11 @@ impl MockTracer {
 
     /// Converts a single C topt_MockSpan to our Rust MockSpan struct
     fn convert_mock_span(mock: &topt_MockSpan) -> MockSpan {
+        let capture_tags = mock_tracer_options().capture_tags;
         MockSpan {
-            span_id: mock.span_id,
-            trace_id: mock.trace_id,
-            parent_span_id: mock.parent_span_id,
+            span_id: deterministic_id(mock.span_id),
+            trace_id: deterministic_id(mock.trace_id),
+            parent_span_id: deterministic_id(mock.parent_span_id),
             start_time: Self::convert_unix_time(&mock.start_time),
             finish_time: Self::convert_unix_time(&mock.finish_time),
             operation_name: if mock.operation_name.is_null() {
@@ -142,8 +254,16 @@ impl MockTracer {
             } else {
                 unsafe { CStr::from_ptr(mock.operation_name).to_string_lossy().into_owned() }
             },
-            string_tags: Self::convert_key_value_array(&mock.string_tags),
-            number_tags: Self::convert_key_number_array(&mock.number_tags),
+            string_tags: if capture_tags {
+                Self::convert_key_value_array(&mock.string_tags)
+            } else {
+                HashMap::new()
+            },
+            number_tags: if capture_tags {
+                Self::convert_key_number_array(&mock.number_tags)
+            } else {
+                HashMap::new()
+            },
         }
     }
 
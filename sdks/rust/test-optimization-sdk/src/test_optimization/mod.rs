@@ -5,19 +5,70 @@
 
 mod lib;
 mod utils;
+mod registry;
+mod stats;
+pub mod sdk_metrics;
+pub mod tags;
+pub mod time;
+pub mod typed;
 
 mod test_session;
+mod session_builder;
 mod test_module;
 mod test_suite;
 mod test;
+mod test_builder;
 mod span;
+mod analysis;
+mod attempt_to_fix;
+mod coverage;
+mod efd;
+mod guards;
+mod hooks;
+mod parameters;
+mod naming;
+mod ci_provider;
+mod propagation;
 mod mock_tracer;
+mod junit;
+#[cfg(feature = "lcov")]
+mod lcov;
+mod logging;
+#[cfg(any(test, feature = "test-util"))]
+mod mock_assertions;
+#[cfg(any(test, feature = "parameterized"))]
+mod parameterized;
+#[cfg(all(unix, feature = "signal-flush"))]
+mod signal_flush;
+mod test_management;
 mod test_optimization;
 
+pub use analysis::*;
+pub use attempt_to_fix::*;
+pub use ci_provider::*;
+pub use coverage::*;
+pub use efd::EfdStatus;
+pub use guards::*;
+pub use hooks::*;
+#[cfg(feature = "lcov")]
+pub use lcov::*;
+pub use logging::LogLevel;
 pub use mock_tracer::*;
+pub use naming::*;
+pub use parameters::*;
+pub use propagation::*;
+pub use registry::HandleKind;
+pub use tags::TagValue;
+#[cfg(any(test, feature = "test-util"))]
+pub use mock_assertions::*;
+#[cfg(any(test, feature = "parameterized"))]
+pub use parameterized::*;
 pub use span::*;
 pub use test::*;
+pub use test_builder::*;
 pub use test_module::*;
 pub use test_session::*;
+pub use session_builder::*;
 pub use test_suite::*;
+pub use test_management::*;
 pub use test_optimization::*;
\ No newline at end of file
@@ -0,0 +1,101 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Naming module for the operation names of the test spans
+//!
+//! The native library names the session, module, suite and test spans itself.
+//! This module provides operation names named after the kind of each span and a naming
+//! scheme to override them, applied through the `span.name` tag when a handle is created.
+//! These names are a choice of this SDK, not a Datadog naming convention.
+use crate::test_optimization::*;
+use std::sync::Mutex;
+
+/// Operation name of test session spans named after their kind
+pub const TEST_SESSION_OPERATION_NAME: &str = "test_session";
+/// Operation name of test module spans named after their kind
+pub const TEST_MODULE_OPERATION_NAME: &str = "test_module";
+/// Operation name of test suite spans named after their kind
+pub const TEST_SUITE_OPERATION_NAME: &str = "test_suite";
+/// Operation name of test spans named after their kind
+pub const TEST_OPERATION_NAME: &str = "test";
+
+/// Naming scheme applied to the handles created after it is set
+static NAMING_SCHEME: Mutex<Option<NamingScheme>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Represents the operation names of the session, module, suite and test spans
+///
+/// A `None` name keeps the one given by the native library, so the default scheme
+/// changes nothing. Custom spans are named by `Span::create` and are not affected.
+pub struct NamingScheme {
+    /// Operation name of test session spans
+    #[allow(dead_code)]
+    pub session: Option<String>,
+    /// Operation name of test module spans
+    #[allow(dead_code)]
+    pub module: Option<String>,
+    /// Operation name of test suite spans
+    #[allow(dead_code)]
+    pub suite: Option<String>,
+    /// Operation name of test spans
+    #[allow(dead_code)]
+    pub test: Option<String>,
+}
+
+impl NamingScheme {
+    /// Creates a scheme naming each span after its kind (`test_session`, `test_module`,
+    /// `test_suite` and `test`)
+    #[allow(dead_code)]
+    pub fn kind_names() -> Self {
+        Self {
+            session: Some(TEST_SESSION_OPERATION_NAME.to_string()),
+            module: Some(TEST_MODULE_OPERATION_NAME.to_string()),
+            suite: Some(TEST_SUITE_OPERATION_NAME.to_string()),
+            test: Some(TEST_OPERATION_NAME.to_string()),
+        }
+    }
+
+    /// Gets the operation name overridden for a kind of handle, `None` for custom spans
+    #[allow(dead_code)]
+    pub fn operation_name(&self, kind: HandleKind) -> Option<&str> {
+        match kind {
+            HandleKind::Session => self.session.as_deref(),
+            HandleKind::Module => self.module.as_deref(),
+            HandleKind::Suite => self.suite.as_deref(),
+            HandleKind::Test => self.test.as_deref(),
+            HandleKind::Span => None,
+        }
+    }
+}
+
+impl TestOptimization {
+    /// Set the naming scheme of the session, module, suite and test spans
+    ///
+    /// Call it before initializing (or pass it to `TestSessionBuilder::naming_scheme`):
+    /// it applies to the handles created afterwards.
+    #[allow(dead_code)]
+    pub fn set_naming_scheme(naming_scheme: NamingScheme) {
+        *NAMING_SCHEME.lock().unwrap_or_else(|e| e.into_inner()) = Some(naming_scheme);
+    }
+
+    /// Get the naming scheme, the default one (keeping the native names) if none was set
+    #[allow(dead_code)]
+    pub fn naming_scheme() -> NamingScheme {
+        NAMING_SCHEME
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .unwrap_or_default()
+    }
+}
+
+/// Gets the operation name a new handle of the given kind must be renamed to, if any
+pub(in crate::test_optimization) fn operation_name(kind: HandleKind) -> Option<String> {
+    NAMING_SCHEME
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|naming_scheme| naming_scheme.operation_name(kind).map(str::to_string))
+}
@@ -0,0 +1,69 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Parameterized module for reporting `rstest`/`test-case` style cases
+//!
+//! This module provides helpers to run a single case of a parameterized test,
+//! recording its arguments as the test parameters and closing it with the
+//! status matching the case outcome.
+use crate::test_optimization::*;
+use std::fmt::Debug;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+/// Builds the `test.parameters` JSON for the given case arguments
+///
+/// Arguments are formatted with their `Debug` representation, producing the canonical
+/// `{"arguments":{"name":"value",...},"metadata":{}}` of `TestParameters`, sorted by name.
+#[allow(dead_code)]
+pub fn parameters_json(arguments: &[(&str, &dyn Debug)]) -> String {
+    TestParameters::from_debug(arguments).to_json()
+}
+
+impl TestSuite {
+    /// Runs a single case of a parameterized test within this suite
+    ///
+    /// The test is created with the case arguments as parameters and closed as
+    /// `Pass` when `case` returns, or as `Fail` when it panics, in which case the
+    /// panic is resumed after closing the test.
+    #[allow(dead_code)]
+    pub fn run_case(
+        &self,
+        name: impl AsRef<str>,
+        arguments: &[(&str, &dyn Debug)],
+        case: impl FnOnce(),
+    ) -> bool {
+        let test = self.create_parameterized_test(name, parameters_json(arguments));
+        match catch_unwind(AssertUnwindSafe(case)) {
+            Ok(()) => test.close(TestStatus::Pass),
+            Err(payload) => {
+                test.close(TestStatus::Fail);
+                resume_unwind(payload)
+            }
+        }
+    }
+}
+
+/// Runs a parameterized test case under a suite, capturing the named arguments as parameters
+///
+/// ```ignore
+/// #[rstest]
+/// #[case(1, 2)]
+/// #[case(2, 4)]
+/// fn doubles(#[case] input: i32, #[case] expected: i32) {
+///     dd_case!(suite(), "doubles", [input, expected], {
+///         assert_eq!(input * 2, expected);
+///     });
+/// }
+/// ```
+#[macro_export]
+macro_rules! dd_case {
+    ($suite:expr, $name:expr, [$($arg:ident),* $(,)?], $body:block) => {
+        $suite.run_case(
+            $name,
+            &[$((stringify!($arg), &$arg as &dyn ::std::fmt::Debug)),*],
+            || $body,
+        )
+    };
+}
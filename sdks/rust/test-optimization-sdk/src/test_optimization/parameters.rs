@@ -0,0 +1,151 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Parameters module for the structured `test.parameters` of parameterized tests
+//!
+//! The parameters of a test are reported, and returned by the skippable tests, as a
+//! JSON string. This module provides a structured representation with a canonical
+//! JSON form, so both sides compare by content rather than by exact string.
+use crate::test_optimization::utils::*;
+use crate::test_optimization::*;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Represents the parameters of a parameterized test
+///
+/// The canonical JSON form is `{"arguments":{...},"metadata":{...}}` without whitespace,
+/// with the keys of both objects sorted by byte order and every value a JSON string.
+pub struct TestParameters {
+    /// Argument values by argument name
+    #[allow(dead_code)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub arguments: BTreeMap<String, String>,
+    /// Additional metadata by key
+    #[allow(dead_code)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl TestParameters {
+    /// Creates empty parameters
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an argument, replacing any previous value of the same name
+    #[allow(dead_code)]
+    pub fn with_argument(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.arguments
+            .insert(name.as_ref().to_string(), value.as_ref().to_string());
+        self
+    }
+
+    /// Creates parameters from case arguments, formatted with their `Debug` representation
+    #[allow(dead_code)]
+    pub fn from_debug(arguments: &[(&str, &dyn Debug)]) -> Self {
+        arguments
+            .iter()
+            .fold(Self::new(), |parameters, (name, value)| {
+                parameters.with_argument(name, format!("{:?}", value))
+            })
+    }
+
+    /// Whether there are neither arguments nor metadata
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.arguments.is_empty() && self.metadata.is_empty()
+    }
+
+    /// Formats the parameters as canonical JSON
+    #[allow(dead_code)]
+    pub fn to_json(&self) -> String {
+        let object = |map: &BTreeMap<String, String>| {
+            map.iter()
+                .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        format!(
+            "{{\"arguments\":{{{}}},\"metadata\":{{{}}}}}",
+            object(&self.arguments),
+            object(&self.metadata)
+        )
+    }
+
+    /// Parses parameters from JSON, in any key order and with any whitespace
+    ///
+    /// A missing or `null` `arguments`/`metadata` is empty, and non-string values are kept
+    /// as their compact JSON text (e.g. `1` becomes `"1"`). Returns `None` for JSON that
+    /// isn't an object of objects.
+    #[cfg(feature = "serde")]
+    #[allow(dead_code)]
+    pub fn from_json(json: &str) -> Option<Self> {
+        use serde_json::Value;
+
+        let value: Value = serde_json::from_str(json).ok()?;
+        let object = value.as_object()?;
+        let map = |key: &str| match object.get(key) {
+            None | Some(Value::Null) => Some(BTreeMap::new()),
+            Some(Value::Object(entries)) => Some(
+                entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            Value::String(value) => value.clone(),
+                            value => value.to_string(),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect(),
+            ),
+            Some(_) => None,
+        };
+        Some(Self {
+            arguments: map("arguments")?,
+            metadata: map("metadata")?,
+        })
+    }
+}
+
+impl SkippableTest {
+    /// Whether this skippable test is the test with the given name and parameters
+    ///
+    /// Skippable tests without parameters match empty parameters. With the `serde` feature,
+    /// parameters are compared by content, so whitespace and key order don't matter;
+    /// otherwise (or if they can't be parsed) they must equal the canonical JSON.
+    #[allow(dead_code)]
+    pub fn matches(&self, test_name: &str, parameters: &TestParameters) -> bool {
+        if self.test_name != test_name {
+            return false;
+        }
+        if self.parameters.is_empty() {
+            return parameters.is_empty();
+        }
+        #[cfg(feature = "serde")]
+        if let Some(skippable_parameters) = TestParameters::from_json(&self.parameters) {
+            return &skippable_parameters == parameters;
+        }
+        self.parameters == parameters.to_json()
+    }
+}
+
+impl TestSuite {
+    /// Creates a new test within this suite with structured parameters
+    ///
+    /// The parameters are set as the `test.parameters` tag in their canonical JSON form.
+    #[allow(dead_code)]
+    pub fn create_test_with_parameters(
+        &self,
+        name: impl AsRef<str>,
+        parameters: &TestParameters,
+    ) -> Test {
+        let test = self.create_test(name);
+        test.set_string_tag(tags::TEST_PARAMETERS, parameters.to_json());
+        test
+    }
+}
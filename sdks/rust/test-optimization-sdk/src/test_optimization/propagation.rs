@@ -0,0 +1,269 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Propagation module for distributed trace context headers
+//!
+//! This module parses and formats the W3C Trace Context and Datadog propagation
+//! headers, or passes the context itself around as a serializable `TraceContext`.
+//! The native library doesn't accept a remote parent, so the received context is
+//! recorded on the session as a span link instead of re-parenting it.
+use crate::test_optimization::lib::*;
+use crate::test_optimization::utils::Bool_to_bool;
+use crate::test_optimization::*;
+use std::collections::HashMap;
+
+/// W3C Trace Context header
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// Datadog trace ID header (lower 64 bits, decimal)
+pub const DATADOG_TRACE_ID_HEADER: &str = "x-datadog-trace-id";
+/// Datadog parent span ID header (decimal)
+pub const DATADOG_PARENT_ID_HEADER: &str = "x-datadog-parent-id";
+/// Datadog sampling priority header
+pub const DATADOG_SAMPLING_PRIORITY_HEADER: &str = "x-datadog-sampling-priority";
+/// Datadog propagated tags header, carrying the upper 64 bits of the trace ID as `_dd.p.tid`
+pub const DATADOG_TAGS_HEADER: &str = "x-datadog-tags";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Represents a trace context received from or sent to another process
+pub struct PropagationContext {
+    /// 128-bit trace ID (the upper 64 bits are zero for 64-bit trace IDs)
+    #[allow(dead_code)]
+    #[cfg_attr(feature = "serde", serde(with = "hex_trace_id"))]
+    pub trace_id: u128,
+    /// ID of the parent span
+    #[allow(dead_code)]
+    pub span_id: u64,
+    /// Sampling priority, if propagated
+    #[allow(dead_code)]
+    pub sampling_priority: Option<i32>,
+}
+
+/// A trace context passed between trusted processes as is, instead of as headers
+///
+/// With the `serde` feature, it serializes as
+/// `{"trace_id":"<32 hex digits>","span_id":…,"sampling_priority":…}`, the trace ID being a
+/// string since JSON parsers commonly lose precision past 53 bits. Unlike the headers,
+/// nothing is lost or reinterpreted on the way. Use the headers (`Test::inject_headers` and
+/// `TestSession::create_linked_to`) to cross process or vendor boundaries.
+pub type TraceContext = PropagationContext;
+
+/// Serde representation of the trace ID as 32 lowercase hex digits, like in `traceparent`
+#[cfg(feature = "serde")]
+mod hex_trace_id {
+    use serde::de::{self, Unexpected};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(trace_id: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:032x}", trace_id))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        let trace_id = String::deserialize(deserializer)?;
+        if trace_id.len() != 32 || !trace_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(de::Error::invalid_value(
+                Unexpected::Str(&trace_id),
+                &"a trace ID of 32 hex digits",
+            ));
+        }
+        u128::from_str_radix(&trace_id, 16).map_err(de::Error::custom)
+    }
+}
+
+impl PropagationContext {
+    /// Extracts a context from propagation headers, matching header names case-insensitively
+    ///
+    /// A valid `traceparent` (version `00`) takes precedence over the Datadog headers,
+    /// except for an `x-datadog-sampling-priority` agreeing with its sampled flag.
+    /// Returns `None` when no supported header carries non-zero trace and span IDs.
+    #[allow(dead_code)]
+    pub fn extract(headers: &HashMap<String, String>) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.trim())
+        };
+        match header(TRACEPARENT_HEADER).and_then(Self::parse_traceparent) {
+            Some(mut context) => {
+                // keep the finer Datadog priority when it agrees with the sampled flag
+                let sampled = context.sampling_priority == Some(1);
+                if let Some(priority) = header(DATADOG_SAMPLING_PRIORITY_HEADER)
+                    .and_then(|priority| priority.parse::<i32>().ok())
+                    .filter(|priority| (*priority > 0) == sampled)
+                {
+                    context.sampling_priority = Some(priority);
+                }
+                Some(context)
+            }
+            None => Self::parse_datadog(&header),
+        }
+    }
+
+    /// Parses a `00-<trace-id>-<parent-id>-<flags>` W3C header
+    fn parse_traceparent(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.split('-').collect();
+        if parts.len() < 4 || parts[0] != "00" || parts[1].len() != 32 || parts[2].len() != 16 {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(parts[1], 16).ok()?;
+        let span_id = u64::from_str_radix(parts[2], 16).ok()?;
+        let flags = u8::from_str_radix(parts[3], 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampling_priority: Some(i32::from(flags & 0x01)),
+        })
+    }
+
+    /// Parses the `x-datadog-*` headers
+    fn parse_datadog<'a>(header: &impl Fn(&str) -> Option<&'a str>) -> Option<Self> {
+        let lower = header(DATADOG_TRACE_ID_HEADER)?.parse::<u64>().ok()?;
+        let span_id = header(DATADOG_PARENT_ID_HEADER)?.parse::<u64>().ok()?;
+        if lower == 0 || span_id == 0 {
+            return None;
+        }
+        let upper = header(DATADOG_TAGS_HEADER)
+            .and_then(|tags| {
+                tags.split(',')
+                    .find_map(|tag| tag.trim().strip_prefix("_dd.p.tid="))
+                    .and_then(|tid| u64::from_str_radix(tid, 16).ok())
+            })
+            .unwrap_or(0);
+        Some(Self {
+            trace_id: (u128::from(upper) << 64) | u128::from(lower),
+            span_id,
+            sampling_priority: header(DATADOG_SAMPLING_PRIORITY_HEADER)
+                .and_then(|priority| priority.parse().ok()),
+        })
+    }
+
+    /// Formats the context as `traceparent` and `x-datadog-*` propagation headers
+    #[allow(dead_code)]
+    pub fn to_headers(&self) -> HashMap<String, String> {
+        let sampled = self.sampling_priority.is_some_and(|priority| priority > 0);
+        let mut headers = HashMap::from([
+            (
+                TRACEPARENT_HEADER.to_string(),
+                format!(
+                    "00-{:032x}-{:016x}-{:02x}",
+                    self.trace_id,
+                    self.span_id,
+                    u8::from(sampled)
+                ),
+            ),
+            (
+                DATADOG_TRACE_ID_HEADER.to_string(),
+                (self.trace_id as u64).to_string(),
+            ),
+            (DATADOG_PARENT_ID_HEADER.to_string(), self.span_id.to_string()),
+        ]);
+        let upper = (self.trace_id >> 64) as u64;
+        if upper != 0 {
+            headers.insert(
+                DATADOG_TAGS_HEADER.to_string(),
+                format!("_dd.p.tid={:016x}", upper),
+            );
+        }
+        if let Some(priority) = self.sampling_priority {
+            headers.insert(
+                DATADOG_SAMPLING_PRIORITY_HEADER.to_string(),
+                priority.to_string(),
+            );
+        }
+        headers
+    }
+
+    /// Formats the context as the value of the `_dd.span_links` tag
+    pub(in crate::test_optimization) fn span_link(&self) -> String {
+        format!(
+            r#"[{{"trace_id":"{:032x}","span_id":"{:016x}"}}]"#,
+            self.trace_id, self.span_id
+        )
+    }
+}
+
+impl TestSession {
+    /// Creates a new test session linked to the remote context of the given propagation headers
+    ///
+    /// Supported formats are W3C Trace Context (`traceparent`, version `00`) and Datadog
+    /// (`x-datadog-trace-id`, `x-datadog-parent-id`, `x-datadog-sampling-priority` and the
+    /// `_dd.p.tid` entry of `x-datadog-tags`). The remote span doesn't become the parent of
+    /// the session: the native library has no parent parameter and always starts the session
+    /// in its own trace, so the remote span is only recorded as a span link
+    /// (`_dd.span_links`).
+    /// Without a supported header, this behaves like `TestSession::create(None, None)`;
+    /// use `set_framework` to report the framework.
+    #[allow(dead_code)]
+    pub fn create_linked_to(headers: &HashMap<String, String>) -> Self {
+        match PropagationContext::extract(headers) {
+            Some(context) => Self::create_linked_to_context(&context),
+            None => Self::create(None::<&str>, None::<&str>),
+        }
+    }
+
+    /// Creates a new test session linked to a remote trace context, like
+    /// `create_linked_to` without going through headers
+    #[allow(dead_code)]
+    pub fn create_linked_to_context(context: &TraceContext) -> Self {
+        let session = Self::create(None::<&str>, None::<&str>);
+        session.set_string_tag(tags::SPAN_LINKS, context.span_link());
+        session
+    }
+
+    /// Gets the trace context of the session span, to link another process to it
+    ///
+    /// Returns `None` once the session is closed, see `Test::context`.
+    #[allow(dead_code)]
+    pub fn context(&self) -> Option<TraceContext> {
+        if self.closed.is_closed() {
+            return None;
+        }
+        native_trace_context(self.session_id)
+    }
+}
+
+impl Test {
+    /// Gets the propagation headers of the test span, to continue its trace in a subprocess
+    ///
+    /// The headers carry the 128-bit trace ID of the test span, as read from the native
+    /// library. Tests are always kept, so the sampling priority is `1` (auto keep). Returns
+    /// no headers once the test is closed.
+    #[allow(dead_code)]
+    pub fn inject_headers(&self) -> HashMap<String, String> {
+        self.context()
+            .map(|context| context.to_headers())
+            .unwrap_or_default()
+    }
+
+    /// Gets the trace context of the test span, the typed counterpart of `inject_headers`
+    ///
+    /// The trace and span IDs are read from the native library and the sampling priority is
+    /// `1` (auto keep). Returns `None` once the test is closed, or if the native library
+    /// doesn't know the test.
+    #[allow(dead_code)]
+    pub fn context(&self) -> Option<TraceContext> {
+        if self.closed.is_closed() {
+            return None;
+        }
+        native_trace_context(self.test_id)
+    }
+}
+
+/// Gets the trace context of an open handle from the native library
+fn native_trace_context(tslv_id: u64) -> Option<TraceContext> {
+    let result = unsafe { topt_get_trace_context(tslv_id) };
+    if !Bool_to_bool(result.valid) {
+        return None;
+    }
+    Some(TraceContext {
+        trace_id: (u128::from(result.trace_id_upper) << 64) | u128::from(result.trace_id_lower),
+        span_id: result.span_id,
+        sampling_priority: Some(1),
+    })
+}
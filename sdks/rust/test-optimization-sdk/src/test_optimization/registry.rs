@@ -0,0 +1,262 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Registry module for tracking open handles
+//!
+//! The native library only exposes its open spans through the mock tracer, so the
+//! SDK keeps its own record of the handles it creates until they are closed,
+//! together with a shadow of the tags set through the SDK on them.
+use crate::test_optimization::lib::topt_UnixTime;
+use crate::test_optimization::mock_tracer;
+use crate::test_optimization::sdk_metrics;
+use crate::test_optimization::utils::CloseFlag;
+use crate::test_optimization::TagValue;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents the kind of handle tracked by the registry
+pub enum HandleKind {
+    /// A test session
+    Session,
+    /// A test module
+    Module,
+    /// A test suite
+    Suite,
+    /// A test
+    Test,
+    /// A custom span
+    Span,
+}
+
+impl fmt::Display for HandleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HandleKind::Session => "session",
+            HandleKind::Module => "module",
+            HandleKind::Suite => "suite",
+            HandleKind::Test => "test",
+            HandleKind::Span => "span",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Writes a handle as `Kind "name" [id=42]` for the `Display` of the handle types
+///
+/// The name is omitted when it is unknown (empty), e.g. a session without framework.
+pub(in crate::test_optimization) fn fmt_handle(
+    f: &mut fmt::Formatter<'_>,
+    kind: HandleKind,
+    name: &str,
+    id: u64,
+) -> fmt::Result {
+    let kind = kind.to_string();
+    write!(f, "{}{}", kind[..1].to_uppercase(), &kind[1..])?;
+    if !name.is_empty() {
+        write!(f, " {:?}", name)?;
+    }
+    write!(f, " [id={}]", id)
+}
+
+#[derive(Debug, Clone)]
+/// Represents a handle that has been created but not closed yet
+pub(in crate::test_optimization) struct OpenHandle {
+    /// The kind of the handle
+    pub kind: HandleKind,
+    /// The name of the handle (operation name for spans)
+    pub name: String,
+    /// The ID of the parent handle, 0 for sessions
+    pub parent_id: u64,
+    /// When the handle was created
+    pub opened_at: Instant,
+    /// The start time reported to the native library
+    pub start_time: topt_UnixTime,
+    /// String tags set through the SDK
+    pub string_tags: HashMap<String, String>,
+    /// Numeric tags set through the SDK
+    pub number_tags: HashMap<String, f64>,
+    /// Tags applied to the tests created under the handle (suites only)
+    pub inherited_tags: BTreeMap<String, TagValue>,
+    /// Content hashes of the source files set on the handle, by file (tests only)
+    pub source_hashes: BTreeMap<String, String>,
+    /// Changed lines of the files set on the handle, by file (tests only)
+    pub changed_lines: BTreeMap<String, BTreeSet<u32>>,
+    /// Artifacts added to the handle as JSON objects, by name (tests only)
+    pub artifacts: BTreeMap<String, String>,
+    /// Closed flag shared with the handles created for this ID
+    pub closed: CloseFlag,
+}
+
+/// Number of shards of the registry
+const SHARD_COUNT: usize = 16;
+
+/// Open handles by ID, split in shards by ID
+///
+/// Every handle creation and tag set goes through the registry, so a single lock would
+/// serialize the threads of a parallel test run; with shards, handles of different IDs
+/// rarely share a lock. Only the scans of the whole registry lock every shard, one at a time.
+static OPEN_HANDLES: [Mutex<BTreeMap<u64, OpenHandle>>; SHARD_COUNT] =
+    [const { Mutex::new(BTreeMap::new()) }; SHARD_COUNT];
+
+/// Locks the shard of an ID, recovering it if a panicking thread poisoned the lock
+fn lock(id: u64) -> MutexGuard<'static, BTreeMap<u64, OpenHandle>> {
+    lock_shard(&OPEN_HANDLES[(id % SHARD_COUNT as u64) as usize])
+}
+
+/// Locks a shard, recovering it if a panicking thread poisoned the lock
+fn lock_shard(
+    shard: &'static Mutex<BTreeMap<u64, OpenHandle>>,
+) -> MutexGuard<'static, BTreeMap<u64, OpenHandle>> {
+    shard.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns a snapshot of the open handles matching a filter, ordered by ID
+fn snapshot(filter: impl Fn(&OpenHandle) -> bool) -> Vec<(u64, OpenHandle)> {
+    let mut handles: Vec<(u64, OpenHandle)> = OPEN_HANDLES
+        .iter()
+        .flat_map(|shard| {
+            lock_shard(shard)
+                .iter()
+                .filter(|(_, handle)| filter(handle))
+                .map(|(id, handle)| (*id, handle.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    handles.sort_unstable_by_key(|(id, _)| *id);
+    handles
+}
+
+/// Records a newly created handle, returning the closed flag the handle must use; invalid
+/// (zero) IDs are ignored
+///
+/// The native library returns the existing ID when a module or suite with the same
+/// name is created again, in which case the original record, and its flag, are kept.
+pub(in crate::test_optimization) fn register(
+    id: u64,
+    kind: HandleKind,
+    name: impl AsRef<str>,
+    parent_id: u64,
+    start_time: topt_UnixTime,
+) -> CloseFlag {
+    if id == 0 {
+        return CloseFlag::default();
+    }
+    sdk_metrics::record_created(kind);
+    // number the handle in creation order when the mock tracer uses deterministic IDs
+    mock_tracer::deterministic_id(id);
+    lock(id)
+        .entry(id)
+        .or_insert_with(|| OpenHandle {
+            kind,
+            name: name.as_ref().to_string(),
+            parent_id,
+            opened_at: Instant::now(),
+            start_time,
+            string_tags: HashMap::new(),
+            number_tags: HashMap::new(),
+            inherited_tags: BTreeMap::new(),
+            source_hashes: BTreeMap::new(),
+            changed_lines: BTreeMap::new(),
+            artifacts: BTreeMap::new(),
+            closed: CloseFlag::default(),
+        })
+        .closed
+        .clone()
+}
+
+/// Removes a closed handle from the registry, returning its record if it was open
+pub(in crate::test_optimization) fn unregister(id: u64) -> Option<OpenHandle> {
+    lock(id).remove(&id)
+}
+
+/// Returns a snapshot of all the open handles ordered by ID
+pub(in crate::test_optimization) fn open_handles() -> Vec<(u64, OpenHandle)> {
+    snapshot(|_| true)
+}
+
+/// Returns the open handles of the given kind under the given parent, ordered by ID
+pub(in crate::test_optimization) fn open_children(
+    parent_id: u64,
+    kind: HandleKind,
+) -> Vec<(u64, OpenHandle)> {
+    snapshot(|handle| handle.parent_id == parent_id && handle.kind == kind)
+}
+
+/// Records a string tag set on an open handle
+pub(in crate::test_optimization) fn record_string_tag(id: u64, key: &str, value: &str) {
+    if let Some(handle) = lock(id).get_mut(&id) {
+        handle.string_tags.insert(key.to_string(), value.to_string());
+    }
+}
+
+/// Records a numeric tag set on an open handle
+pub(in crate::test_optimization) fn record_number_tag(id: u64, key: &str, value: f64) {
+    if let Some(handle) = lock(id).get_mut(&id) {
+        handle.number_tags.insert(key.to_string(), value);
+    }
+}
+
+/// Records a tag inherited by the children created under an open handle
+///
+/// Returns `false` if the handle isn't open.
+pub(in crate::test_optimization) fn record_inherited_tag(
+    id: u64,
+    key: &str,
+    value: TagValue,
+) -> bool {
+    match lock(id).get_mut(&id) {
+        Some(handle) => {
+            handle.inherited_tags.insert(key.to_string(), value);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Updates the record of an open handle, returning `None` if the handle isn't open
+///
+/// Used for the state a handle accumulates across calls and shares with its clones, which
+/// lives in the record so it is only allocated for the handles that use it.
+pub(in crate::test_optimization) fn update<R>(
+    id: u64,
+    update: impl FnOnce(&mut OpenHandle) -> R,
+) -> Option<R> {
+    lock(id).get_mut(&id).map(update)
+}
+
+/// Returns the tags inherited by the children created under an open handle
+pub(in crate::test_optimization) fn inherited_tags(id: u64) -> Vec<(String, TagValue)> {
+    lock(id)
+        .get(&id)
+        .map(|handle| handle.inherited_tags.clone().into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Returns the name of an open handle
+pub(in crate::test_optimization) fn name(id: u64) -> Option<String> {
+    lock(id).get(&id).map(|handle| handle.name.clone())
+}
+
+/// Returns the string tag recorded on an open handle
+pub(in crate::test_optimization) fn string_tag(id: u64, key: &str) -> Option<String> {
+    lock(id).get(&id).and_then(|handle| handle.string_tags.get(key).cloned())
+}
+
+/// Returns the numeric tag recorded on an open handle
+pub(in crate::test_optimization) fn number_tag(id: u64, key: &str) -> Option<f64> {
+    lock(id).get(&id).and_then(|handle| handle.number_tags.get(key).copied())
+}
+
+/// Returns all the string and numeric tags recorded on an open handle
+pub(in crate::test_optimization) fn tags(
+    id: u64,
+) -> Option<(HashMap<String, String>, HashMap<String, f64>)> {
+    lock(id)
+        .get(&id)
+        .map(|handle| (handle.string_tags.clone(), handle.number_tags.clone()))
+}
@@ -0,0 +1,176 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! SDK metrics module for observing the SDK itself
+//!
+//! With the `metrics` feature, the SDK reports counters and histograms through the
+//! `metrics` crate facade, to the recorder installed by the application (e.g. a
+//! Prometheus exporter). Without it, these functions compile to nothing.
+use crate::test_optimization::HandleKind;
+
+/// Counter of the handles created, labeled by `kind`
+pub const HANDLES_CREATED_METRIC: &str = "test_optimization_sdk_handles_created_total";
+/// Counter of the native close calls, labeled by `result` (`ok` or `error`)
+pub const CLOSES_METRIC: &str = "test_optimization_sdk_closes_total";
+/// Counter of the coverage payloads sent to the native library
+pub const COVERAGE_PAYLOADS_METRIC: &str = "test_optimization_sdk_coverage_payloads_total";
+/// Histogram of the duration in seconds of native calls, labeled by `function`
+pub const FFI_CALL_DURATION_METRIC: &str = "test_optimization_sdk_ffi_call_duration_seconds";
+
+/// Records a handle created through the SDK
+pub(in crate::test_optimization) fn record_created(kind: HandleKind) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(HANDLES_CREATED_METRIC, "kind" => kind.to_string()).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = kind;
+}
+
+/// Records the result of a native close call
+pub(in crate::test_optimization) fn record_close(result: bool) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(CLOSES_METRIC, "result" => if result { "ok" } else { "error" })
+        .increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = result;
+}
+
+/// Records a coverage payload sent to the native library
+pub(in crate::test_optimization) fn record_coverage_payload() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(COVERAGE_PAYLOADS_METRIC).increment(1);
+}
+
+/// Runs a native call, recording its duration under the name of the native function
+pub(in crate::test_optimization) fn time_ffi_call<T>(
+    function: &'static str,
+    call: impl FnOnce() -> T,
+) -> T {
+    #[cfg(feature = "metrics")]
+    {
+        let started_at = std::time::Instant::now();
+        let result = call();
+        metrics::histogram!(FFI_CALL_DURATION_METRIC, "function" => function)
+            .record(started_at.elapsed().as_secs_f64());
+        result
+    }
+    #[cfg(not(feature = "metrics"))]
+    {
+        let _ = function;
+        call()
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Values recorded by metric, keyed by name and labels, e.g. `name{result=ok}`
+    type Values = Arc<Mutex<BTreeMap<String, Vec<f64>>>>;
+
+    /// Recorder keeping every value recorded, so the tests can inspect them
+    #[derive(Default)]
+    struct DebuggingRecorder {
+        values: Values,
+    }
+
+    /// Counter or histogram handle of the debugging recorder
+    struct DebuggingHandle {
+        values: Values,
+        key: String,
+    }
+
+    impl DebuggingHandle {
+        fn push(&self, value: f64) {
+            let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+            values.entry(self.key.clone()).or_default().push(value);
+        }
+    }
+
+    impl CounterFn for DebuggingHandle {
+        fn increment(&self, value: u64) {
+            self.push(value as f64);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.push(value as f64);
+        }
+    }
+
+    impl HistogramFn for DebuggingHandle {
+        fn record(&self, value: f64) {
+            self.push(value);
+        }
+    }
+
+    impl DebuggingRecorder {
+        fn handle(&self, key: &Key) -> Arc<DebuggingHandle> {
+            let labels: Vec<String> = key
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect();
+            Arc::new(DebuggingHandle {
+                values: self.values.clone(),
+                key: format!("{}{{{}}}", key.name(), labels.join(",")),
+            })
+        }
+    }
+
+    impl Recorder for DebuggingRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[test]
+    fn metrics_are_reported_to_the_installed_recorder() {
+        let recorder = DebuggingRecorder::default();
+        let result = metrics::with_local_recorder(&recorder, || {
+            record_created(HandleKind::Test);
+            record_close(true);
+            record_close(true);
+            record_close(false);
+            record_coverage_payload();
+            time_ffi_call("topt_suite_close", || 42)
+        });
+        assert_eq!(result, 42);
+
+        let values = recorder.values.lock().unwrap();
+        assert_eq!(
+            values[&format!("{}{{kind=test}}", HANDLES_CREATED_METRIC)],
+            [1.0]
+        );
+        assert_eq!(
+            values[&format!("{}{{result=ok}}", CLOSES_METRIC)],
+            [1.0, 1.0]
+        );
+        assert_eq!(values[&format!("{}{{result=error}}", CLOSES_METRIC)], [1.0]);
+        assert_eq!(values[&format!("{}{{}}", COVERAGE_PAYLOADS_METRIC)], [1.0]);
+        let durations =
+            &values[&format!("{}{{function=topt_suite_close}}", FFI_CALL_DURATION_METRIC)];
+        assert_eq!(durations.len(), 1);
+        assert!(durations[0] >= 0.0);
+        assert_eq!(values.len(), 5);
+    }
+}
@@ -0,0 +1,191 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Session builder module for configuring the library and starting a session
+//!
+//! This module provides a builder that collects the initialization options,
+//! including environment variables handed to the native library, initializes
+//! the library and creates the test session in one call.
+use crate::test_optimization::test_optimization::{LANGUAGE_NAME, RUNTIME_NAME};
+use crate::test_optimization::*;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Environment variable enabling agentless mode
+pub const DD_CIVISIBILITY_AGENTLESS_ENABLED_ENV: &str = "DD_CIVISIBILITY_AGENTLESS_ENABLED";
+/// Environment variable holding the Datadog API key
+pub const DD_API_KEY_ENV: &str = "DD_API_KEY";
+/// Environment variable holding the Datadog site
+pub const DD_SITE_ENV: &str = "DD_SITE";
+/// Environment variable capping the threads running Go code in the native library
+pub const GOMAXPROCS_ENV: &str = "GOMAXPROCS";
+
+/// Environment variables whose values are replaced in the `Debug` output
+const REDACTED_ENVIRONMENT_VARIABLES: [&str; 1] = [DD_API_KEY_ENV];
+
+#[derive(Clone, Default)]
+/// Builds the initialization options of the library and starts a test session
+///
+/// ```ignore
+/// let session = TestSessionBuilder::new()
+///     .framework("my-framework", "1.0.0")
+///     .agentless(&api_key, "datadoghq.eu")
+///     .start()?;
+/// ```
+pub struct TestSessionBuilder {
+    /// The testing framework name
+    framework: Option<String>,
+    /// The testing framework version
+    framework_version: Option<String>,
+    /// The working directory, the current one when `None`
+    working_directory: Option<PathBuf>,
+    /// Whether to use the mock tracer
+    use_mock_tracer: bool,
+    /// Environment variables set before initializing, in order
+    environment_variables: Vec<(String, String)>,
+    /// Whether agentless mode was requested
+    agentless: bool,
+    /// The naming scheme of the test spans, the current one when `None`
+    naming_scheme: Option<NamingScheme>,
+}
+
+impl TestSessionBuilder {
+    /// Creates a builder with the default options
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the testing framework name and version reported by the session
+    #[allow(dead_code)]
+    pub fn framework(mut self, name: impl AsRef<str>, version: impl AsRef<str>) -> Self {
+        self.framework = Some(name.as_ref().to_string());
+        self.framework_version = Some(version.as_ref().to_string());
+        self
+    }
+
+    /// Sets the working directory, see `TestOptimization::init_with_working_dir`
+    #[allow(dead_code)]
+    pub fn working_directory(mut self, working_directory: impl Into<PathBuf>) -> Self {
+        self.working_directory = Some(working_directory.into());
+        self
+    }
+
+    /// Sets whether the mock tracer is used instead of sending the spans
+    #[allow(dead_code)]
+    pub fn mock_tracer(mut self, use_mock_tracer: bool) -> Self {
+        self.use_mock_tracer = use_mock_tracer;
+        self
+    }
+
+    /// Sets the naming scheme of the test spans, see `TestOptimization::set_naming_scheme`
+    #[allow(dead_code)]
+    pub fn naming_scheme(mut self, naming_scheme: NamingScheme) -> Self {
+        self.naming_scheme = Some(naming_scheme);
+        self
+    }
+
+    /// Sets an environment variable in the init options; a later value for the same name wins
+    ///
+    /// The native library sets every init option variable in the process environment when
+    /// initializing, so they stay visible to the whole process afterwards, secrets included.
+    #[allow(dead_code)]
+    pub fn env(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.environment_variables
+            .push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    /// Sends the data directly to Datadog instead of through an agent
+    ///
+    /// Sets `DD_CIVISIBILITY_AGENTLESS_ENABLED`, `DD_API_KEY` and `DD_SITE`. The site is the
+    /// one of the Datadog account: `datadoghq.com` (US1, the default when empty),
+    /// `us3.datadoghq.com`, `us5.datadoghq.com`, `datadoghq.eu` (EU1), `ap1.datadoghq.com`
+    /// or `ddog-gov.com` (US1-FED). `start` fails with `InitError::MissingApiKey` if the
+    /// API key is empty. The key is redacted from the `Debug` output of the builder, but like
+    /// every init option it ends up in the process environment as `DD_API_KEY` (see `env`).
+    #[allow(dead_code)]
+    pub fn agentless(mut self, api_key: &str, site: &str) -> Self {
+        self.agentless = true;
+        self = self
+            .env(DD_CIVISIBILITY_AGENTLESS_ENABLED_ENV, "true")
+            .env(DD_API_KEY_ENV, api_key);
+        if !site.is_empty() {
+            self = self.env(DD_SITE_ENV, site);
+        }
+        self
+    }
+
+    /// Caps the threads the native library runs its Go code on, e.g. its background flushes,
+    /// so they don't contend with the tests on small CI runners
+    ///
+    /// Sets `GOMAXPROCS`, whose default is the number of CPUs. The Go runtime reads it when the
+    /// library is loaded, so the native library applies the value set by the init options when
+    /// initializing. `0` is ignored.
+    #[allow(dead_code)]
+    pub fn max_flush_concurrency(self, max_concurrency: usize) -> Self {
+        if max_concurrency == 0 {
+            return self;
+        }
+        self.env(GOMAXPROCS_ENV, max_concurrency.to_string())
+    }
+
+    /// Initializes the library with the options and creates the test session
+    ///
+    /// Fails without initializing if the options are invalid. Like the other init functions,
+    /// only the first initialization of the process has an effect on the native library.
+    #[allow(dead_code)]
+    pub fn start(self) -> Result<TestSession, InitError> {
+        if self.agentless && self.environment_variable(DD_API_KEY_ENV).is_none_or(str::is_empty) {
+            return Err(InitError::MissingApiKey);
+        }
+        if let Some(naming_scheme) = self.naming_scheme {
+            TestOptimization::set_naming_scheme(naming_scheme);
+        }
+        TestOptimization::try_init_with_environment(
+            LANGUAGE_NAME,
+            RUNTIME_NAME,
+            TestOptimization::runtime_version(),
+            self.working_directory.as_ref(),
+            self.use_mock_tracer,
+            &self.environment_variables,
+        )?;
+        Ok(TestSession::create(self.framework, self.framework_version))
+    }
+
+    /// Gets the last value set for an environment variable
+    fn environment_variable(&self, key: &str) -> Option<&str> {
+        self.environment_variables
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl fmt::Debug for TestSessionBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let environment_variables: Vec<(&str, &str)> = self
+            .environment_variables
+            .iter()
+            .map(|(key, value)| {
+                if REDACTED_ENVIRONMENT_VARIABLES.contains(&key.as_str()) {
+                    (key.as_str(), "<redacted>")
+                } else {
+                    (key.as_str(), value.as_str())
+                }
+            })
+            .collect();
+        f.debug_struct("TestSessionBuilder")
+            .field("framework", &self.framework)
+            .field("framework_version", &self.framework_version)
+            .field("working_directory", &self.working_directory)
+            .field("use_mock_tracer", &self.use_mock_tracer)
+            .field("environment_variables", &environment_variables)
+            .field("agentless", &self.agentless)
+            .field("naming_scheme", &self.naming_scheme)
+            .finish()
+    }
+}
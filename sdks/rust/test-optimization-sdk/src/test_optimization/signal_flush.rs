@@ -0,0 +1,106 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Signal flush module for keeping the data of cancelled CI jobs
+//!
+//! This module provides a SIGTERM/SIGINT handler that closes the open handles and
+//! shuts the library down, so the buffered spans are sent before the process dies.
+use crate::test_optimization::logging;
+use crate::test_optimization::*;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use signal_hook::low_level::emulate_default_handler;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// Whether the signal flush has been installed
+static SIGNAL_FLUSH_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+impl TestOptimization {
+    /// Flush the spans when the process receives SIGTERM or SIGINT (unix only)
+    ///
+    /// The signal handler itself only records the signal (through a self-pipe, which is
+    /// async-signal-safe); a dedicated thread then closes the open handles with
+    /// `close_all_open(TestStatus::Fail)`, shuts the library down, and terminates the process
+    /// the way the signal would have. Returns `false` if already installed or if the handler
+    /// couldn't be registered.
+    ///
+    /// Caveats: SIGKILL (e.g. once a CI grace period expires) can't be caught, so the flush
+    /// has to finish within that period. Handlers registered later for the same signals by the
+    /// application run too, but the process still terminates after the flush.
+    #[allow(dead_code)]
+    pub fn install_signal_flush() -> bool {
+        if SIGNAL_FLUSH_INSTALLED.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        let mut signals = match Signals::new([SIGTERM, SIGINT]) {
+            Ok(signals) => signals,
+            Err(e) => {
+                logging::write(
+                    LogLevel::Error,
+                    format_args!("failed to install the signal flush: {}", e),
+                );
+                SIGNAL_FLUSH_INSTALLED.store(false, Ordering::Release);
+                return false;
+            }
+        };
+        let spawned = thread::Builder::new()
+            .name("test-optimization-signal-flush".to_string())
+            .spawn(move || flush_on_first_signal(signals.forever(), flush, terminate));
+        spawned.is_ok()
+    }
+}
+
+/// Runs the flush on the first signal received, then terminates with that signal
+fn flush_on_first_signal(
+    signals: impl IntoIterator<Item = i32>,
+    flush: impl FnOnce(),
+    terminate: impl FnOnce(i32),
+) {
+    if let Some(signal) = signals.into_iter().next() {
+        flush();
+        terminate(signal);
+    }
+}
+
+/// Closes the open handles as failed and shuts the library down, sending the buffered spans
+fn flush() {
+    TestOptimization::close_all_open(TestStatus::Fail);
+    TestOptimization::shutdown();
+}
+
+/// Terminates the process the way the signal would have
+fn terminate(signal: i32) {
+    if emulate_default_handler(signal).is_err() {
+        process::exit(128 + signal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::iter;
+
+    #[test]
+    fn the_first_signal_flushes_then_terminates() {
+        let calls = RefCell::new(Vec::new());
+        flush_on_first_signal(
+            [SIGTERM, SIGINT],
+            || calls.borrow_mut().push("flush".to_string()),
+            |signal| calls.borrow_mut().push(format!("terminate {}", signal)),
+        );
+        assert_eq!(calls.into_inner(), ["flush".to_string(), format!("terminate {}", SIGTERM)]);
+
+        let called = RefCell::new(false);
+        flush_on_first_signal(
+            iter::empty(),
+            || *called.borrow_mut() = true,
+            |_| *called.borrow_mut() = true,
+        );
+        assert!(!called.into_inner());
+    }
+}
@@ -8,9 +8,101 @@
 //! This module provides functionality for creating and managing spans,
 //! setting tags, error information, and closing spans.
 use crate::test_optimization::lib::*;
+use crate::test_optimization::registry;
 use crate::test_optimization::utils::*;
+use crate::test_optimization::time::system_time_to_unix;
+use crate::test_optimization::{tags, HandleKind, TagValue};
+use std::any::Any;
 use std::ffi::{c_char, CString};
+use std::fmt;
 use std::ptr::null_mut;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Sampling priority dropping the trace on user request
+pub const SAMPLING_PRIORITY_USER_REJECT: i32 = -1;
+/// Sampling priority dropping the trace on the sampler decision
+pub const SAMPLING_PRIORITY_AUTO_REJECT: i32 = 0;
+/// Sampling priority keeping the trace on the sampler decision
+pub const SAMPLING_PRIORITY_AUTO_KEEP: i32 = 1;
+/// Sampling priority keeping the trace on user request
+pub const SAMPLING_PRIORITY_USER_KEEP: i32 = 2;
+
+#[derive(Debug, Clone, Default)]
+/// Builds every option of the native span creation, for `Span::create_with_options`
+///
+/// Unlike `Span::create`, nothing is defaulted on the SDK side: unset names are left to the
+/// native library and the start time is the current one only when unset. The SDK keeps the
+/// strings alive for the duration of the call.
+pub struct SpanStartOptions {
+    /// The operation name of the span
+    operation_name: String,
+    /// The service name, the default service when `None`
+    service_name: Option<String>,
+    /// The resource name, the operation name when `None`
+    resource_name: Option<String>,
+    /// The span type
+    span_type: Option<String>,
+    /// The start time, the current time when `None`
+    start_time: Option<SystemTime>,
+    /// String tags set when the span is created, in order
+    string_tags: Vec<(String, String)>,
+    /// Numeric tags set when the span is created, in order
+    number_tags: Vec<(String, f64)>,
+}
+impl SpanStartOptions {
+    /// Creates the options of a span with the given operation name
+    #[allow(dead_code)]
+    pub fn new(operation_name: impl AsRef<str>) -> Self {
+        Self {
+            operation_name: operation_name.as_ref().to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the service name
+    #[allow(dead_code)]
+    pub fn service_name(mut self, service_name: impl AsRef<str>) -> Self {
+        self.service_name = Some(service_name.as_ref().to_string());
+        self
+    }
+
+    /// Sets the resource name
+    #[allow(dead_code)]
+    pub fn resource_name(mut self, resource_name: impl AsRef<str>) -> Self {
+        self.resource_name = Some(resource_name.as_ref().to_string());
+        self
+    }
+
+    /// Sets the span type
+    #[allow(dead_code)]
+    pub fn span_type(mut self, span_type: impl AsRef<str>) -> Self {
+        self.span_type = Some(span_type.as_ref().to_string());
+        self
+    }
+
+    /// Sets the start time, e.g. for work measured before the span could be created
+    #[allow(dead_code)]
+    pub fn start_time(mut self, start_time: SystemTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    /// Adds a string tag set when the span is created
+    #[allow(dead_code)]
+    pub fn string_tag(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.string_tags
+            .push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    /// Adds a numeric tag set when the span is created
+    #[allow(dead_code)]
+    pub fn number_tag(mut self, key: impl AsRef<str>, value: f64) -> Self {
+        self.number_tags.push((key.as_ref().to_string(), value));
+        self
+    }
+}
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -20,9 +112,22 @@ pub struct Span {
     pub span_id: u64,
     /// The ID of the parent span, if any
     pub parent_id: u64,
+    /// Closed flag shared by the clones of this span
+    pub(in crate::test_optimization) closed: CloseFlag,
+    /// Operation name of the span
+    pub(in crate::test_optimization) name: Arc<str>,
 }
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        registry::fmt_handle(f, HandleKind::Span, &self.name, self.span_id)
+    }
+}
+
 impl Span {
     /// Creates a new span with the specified parameters
+    ///
+    /// An empty `service_name` reports the span under the default service (see
+    /// `TestOptimization::set_service`), a non-empty one overrides it.
     #[allow(dead_code)]
     pub fn create(
         parent_id: u64,
@@ -39,7 +144,11 @@ impl Span {
 
         let span_start_options = topt_SpanStartOptions {
             operation_name: operation_name_cstring.as_ptr() as *mut c_char,
-            service_name: service_name_cstring.as_ptr() as *mut c_char,
+            service_name: if service_name.as_ref().is_empty() {
+                null_mut()
+            } else {
+                service_name_cstring.as_ptr() as *mut c_char
+            },
             resource_name: resource_name_cstring.as_ptr() as *mut c_char,
             span_type: span_type_cstring.as_ptr() as *mut c_char,
             start_time: &mut now,
@@ -51,12 +160,107 @@ impl Span {
             topt_span_create(parent_id, span_start_options)
         };
 
-        Self { span_id: span_result.span_id, parent_id }
+        let closed = registry::register(
+            span_result.span_id,
+            HandleKind::Span,
+            operation_name.as_ref(),
+            parent_id,
+            now,
+        );
+        Self {
+            span_id: span_result.span_id,
+            parent_id,
+            closed,
+            name: operation_name.as_ref().into(),
+        }
+    }
+
+    /// Creates a new span with every native start option, see `SpanStartOptions`
+    #[allow(dead_code)]
+    pub fn create_with_options(parent_id: u64, options: &SpanStartOptions) -> Self {
+        let optional_cstring = |value: &Option<String>| {
+            value.as_deref().map(|value| CString::new(value).unwrap())
+        };
+        let optional_ptr = |value: &Option<CString>| {
+            value.as_ref().map_or(null_mut(), |value| value.as_ptr() as *mut c_char)
+        };
+        let operation_name_cstring = CString::new(options.operation_name.as_str()).unwrap();
+        let service_name_cstring = optional_cstring(&options.service_name);
+        let resource_name_cstring = optional_cstring(&options.resource_name);
+        let span_type_cstring = optional_cstring(&options.span_type);
+        let mut start_time = options.start_time.map_or_else(get_now, system_time_to_unix);
+
+        // Keep the tag CStrings alive until the span is created
+        let string_tag_cstrings: Vec<(CString, CString)> = options
+            .string_tags
+            .iter()
+            .map(|(key, value)| {
+                (CString::new(key.as_str()).unwrap(), CString::new(value.as_str()).unwrap())
+            })
+            .collect();
+        let number_tag_cstrings: Vec<(CString, f64)> = options
+            .number_tags
+            .iter()
+            .map(|(key, value)| (CString::new(key.as_str()).unwrap(), *value))
+            .collect();
+        let mut string_tag_pairs: Vec<topt_KeyValuePair> = string_tag_cstrings
+            .iter()
+            .map(|(key, value)| topt_KeyValuePair {
+                key: key.as_ptr() as *mut c_char,
+                value: value.as_ptr() as *mut c_char,
+            })
+            .collect();
+        let mut number_tag_pairs: Vec<topt_KeyNumberPair> = number_tag_cstrings
+            .iter()
+            .map(|(key, value)| topt_KeyNumberPair {
+                key: key.as_ptr() as *mut c_char,
+                value: *value,
+            })
+            .collect();
+        let mut string_tags = topt_KeyValueArray {
+            data: string_tag_pairs.as_mut_ptr(),
+            len: string_tag_pairs.len(),
+        };
+        let mut number_tags = topt_KeyNumberArray {
+            data: number_tag_pairs.as_mut_ptr(),
+            len: number_tag_pairs.len(),
+        };
+
+        let span_start_options = topt_SpanStartOptions {
+            operation_name: operation_name_cstring.as_ptr() as *mut c_char,
+            service_name: optional_ptr(&service_name_cstring),
+            resource_name: optional_ptr(&resource_name_cstring),
+            span_type: optional_ptr(&span_type_cstring),
+            start_time: &mut start_time,
+            string_tags: &mut string_tags,
+            number_tags: &mut number_tags,
+        };
+
+        let span_result = unsafe {
+            topt_span_create(parent_id, span_start_options)
+        };
+
+        let closed = registry::register(
+            span_result.span_id,
+            HandleKind::Span,
+            &options.operation_name,
+            parent_id,
+            start_time,
+        );
+        Self {
+            span_id: span_result.span_id,
+            parent_id,
+            closed,
+            name: options.operation_name.as_str().into(),
+        }
     }
 
     /// Sets a string tag for this span
     #[allow(dead_code)]
     pub fn set_string_tag(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let key_cstring = CString::new(key.as_ref()).unwrap();
         let value_cstring = CString::new(value.as_ref()).unwrap();
         unsafe {
@@ -71,12 +275,47 @@ impl Span {
     /// Sets a numeric tag for this span
     #[allow(dead_code)]
     pub fn set_number_tag(&self, key: impl AsRef<str>, value: f64) -> bool {
-        let key_cstring = CString::new(key.as_ref()).unwrap();
+        !self.closed.is_closed() && self.set_number_tag_unchecked(key.as_ref(), value)
+    }
+
+    /// Sets a numeric tag for this span even once closed, e.g. the clock skew on close
+    fn set_number_tag_unchecked(&self, key: &str, value: f64) -> bool {
+        let key_cstring = CString::new(key).unwrap();
         unsafe {
             Bool_to_bool(topt_span_set_number_tag(self.span_id, key_cstring.as_ptr() as *mut c_char, value))
         }
     }
 
+    /// Sets a typed tag for this span, dispatching to the string or numeric setter
+    #[allow(dead_code)]
+    pub fn set_tag(&self, key: impl AsRef<str>, value: impl Into<TagValue>) -> bool {
+        let key = key.as_ref();
+        value.into().apply(
+            |value| self.set_string_tag(key, value),
+            |value| self.set_number_tag(key, value),
+        )
+    }
+
+    /// Sets the resource name of this span, e.g. once the query it runs is known
+    ///
+    /// The native library has no call to update the resource of a span, so this sets the
+    /// `resource.name` tag, which the tracer applies as the resource of the span.
+    #[allow(dead_code)]
+    pub fn set_resource_name(&self, name: &str) -> bool {
+        self.set_string_tag(tags::RESOURCE_NAME, name)
+    }
+
+    /// Sets the sampling priority of the trace of this span
+    ///
+    /// The values are the Datadog ones: `SAMPLING_PRIORITY_USER_REJECT` (-1) and
+    /// `SAMPLING_PRIORITY_AUTO_REJECT` (0) drop the trace, `SAMPLING_PRIORITY_AUTO_KEEP` (1)
+    /// and `SAMPLING_PRIORITY_USER_KEEP` (2) keep it. User priorities take precedence over
+    /// the sampling rules of the agent.
+    #[allow(dead_code)]
+    pub fn set_sampling_priority(&self, priority: i32) -> bool {
+        self.set_number_tag(tags::SAMPLING_PRIORITY, priority as f64)
+    }
+
     /// Sets error information for this span
     #[allow(dead_code)]
     pub fn set_error_info(
@@ -85,6 +324,9 @@ impl Span {
         error_message: impl AsRef<str>,
         error_stacktrace: impl AsRef<str>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let error_type_cstring = CString::new(error_type.as_ref()).unwrap();
         let error_message_cstring = CString::new(error_message.as_ref()).unwrap();
         let error_stacktrace_cstring = CString::new(error_stacktrace.as_ref()).unwrap();
@@ -99,12 +341,30 @@ impl Span {
         }
     }
 
+    /// Sets error information for this span from a panic payload, e.g. from `catch_unwind`
+    ///
+    /// The error type is `panic` and the message is the one passed to `panic!`; payloads that
+    /// aren't a string are reported as `Box<dyn Any>`.
+    #[allow(dead_code)]
+    pub fn set_error_from_panic(&self, payload: &(dyn Any + Send)) -> bool {
+        self.set_error_info(tags::PANIC_ERROR_TYPE, panic_message(payload), "")
+    }
+
     /// Closes this span
+    ///
+    /// Closing a span that is already closed, including through a clone, does nothing and
+    /// returns `false`.
     #[allow(dead_code)]
     pub fn close(&self) -> bool {
-        let mut now = get_now();
-        unsafe {
-            Bool_to_bool(topt_span_close(self.span_id, &mut now))
+        if !self.closed.close() {
+            return false;
         }
+        let (_, closed) = close_handle(
+            self.span_id,
+            "topt_span_close",
+            |key, value| self.set_number_tag_unchecked(key, value),
+            |now| unsafe { Bool_to_bool(topt_span_close(self.span_id, now)) },
+        );
+        closed
     }
 }
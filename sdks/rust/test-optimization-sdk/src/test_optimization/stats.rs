@@ -0,0 +1,71 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Stats module for counting what the SDK reports
+//!
+//! The native library doesn't expose counters of the spans it sends, so the SDK
+//! counts the closes and coverage payloads it hands over to the native library.
+use crate::test_optimization::sdk_metrics;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of spans closed successfully
+static SPANS_SENT: AtomicU64 = AtomicU64::new(0);
+/// Number of coverage payloads sent
+static COVERAGE_PAYLOADS_SENT: AtomicU64 = AtomicU64::new(0);
+/// Number of closes rejected by the native library
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Tests skipped by ITR and their estimated durations, by session ID
+static ITR_SKIPPED: Mutex<BTreeMap<u64, (u64, Duration)>> = Mutex::new(BTreeMap::new());
+
+/// Records the result of a native close call, returning it unchanged
+pub(in crate::test_optimization) fn record_close(result: bool) -> bool {
+    sdk_metrics::record_close(result);
+    if result {
+        SPANS_SENT.fetch_add(1, Ordering::Relaxed);
+    } else {
+        ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+    result
+}
+
+/// Records a coverage payload sent to the native library
+pub(in crate::test_optimization) fn record_coverage_payload() {
+    sdk_metrics::record_coverage_payload();
+    COVERAGE_PAYLOADS_SENT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records a test of the session skipped by ITR, with its estimated duration if known
+pub(in crate::test_optimization) fn record_itr_skip(
+    session_id: u64,
+    estimated_duration: Option<Duration>,
+) {
+    let mut itr_skipped = ITR_SKIPPED.lock().unwrap_or_else(|e| e.into_inner());
+    let (count, time_saved) = itr_skipped.entry(session_id).or_default();
+    *count += 1;
+    *time_saved += estimated_duration.unwrap_or_default();
+}
+
+/// Returns the tests skipped by ITR in the session and the sum of their estimated durations
+pub(in crate::test_optimization) fn itr_skipped(session_id: u64) -> (u64, Duration) {
+    ITR_SKIPPED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&session_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Returns the spans sent, coverage payloads sent and errors counted so far
+pub(in crate::test_optimization) fn snapshot() -> (u64, u64, u64) {
+    (
+        SPANS_SENT.load(Ordering::Relaxed),
+        COVERAGE_PAYLOADS_SENT.load(Ordering::Relaxed),
+        ERRORS.load(Ordering::Relaxed),
+    )
+}
@@ -0,0 +1,237 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Tags module for typed tag values and well-known tag keys
+//!
+//! This module provides the `TagValue` enum accepted by the `set_tag` methods,
+//! the keys of the tags the SDK and the native library set on spans, and the
+//! parsing of the `DD_TAGS` global tags.
+
+/// Name of the test, set by the native library on test spans
+pub const TEST_NAME: &str = "test.name";
+/// Name of the suite of the test, set by the native library on suite and test spans
+pub const TEST_SUITE: &str = "test.suite";
+/// Status of the test (`"pass"`, `"fail"` or `"skip"`), set by the native library on close
+pub const TEST_STATUS: &str = "test.status";
+/// Name of the test framework, set on sessions and modules
+pub const TEST_FRAMEWORK: &str = "test.framework";
+/// Version of the test framework, set on sessions and modules
+pub const TEST_FRAMEWORK_VERSION: &str = "test.framework_version";
+/// Parameters of a parameterized test, as the JSON string skippable tests are keyed on
+pub const TEST_PARAMETERS: &str = "test.parameters";
+/// Reason a test was skipped, set by the native library when closing with a skip reason
+pub const TEST_SKIP_REASON: &str = "test.skip_reason";
+/// Whether the test was skipped by the Intelligent Test Runner (`"true"`)
+pub const TEST_SKIPPED_BY_ITR: &str = "test.skipped_by_itr";
+/// Whether the test is a retry of a previous execution (`"true"`)
+pub const TEST_IS_RETRY: &str = "test.is_retry";
+/// Number of the retry, from 1 for the first retry, as a numeric tag
+pub const TEST_RETRY_ATTEMPT: &str = "test.retry_attempt";
+/// Status of the original execution of a retried test (`"pass"`, `"fail"` or `"skip"`)
+pub const TEST_RETRY_ORIGINAL_STATUS: &str = "test.retry_original_status";
+/// Whether the test is unknown to the backend (`"true"`), set when early flake detection is on
+pub const TEST_IS_NEW: &str = "test.is_new";
+/// Why the test was retried, see `RetryReason` (e.g. `"attempt_to_fix"`)
+pub const TEST_RETRY_REASON: &str = "test.retry_reason";
+/// Whether every execution of a retried test failed (`"true"`), set on the last execution
+pub const TEST_HAS_FAILED_ALL_RETRIES: &str = "test.has_failed_all_retries";
+/// Whether the test is attempted to be fixed (`"true"`), set on each of its executions
+pub const TEST_MANAGEMENT_IS_ATTEMPT_TO_FIX: &str = "test.test_management.is_attempt_to_fix";
+/// Whether every execution of a test attempted to be fixed passed, set on the last execution
+pub const TEST_MANAGEMENT_ATTEMPT_TO_FIX_PASSED: &str = "test.test_management.attempt_to_fix_passed";
+/// Code owners of the test, as a JSON array of owners (e.g. `["@org/team","dev@example.com"]`)
+pub const TEST_CODEOWNERS: &str = "test.codeowners";
+/// Content hashes of the source files of the test, as a JSON object from file to hex hash
+/// (defined by this SDK, not read by the backend)
+pub const TEST_SOURCE_HASHES: &str = "test.source.hashes";
+/// Lines changed in the current diff of the files of the test, as a JSON object from file to
+/// line ranges (e.g. `{"src/lib.rs":"3-5,9"}`)
+pub const TEST_CHANGED_LINES: &str = "test.changed_lines";
+/// Artifacts produced by the test, as a JSON array of `{"name","path","size"}` objects
+pub const TEST_ARTIFACTS: &str = "test.artifacts";
+/// Service of the span
+pub const SERVICE_NAME: &str = "service.name";
+/// Resource name of the span; setting it replaces the resource
+pub const RESOURCE_NAME: &str = "resource.name";
+/// Operation name of the span; setting it renames the span
+pub const SPAN_NAME: &str = "span.name";
+/// Repository URL of the git checkout
+pub const GIT_REPOSITORY_URL: &str = "git.repository_url";
+/// Branch of the git checkout
+pub const GIT_BRANCH: &str = "git.branch";
+/// Commit SHA of the git checkout
+pub const GIT_COMMIT_SHA: &str = "git.commit.sha";
+/// Message of the checked out commit
+pub const GIT_COMMIT_MESSAGE: &str = "git.commit.message";
+/// Author name of the checked out commit
+pub const GIT_COMMIT_AUTHOR_NAME: &str = "git.commit.author.name";
+/// Total number of tests of the session
+pub const SESSION_TESTS_TOTAL: &str = "test.session.tests_total";
+/// Number of passed tests of the session
+pub const SESSION_TESTS_PASSED: &str = "test.session.tests_passed";
+/// Number of failed tests of the session
+pub const SESSION_TESTS_FAILED: &str = "test.session.tests_failed";
+/// Number of skipped tests of the session, including the ones skipped by ITR
+pub const SESSION_TESTS_SKIPPED: &str = "test.session.tests_skipped";
+/// Number of tests of the session skipped by the Intelligent Test Runner
+pub const SESSION_TESTS_SKIPPED_BY_ITR: &str = "test.session.tests_skipped_by_itr";
+/// Number of flaky tests of the session
+pub const SESSION_TESTS_FLAKY: &str = "test.session.tests_flaky";
+/// Name of the CI provider
+pub const CI_PROVIDER_NAME: &str = "ci.provider.name";
+/// ID of the CI pipeline
+pub const CI_PIPELINE_ID: &str = "ci.pipeline.id";
+/// Name of the CI pipeline
+pub const CI_PIPELINE_NAME: &str = "ci.pipeline.name";
+/// Number of the CI pipeline
+pub const CI_PIPELINE_NUMBER: &str = "ci.pipeline.number";
+/// URL of the CI pipeline
+pub const CI_PIPELINE_URL: &str = "ci.pipeline.url";
+/// Name of the CI job
+pub const CI_JOB_NAME: &str = "ci.job.name";
+/// URL of the CI job
+pub const CI_JOB_URL: &str = "ci.job.url";
+/// Name of the CI stage
+pub const CI_STAGE_NAME: &str = "ci.stage.name";
+/// Name of the CI node running the job
+pub const CI_NODE_NAME: &str = "ci.node.name";
+/// Path of the CI workspace
+pub const CI_WORKSPACE_PATH: &str = "ci.workspace_path";
+/// Type of the error set with `set_error_info`
+pub const ERROR_TYPE: &str = "error.type";
+/// Message of the error set with `set_error_info`
+pub const ERROR_MESSAGE: &str = "error.message";
+/// Stack trace of the error set with `set_error_info` or `set_error_frames`
+pub const ERROR_STACK: &str = "error.stack";
+/// Error type set for panics by `set_error_from_panic`
+pub const PANIC_ERROR_TYPE: &str = "panic";
+/// Sampling priority of the trace, as a numeric tag (see `Span::set_sampling_priority`)
+pub const SAMPLING_PRIORITY: &str = "sampling.priority";
+/// Keeps the trace regardless of sampling when set to `"true"`
+pub const MANUAL_KEEP: &str = "manual.keep";
+/// Links to spans of other traces, as a JSON array (e.g. the remote parent of a session)
+pub const SPAN_LINKS: &str = "_dd.span_links";
+/// Clock skew in seconds when the finish time of a span was clamped to its start time
+pub const CLOCK_SKEW: &str = "_dd.clock_skew";
+
+/// Skip reason used for tests skipped by the Intelligent Test Runner
+pub const SKIPPED_BY_ITR_REASON: &str = "Skipped by Datadog Intelligent Test Runner";
+
+/// Environment variable holding the global tags, as `key1:value1,key2:value2`
+pub const DD_TAGS_ENV: &str = "DD_TAGS";
+
+/// Parses global tags in the `DD_TAGS` format (`key1:value1,key2:value2`)
+///
+/// Keys and values are trimmed, and a value may be double-quoted to contain commas
+/// (`key:"a,b"`), the quotes being removed. Only the first `:` separates the key, so
+/// values may contain colons. Malformed entries (empty, without a `:`, with an empty key
+/// or an unterminated quote) are skipped.
+#[allow(dead_code)]
+pub fn parse_dd_tags(value: &str) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut entry = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                entry.push(c);
+            }
+            ',' if !quoted => entries.push(std::mem::take(&mut entry)),
+            c => entry.push(c),
+        }
+    }
+    // an unterminated quote swallowed the rest of the string
+    if !quoted {
+        entries.push(entry);
+    }
+
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once(':')?;
+            let key = key.trim();
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            if key.is_empty() || key.contains('"') {
+                return None;
+            }
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Represents a tag value of any of the supported types
+///
+/// On the wire, `Str` and `Bool` are sent as string tags (booleans as `"true"`/`"false"`,
+/// following the Datadog convention) while `Num` and `Int` are sent as numeric tags.
+/// `Int` values are converted to `f64`, so integers beyond 2^53 lose precision.
+pub enum TagValue {
+    /// A string value
+    Str(String),
+    /// A floating point value
+    Num(f64),
+    /// An integer value
+    Int(i64),
+    /// A boolean value
+    Bool(bool),
+}
+
+impl TagValue {
+    /// Sets this value with the string or numeric tag setter of a handle, the common body of
+    /// the `set_tag` methods
+    pub(in crate::test_optimization) fn apply(
+        self,
+        set_string_tag: impl FnOnce(String) -> bool,
+        set_number_tag: impl FnOnce(f64) -> bool,
+    ) -> bool {
+        match self {
+            TagValue::Str(value) => set_string_tag(value),
+            TagValue::Bool(value) => set_string_tag(value.to_string()),
+            TagValue::Num(value) => set_number_tag(value),
+            TagValue::Int(value) => set_number_tag(value as f64),
+        }
+    }
+}
+
+impl From<String> for TagValue {
+    fn from(value: String) -> Self {
+        TagValue::Str(value)
+    }
+}
+
+impl From<&str> for TagValue {
+    fn from(value: &str) -> Self {
+        TagValue::Str(value.to_string())
+    }
+}
+
+impl From<f64> for TagValue {
+    fn from(value: f64) -> Self {
+        TagValue::Num(value)
+    }
+}
+
+impl From<i64> for TagValue {
+    fn from(value: i64) -> Self {
+        TagValue::Int(value)
+    }
+}
+
+impl From<i32> for TagValue {
+    fn from(value: i32) -> Self {
+        TagValue::Int(value as i64)
+    }
+}
+
+impl From<bool> for TagValue {
+    fn from(value: bool) -> Self {
+        TagValue::Bool(value)
+    }
+}
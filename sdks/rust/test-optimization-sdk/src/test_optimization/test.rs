@@ -8,15 +8,30 @@
 //! This module provides functionality for creating and managing tests,
 //! setting tags, error information, source code, coverage data, benchmark
 //! data, and closing tests with various statuses.
+use crate::test_optimization::analysis;
+use crate::test_optimization::efd;
+use crate::test_optimization::hooks;
 use crate::test_optimization::lib::*;
+use crate::test_optimization::mock_tracer::mock_tracer_options;
+use crate::test_optimization::registry;
+use crate::test_optimization::sdk_metrics;
+use crate::test_optimization::stats;
 use crate::test_optimization::utils::*;
 use crate::test_optimization::*;
 use std::alloc::{alloc, dealloc, Layout};
+use std::any::Any;
 use std::collections::HashMap;
+use std::error::Error;
 use std::ffi::{c_char, CString};
+use std::fmt::{self, Debug};
+use std::fs;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::Path;
 use std::ptr::null_mut;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
 /// Represents the possible statuses of a test execution
 pub enum TestStatus {
@@ -28,6 +43,200 @@ pub enum TestStatus {
     Skip = 2,
 }
 
+impl TestStatus {
+    /// Gets the process exit code for this status: 1 for `Fail`, 0 for `Pass` and `Skip`
+    #[allow(dead_code)]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TestStatus::Fail => 1,
+            TestStatus::Pass | TestStatus::Skip => 0,
+        }
+    }
+
+    /// Gets the value of the `test.status` tag for this status
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestStatus::Pass => "pass",
+            TestStatus::Fail => "fail",
+            TestStatus::Skip => "skip",
+        }
+    }
+
+    /// Gets the status for a value of the `test.status` tag, `None` for unknown values
+    #[allow(dead_code)]
+    pub fn from_tag(value: &str) -> Option<Self> {
+        match value {
+            "pass" => Some(TestStatus::Pass),
+            "fail" => Some(TestStatus::Fail),
+            "skip" => Some(TestStatus::Skip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents why a test was retried, reported as the `test.retry_reason` tag
+pub enum RetryReason {
+    /// Retry of a new test by early flake detection
+    EarlyFlakeDetection,
+    /// Retry of a failed test by automatic test retries (flaky test retries)
+    AutoTestRetry,
+    /// Retry of a test attempted to be fixed, see `Test::begin_attempt_to_fix`
+    AttemptToFix,
+    /// Retry decided by the test framework or the adapter itself
+    External,
+}
+
+impl RetryReason {
+    /// Gets the value of the `test.retry_reason` tag for this reason
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetryReason::EarlyFlakeDetection => "early_flake_detection",
+            RetryReason::AutoTestRetry => "auto_test_retry",
+            RetryReason::AttemptToFix => "attempt_to_fix",
+            RetryReason::External => "external",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents the measure type benchmark data is reported under
+///
+/// The well-known types are the ones the Datadog UI recognizes; `Custom` keeps any other
+/// measure type. Strings convert to the well-known type of the same name, and to `Custom`
+/// otherwise for forward compatibility.
+pub enum BenchmarkMeasureType {
+    /// Duration statistics of the benchmark runs (`duration`)
+    Duration,
+    /// Total run time of the benchmark (`run_time`)
+    RunTime,
+    /// Mean heap allocations per operation (`mean_heap_allocations`)
+    MeanHeapAllocations,
+    /// Total heap allocations (`total_heap_allocations`)
+    TotalHeapAllocations,
+    /// Application launch time (`application_launch`)
+    ApplicationLaunch,
+    /// Garbage collector statistics (`garbage_collector`)
+    GarbageCollector,
+    /// Total memory operations (`memory_total_operations`)
+    MemoryTotalOperations,
+    /// Any other measure type, sent as is
+    Custom(String),
+}
+
+impl BenchmarkMeasureType {
+    /// Gets the measure type as sent to the native library
+    #[allow(dead_code)]
+    pub fn as_str(&self) -> &str {
+        match self {
+            BenchmarkMeasureType::Duration => "duration",
+            BenchmarkMeasureType::RunTime => "run_time",
+            BenchmarkMeasureType::MeanHeapAllocations => "mean_heap_allocations",
+            BenchmarkMeasureType::TotalHeapAllocations => "total_heap_allocations",
+            BenchmarkMeasureType::ApplicationLaunch => "application_launch",
+            BenchmarkMeasureType::GarbageCollector => "garbage_collector",
+            BenchmarkMeasureType::MemoryTotalOperations => "memory_total_operations",
+            BenchmarkMeasureType::Custom(measure_type) => measure_type,
+        }
+    }
+}
+
+impl From<&str> for BenchmarkMeasureType {
+    fn from(value: &str) -> Self {
+        match value {
+            "duration" => BenchmarkMeasureType::Duration,
+            "run_time" => BenchmarkMeasureType::RunTime,
+            "mean_heap_allocations" => BenchmarkMeasureType::MeanHeapAllocations,
+            "total_heap_allocations" => BenchmarkMeasureType::TotalHeapAllocations,
+            "application_launch" => BenchmarkMeasureType::ApplicationLaunch,
+            "garbage_collector" => BenchmarkMeasureType::GarbageCollector,
+            "memory_total_operations" => BenchmarkMeasureType::MemoryTotalOperations,
+            _ => BenchmarkMeasureType::Custom(value.to_string()),
+        }
+    }
+}
+
+impl From<&String> for BenchmarkMeasureType {
+    fn from(value: &String) -> Self {
+        BenchmarkMeasureType::from(value.as_str())
+    }
+}
+
+impl From<String> for BenchmarkMeasureType {
+    fn from(value: String) -> Self {
+        BenchmarkMeasureType::from(value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents a frame of a structured stack trace, e.g. from the `backtrace` crate
+pub struct StackFrame {
+    /// The function name, demangled
+    #[allow(dead_code)]
+    pub function: String,
+    /// The source file, empty if unknown
+    #[allow(dead_code)]
+    pub file: String,
+    /// The line in the source file, 0 if unknown
+    #[allow(dead_code)]
+    pub line: u32,
+}
+
+impl StackFrame {
+    /// Formats frames as a stack trace, innermost frame first
+    ///
+    /// This follows the layout of Rust backtraces: one `{index}: {function}` line per frame,
+    /// followed by an indented `at {file}:{line}` line when the file is known (without the
+    /// line number when it is 0).
+    #[allow(dead_code)]
+    pub fn format_stacktrace(frames: &[StackFrame]) -> String {
+        let mut stacktrace = String::new();
+        for (index, frame) in frames.iter().enumerate() {
+            stacktrace.push_str(&format!("{:>4}: {}\n", index, frame.function));
+            if !frame.file.is_empty() {
+                if frame.line == 0 {
+                    stacktrace.push_str(&format!("             at {}\n", frame.file));
+                } else {
+                    stacktrace.push_str(&format!("             at {}:{}\n", frame.file, frame.line));
+                }
+            }
+        }
+        stacktrace
+    }
+}
+
+/// Represents what a test body returns, e.g. `()` or `Result<(), E>`, see `Test::run`
+pub trait TestOutcome {
+    /// Gets the error type and message when the outcome is a failure
+    fn error(&self) -> Option<(&'static str, String)>;
+
+    /// Gets the outcome returned for a test that was skipped without running its body
+    fn skipped() -> Self;
+}
+
+impl TestOutcome for () {
+    fn error(&self) -> Option<(&'static str, String)> {
+        None
+    }
+
+    fn skipped() -> Self {}
+}
+
+impl<E: Debug> TestOutcome for Result<(), E> {
+    /// The message is the `Debug` representation, as printed by the test harness
+    fn error(&self) -> Option<(&'static str, String)> {
+        self.as_ref()
+            .err()
+            .map(|error| (std::any::type_name::<E>(), format!("{:?}", error)))
+    }
+
+    fn skipped() -> Self {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Represents an individual test within a test suite
 pub struct Test {
@@ -39,39 +248,181 @@ pub struct Test {
     pub(in crate::test_optimization) suite_id: u64,
     /// The unique identifier for this test
     pub test_id: u64,
+    /// Closed flag shared by the clones of this test
+    pub(in crate::test_optimization) closed: CloseFlag,
+    /// When the test was created, on the monotonic clock
+    pub(in crate::test_optimization) started_at: Instant,
+    /// Name of the test, empty if unknown
+    pub(in crate::test_optimization) name: Arc<str>,
+    /// Name of the suite of the test, empty if unknown
+    pub(in crate::test_optimization) suite_name: Arc<str>,
 }
+impl fmt::Display for Test {
+    /// Writes the test as `Test "my_test" [id=42] in suite "My Suite"`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        registry::fmt_handle(f, HandleKind::Test, &self.name, self.test_id)?;
+        if !self.suite_name.is_empty() {
+            write!(f, " in suite {:?}", self.suite_name)?;
+        }
+        Ok(())
+    }
+}
+
 impl Test {
+    /// Creates the handle of a test of `suite` already created in the native library
+    pub(in crate::test_optimization) fn new(
+        suite: &TestSuite,
+        test_id: u64,
+        name: &str,
+        closed: CloseFlag,
+        started_at: Instant,
+    ) -> Self {
+        Self {
+            session_id: suite.session_id,
+            module_id: suite.module_id,
+            suite_id: suite.suite_id,
+            test_id,
+            closed,
+            started_at,
+            name: name.into(),
+            suite_name: suite.name.clone(),
+        }
+    }
+
+    /// Gets the time elapsed since the test was created
+    ///
+    /// This is measured on the monotonic clock (`Instant`), so it isn't affected by changes
+    /// of the system clock, unlike the duration reported to Datadog, which is the difference
+    /// of the system-clock start and finish times. It keeps growing after the test is closed.
+    /// Adapters can use it for duration-based decisions, e.g. with
+    /// `EfdSlowTestRetriesSettings::retries_for`.
+    #[allow(dead_code)]
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Creates a test under a suite known only by its IDs, e.g. received from another process
+    ///
+    /// The caller is responsible for passing IDs that belong together: the suite of that
+    /// module, of that session. They aren't checked against each other, and a mismatch
+    /// reports the test under the wrong parents. Returns `None` if an ID is zero.
+    #[allow(dead_code)]
+    pub fn create_under(
+        session_id: u64,
+        module_id: u64,
+        suite_id: u64,
+        name: impl AsRef<str>,
+    ) -> Option<Test> {
+        if session_id == 0 || module_id == 0 || suite_id == 0 {
+            return None;
+        }
+        let suite = TestSuite {
+            session_id,
+            module_id,
+            suite_id,
+            closed: CloseFlag::default(),
+            name: registry::name(suite_id).unwrap_or_default().into(),
+        };
+        Some(suite.create_test(name))
+    }
+
     /// Gets the parent test suite of this test
+    ///
+    /// The returned handle doesn't share the closed flag of the suite it was created from,
+    /// so only close one of them.
     #[allow(dead_code)]
     pub fn get_suite(&self) -> TestSuite {
-        TestSuite { suite_id: self.suite_id, module_id: self.module_id, session_id: self.session_id }
+        TestSuite {
+            suite_id: self.suite_id,
+            module_id: self.module_id,
+            session_id: self.session_id,
+            closed: CloseFlag::default(),
+            name: self.suite_name.clone(),
+        }
     }
 
     /// Sets a string tag for this test
     #[allow(dead_code)]
     pub fn set_string_tag(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let key_cstring = CString::new(key.as_ref()).unwrap();
         let value_cstring = CString::new(value.as_ref()).unwrap();
-        unsafe {
+        let result = unsafe {
             Bool_to_bool(topt_test_set_string_tag(
                 self.test_id,
                 key_cstring.as_ptr() as *mut c_char,
                 value_cstring.as_ptr() as *mut c_char,
             ))
+        };
+        if result {
+            registry::record_string_tag(self.test_id, key.as_ref(), value.as_ref());
         }
+        result
     }
 
     /// Sets a numeric tag for this test
     #[allow(dead_code)]
     pub fn set_number_tag(&self, key: impl AsRef<str>, value: f64) -> bool {
-        let key_cstring = CString::new(key.as_ref()).unwrap();
-        unsafe {
+        !self.closed.is_closed() && self.set_number_tag_unchecked(key.as_ref(), value)
+    }
+
+    /// Sets a numeric tag for this test even once closed, e.g. the clock skew on close
+    fn set_number_tag_unchecked(&self, key: &str, value: f64) -> bool {
+        let key_cstring = CString::new(key).unwrap();
+        let result = unsafe {
             Bool_to_bool(topt_test_set_number_tag(
                 self.test_id,
                 key_cstring.as_ptr() as *mut c_char,
                 value,
             ))
+        };
+        if result {
+            registry::record_number_tag(self.test_id, key, value);
         }
+        result
+    }
+
+    /// Gets a string tag previously set on this test through the SDK
+    ///
+    /// The native library has no tag getters, so this reads a Rust-side shadow of the
+    /// tags set with `set_string_tag`/`set_tag` (and the helpers built on them) while the
+    /// test is open. Tags the native library sets on its own (`test.name`, CI and git
+    /// tags, ...) are not included, and `None` is returned once the test is closed.
+    #[allow(dead_code)]
+    pub fn get_string_tag(&self, key: impl AsRef<str>) -> Option<String> {
+        registry::string_tag(self.test_id, key.as_ref())
+    }
+
+    /// Gets a numeric tag previously set on this test through the SDK
+    ///
+    /// Like `get_string_tag`, only tags set through the SDK on an open test are visible.
+    #[allow(dead_code)]
+    pub fn get_number_tag(&self, key: impl AsRef<str>) -> Option<f64> {
+        registry::number_tag(self.test_id, key.as_ref())
+    }
+
+    /// Gets all the string and numeric tags set on this test through the SDK, e.g. to export
+    /// them to a custom sink
+    ///
+    /// Like `get_string_tag`, this reads the Rust-side shadow of the tags: the tags the native
+    /// library sets on its own (`test.name`, `test.status`, CI and git tags, ...) are not
+    /// included, and both maps are empty once the test is closed. The `on_test_closed`
+    /// callbacks receive the same maps as they were when the test was closed.
+    #[allow(dead_code)]
+    pub fn tags(&self) -> (HashMap<String, String>, HashMap<String, f64>) {
+        registry::tags(self.test_id).unwrap_or_default()
+    }
+
+    /// Sets a typed tag for this test, dispatching to the string or numeric setter
+    #[allow(dead_code)]
+    pub fn set_tag(&self, key: impl AsRef<str>, value: impl Into<TagValue>) -> bool {
+        let key = key.as_ref();
+        value.into().apply(
+            |value| self.set_string_tag(key, value),
+            |value| self.set_number_tag(key, value),
+        )
     }
 
     /// Sets error information for this test
@@ -82,6 +433,9 @@ impl Test {
         error_message: impl AsRef<str>,
         error_stacktrace: impl AsRef<str>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let error_type_cstring = CString::new(error_type.as_ref()).unwrap();
         let error_message_cstring = CString::new(error_message.as_ref()).unwrap();
         let error_stacktrace_cstring = CString::new(error_stacktrace.as_ref()).unwrap();
@@ -95,6 +449,94 @@ impl Test {
         }
     }
 
+    /// Sets error information for this test from structured stack frames
+    ///
+    /// The native library only takes the stack trace as a string, so the frames are
+    /// formatted with `StackFrame::format_stacktrace`.
+    #[allow(dead_code)]
+    pub fn set_error_frames(
+        &self,
+        error_type: impl AsRef<str>,
+        error_message: impl AsRef<str>,
+        frames: &[StackFrame],
+    ) -> bool {
+        self.set_error_info(error_type, error_message, StackFrame::format_stacktrace(frames))
+    }
+
+    /// Sets error information for this test from a Rust error
+    ///
+    /// The error type is the Rust type name of `E`, the message is its `Display` and the
+    /// stack trace lists its chain of sources, one `Caused by: ...` line each.
+    #[allow(dead_code)]
+    pub fn set_error<E: Error + ?Sized>(&self, error: &E) -> bool {
+        let mut causes = String::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes.push_str(&format!("Caused by: {}\n", cause));
+            source = cause.source();
+        }
+        self.set_error_info(std::any::type_name::<E>(), error.to_string(), causes)
+    }
+
+    /// Runs a test body returning a `Result` and closes this test with its outcome
+    ///
+    /// `Ok` closes the test as `Pass`; `Err` sets the error with `set_error` and closes it
+    /// as `Fail`. A panic also closes the test as `Fail`, with the panic message as the
+    /// error, and is then resumed. Returns `true` if `f` succeeded and the test was closed.
+    #[allow(dead_code)]
+    pub fn run_result<T, E: Error>(&self, f: impl FnOnce() -> Result<T, E>) -> bool {
+        match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(Ok(_)) => self.close(TestStatus::Pass),
+            Ok(Err(error)) => {
+                self.set_error(&error);
+                self.close(TestStatus::Fail);
+                false
+            }
+            Err(payload) => self.fail_with_panic(payload),
+        }
+    }
+
+    /// Runs a test body and closes this test with its outcome, returning what the body returned
+    ///
+    /// Like `run_result`, but for any `TestOutcome`, so it fits `#[test]` functions as they
+    /// are written: `()` closes the test as `Pass`, and so does `Ok`, while `Err` sets the
+    /// error from its `Debug` representation and closes it as `Fail`. A panic closes the test
+    /// as `Fail` and is then resumed.
+    #[allow(dead_code)]
+    pub fn run<R: TestOutcome>(&self, body: impl FnOnce() -> R) -> R {
+        match catch_unwind(AssertUnwindSafe(body)) {
+            Ok(outcome) => {
+                match outcome.error() {
+                    Some((error_type, error_message)) => {
+                        self.set_error_info(error_type, error_message, "");
+                        self.close(TestStatus::Fail);
+                    }
+                    None => {
+                        self.close(TestStatus::Pass);
+                    }
+                }
+                outcome
+            }
+            Err(payload) => self.fail_with_panic(payload),
+        }
+    }
+
+    /// Sets error information for this test from a panic payload, e.g. from `catch_unwind`
+    ///
+    /// The error type is `panic` and the message is the one passed to `panic!`; payloads that
+    /// aren't a string are reported as `Box<dyn Any>`.
+    #[allow(dead_code)]
+    pub fn set_error_from_panic(&self, payload: &(dyn Any + Send)) -> bool {
+        self.set_error_info(tags::PANIC_ERROR_TYPE, panic_message(payload), "")
+    }
+
+    /// Closes this test as `Fail` with the message of a caught panic, then resumes the panic
+    fn fail_with_panic(&self, payload: Box<dyn Any + Send>) -> ! {
+        self.set_error_from_panic(payload.as_ref());
+        self.close(TestStatus::Fail);
+        resume_unwind(payload)
+    }
+
     /// Sets source code information for this test
     #[allow(dead_code)]
     pub fn set_test_source(
@@ -103,6 +545,9 @@ impl Test {
         start_line: *const i32,
         end_line: *const i32,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let file_cstring = CString::new(file.as_ref()).unwrap();
         unsafe {
             Bool_to_bool(topt_test_set_source(
@@ -114,23 +559,172 @@ impl Test {
         }
     }
 
-    /// Closes the test with a specified status
+    /// Sets the code owners of this test, e.g. parsed by the adapter from a CODEOWNERS file
+    ///
+    /// The owners are set as the `test.codeowners` tag, see `format_codeowners`. Returns
+    /// `false` without setting the tag if the list is empty or an owner is invalid.
     #[allow(dead_code)]
-    pub fn close(&self, status: TestStatus) -> bool {
-        let mut now = get_now();
-        let close_options = topt_TestCloseOptions {
-            status: status as u8,
-            finish_time: &mut now,
-            skip_reason: null_mut(),
-            unused01: null_mut(),
-            unused02: null_mut(),
-            unused03: null_mut(),
-            unused04: null_mut(),
-            unused05: null_mut(),
+    pub fn set_codeowners(&self, owners: &[&str]) -> bool {
+        match Self::format_codeowners(owners) {
+            Some(codeowners) => self.set_string_tag(tags::TEST_CODEOWNERS, codeowners),
+            None => false,
+        }
+    }
+
+    /// Formats code owners as the `test.codeowners` tag value, a JSON array of strings
+    ///
+    /// Owners use the CODEOWNERS syntax: `@user`, `@org/team` or an email address, without
+    /// whitespace or quotes. Returns `None` if the list is empty or an owner is invalid, e.g.
+    /// `["@org/team", "dev@example.com"]` gives `["@org/team","dev@example.com"]`.
+    #[allow(dead_code)]
+    pub fn format_codeowners(owners: &[&str]) -> Option<String> {
+        let is_valid = |owner: &str| {
+            let is_handle = owner.len() > 1 && owner.starts_with('@');
+            let is_email = owner
+                .split_once('@')
+                .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'));
+            (is_handle || is_email)
+                && !owner
+                    .chars()
+                    .any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '\\')
         };
-        unsafe {
-            Bool_to_bool(topt_test_close(self.test_id, close_options))
+        if owners.is_empty() || !owners.iter().all(|owner| is_valid(owner)) {
+            return None;
         }
+        let quoted: Vec<String> = owners.iter().map(|owner| format!("\"{}\"", owner)).collect();
+        Some(format!("[{}]", quoted.join(",")))
+    }
+
+    /// Records the content hash of a source file of this test, e.g. a file it covers
+    ///
+    /// `sha` is the hex SHA-1 (as `git hash-object` prints it) or SHA-256 of the file content.
+    /// The hashes set on a test are kept together in the `test.source.hashes` tag, a JSON
+    /// object from file to lowercase hash sorted by file (e.g. `{"src/lib.rs":"0123…"}`);
+    /// setting a file again replaces its hash. The tag is defined by this SDK and is
+    /// informational only: the Datadog backend doesn't read it, so it doesn't change how the
+    /// line range of `set_test_source` or the coverage are mapped. Returns `false` without
+    /// setting the tag if `sha` isn't a hex SHA-1 or SHA-256, or if the test is closed.
+    #[allow(dead_code)]
+    pub fn set_source_hash(&self, file: &str, sha: &str) -> bool {
+        if !matches!(sha.len(), 40 | 64) || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+            return false;
+        }
+        // The hashes are kept in the registry record, shared by the clones of the test
+        let Some(source_hashes) = registry::update(self.test_id, |handle| {
+            handle.source_hashes.insert(file.to_string(), sha.to_ascii_lowercase());
+            handle
+                .source_hashes
+                .iter()
+                .map(|(file, sha)| format!("{}:{}", json_string(file), json_string(sha)))
+                .collect::<Vec<String>>()
+                .join(",")
+        }) else {
+            return false;
+        };
+        self.set_string_tag(tags::TEST_SOURCE_HASHES, format!("{{{}}}", source_hashes))
+    }
+
+    /// Records the lines of a file of this test modified in the current diff, e.g. a PR
+    ///
+    /// Lines are 1-based; together with `set_test_source` and `set_coverage_data`, they tell
+    /// which changes a test exercises. The changed lines set on a test are kept together in
+    /// the `test.changed_lines` tag, a JSON object from file to sorted line ranges sorted by
+    /// file (e.g. `{"src/lib.rs":"3-5,9"}`); setting a file again adds to its lines.
+    ///
+    /// The tag is defined by this SDK and is informational only: the Datadog backend doesn't
+    /// read it, so it doesn't change which tests are skipped, in this run or later ones.
+    /// Returns `false` without setting the tag if `lines` is empty or contains line 0, or if
+    /// the test is closed.
+    #[allow(dead_code)]
+    pub fn set_changed_lines(&self, file: &str, lines: &[u32]) -> bool {
+        if lines.is_empty() || lines.contains(&0) {
+            return false;
+        }
+        // The lines are kept in the registry record, shared by the clones of the test
+        let Some(changed_lines) = registry::update(self.test_id, |handle| {
+            handle
+                .changed_lines
+                .entry(file.to_string())
+                .or_default()
+                .extend(lines.iter().copied());
+            handle
+                .changed_lines
+                .iter()
+                .map(|(file, lines)| {
+                    format!("{}:{}", json_string(file), json_string(&line_ranges(lines)))
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        }) else {
+            return false;
+        };
+        self.set_string_tag(tags::TEST_CHANGED_LINES, format!("{{{}}}", changed_lines))
+    }
+
+    /// Records an artifact produced by this test, e.g. a screenshot or a log file
+    ///
+    /// The native library has no artifact upload, so the file isn't uploaded: the artifacts
+    /// added to a test are kept together in the `test.artifacts` tag, a JSON array of
+    /// `{"name":…,"path":…,"size":…}` objects sorted by name, with the absolute path and the
+    /// size in bytes, so the file can be found on the runner or among the CI artifacts.
+    /// There is no size limit since only the reference is reported; adding a name again
+    /// replaces its artifact. Returns `false` without setting the tag if the file is missing
+    /// or isn't a regular file, or if the test is closed.
+    #[allow(dead_code)]
+    pub fn add_artifact(&self, name: &str, path: &Path) -> bool {
+        let Ok(path) = fs::canonicalize(path) else {
+            return false;
+        };
+        let Ok(metadata) = fs::metadata(&path) else {
+            return false;
+        };
+        if !metadata.is_file() {
+            return false;
+        }
+        let artifact = format!(
+            r#"{{"name":{},"path":{},"size":{}}}"#,
+            json_string(name),
+            json_string(&path.to_string_lossy()),
+            metadata.len()
+        );
+        // The artifacts are kept in the registry record, shared by the clones of the test
+        let Some(artifacts) = registry::update(self.test_id, |handle| {
+            handle.artifacts.insert(name.to_string(), artifact);
+            handle.artifacts.values().cloned().collect::<Vec<String>>().join(",")
+        }) else {
+            return false;
+        };
+        self.set_string_tag(tags::TEST_ARTIFACTS, format!("[{}]", artifacts))
+    }
+
+    /// Marks this test as a retry of a previous execution of the same test
+    ///
+    /// Sets `test.is_retry` to `"true"`, the numeric `test.retry_attempt` (from 1 for the
+    /// first retry) and `test.retry_original_status` to the status of the original execution.
+    #[allow(dead_code)]
+    pub fn mark_retry(&self, attempt: i32, original_status: TestStatus) -> bool {
+        self.set_string_tag(tags::TEST_IS_RETRY, "true")
+            & self.set_number_tag(tags::TEST_RETRY_ATTEMPT, f64::from(attempt))
+            & self.set_string_tag(tags::TEST_RETRY_ORIGINAL_STATUS, original_status.as_str())
+    }
+
+    /// Sets why this test was retried, as the `test.retry_reason` tag
+    ///
+    /// Call it along with `mark_retry` so the backend attributes the retry to the right
+    /// mechanism; the retries of `begin_attempt_to_fix` already have it.
+    #[allow(dead_code)]
+    pub fn set_retry_reason(&self, reason: RetryReason) -> bool {
+        self.set_string_tag(tags::TEST_RETRY_REASON, reason.as_str())
+    }
+
+    /// Closes the test with a specified status
+    ///
+    /// Closing a test that is already closed, including through a clone, does nothing and
+    /// returns `false`. If the clock went backwards since the test was created, the finish
+    /// time is clamped to the start time and the skew is set as the `_dd.clock_skew` tag.
+    #[allow(dead_code)]
+    pub fn close(&self, status: TestStatus) -> bool {
+        self.close_with_options(status, None)
     }
 
     /// Closes the test with a skip status and reason
@@ -138,71 +732,147 @@ impl Test {
     pub fn close_with_skip_reason(&self, skip_reason: impl AsRef<str>) -> bool {
         let skip_reason_ref = skip_reason.as_ref();
         if !skip_reason_ref.is_empty() {
-            let skip_reason_cstring = CString::new(skip_reason_ref).unwrap();
-            let mut now = get_now();
-            let close_options = topt_TestCloseOptions {
-                status: TestStatus::Skip as u8,
-                finish_time: &mut now,
-                skip_reason: skip_reason_cstring.as_ptr() as *mut c_char,
-                unused01: null_mut(),
-                unused02: null_mut(),
-                unused03: null_mut(),
-                unused04: null_mut(),
-                unused05: null_mut(),
-            };
-            unsafe { Bool_to_bool(topt_test_close(self.test_id, close_options)) }
+            self.close_with_options(TestStatus::Skip, Some(skip_reason_ref))
         } else {
             self.close(TestStatus::Skip)
         }
     }
 
+    /// Closes the test with a status and an optional skip reason, see `close`
+    fn close_with_options(&self, status: TestStatus, skip_reason: Option<&str>) -> bool {
+        if !self.closed.close() {
+            return false;
+        }
+        let skip_reason_cstring = skip_reason.map(|reason| CString::new(reason).unwrap());
+        analysis::record_test(self);
+        let (handle, result) = close_handle(
+            self.test_id,
+            "topt_test_close",
+            |key, value| self.set_number_tag_unchecked(key, value),
+            |now| {
+                let close_options = topt_TestCloseOptions {
+                    status: status as u8,
+                    finish_time: now,
+                    skip_reason: skip_reason_cstring
+                        .as_ref()
+                        .map_or(null_mut(), |reason| reason.as_ptr() as *mut c_char),
+                    unused01: null_mut(),
+                    unused02: null_mut(),
+                    unused03: null_mut(),
+                    unused04: null_mut(),
+                    unused05: null_mut(),
+                };
+                unsafe { Bool_to_bool(topt_test_close(self.test_id, close_options)) }
+            },
+        );
+        hooks::notify_test_closed(self.test_id, handle, status, skip_reason);
+        result
+    }
+
+    /// Closes the test as skipped by the Intelligent Test Runner
+    ///
+    /// Sets the `test.skipped_by_itr` tag and closes the test with a skip status and the
+    /// standard ITR skip reason, so the backend accounts for the time saved. The skip is
+    /// counted in `TestSession::itr_skipped_count`.
+    #[allow(dead_code)]
+    pub fn skip_by_itr(&self) -> bool {
+        self.skip_by_itr_with_estimate(None)
+    }
+
+    /// Closes the test as skipped by ITR, adding its estimated duration to the time saved
+    ///
+    /// The native library doesn't return historical durations with the skippable tests, so
+    /// the estimate comes from the caller (e.g. the duration of the last run recorded by the
+    /// adapter) and is summed in `TestSession::itr_estimated_time_saved`.
+    #[allow(dead_code)]
+    pub fn skip_by_itr_with_estimate(&self, estimated_duration: Option<Duration>) -> bool {
+        self.set_string_tag(tags::TEST_SKIPPED_BY_ITR, "true");
+        let closed = self.close_with_skip_reason(tags::SKIPPED_BY_ITR_REASON);
+        if closed {
+            stats::record_itr_skip(self.session_id, estimated_duration);
+        }
+        closed
+    }
+
+    /// Discards the test without reporting it
+    ///
+    /// The test is dropped from the native library without being closed, so its span is
+    /// never finished nor sent. Unlike a skip, which reports a skipped test, a discarded test
+    /// doesn't show up in the results or in the session counts, and it no longer counts as a
+    /// new test for early flake detection (see `TestSession::efd_status`). The mock tracer
+    /// keeps the unfinished span among its open spans until `MockTracer::reset`. Returns
+    /// `false` if the test is already closed or discarded.
+    #[allow(dead_code)]
+    pub fn discard(self) -> bool {
+        if !self.closed.close() {
+            return false;
+        }
+        registry::unregister(self.test_id);
+        efd::record_test_discarded(&self);
+        sdk_metrics::time_ffi_call("topt_test_discard", || unsafe {
+            Bool_to_bool(topt_test_discard(self.test_id))
+        })
+    }
+
+    /// Creates a custom span under this test, e.g. for setup or I/O done for it
+    #[allow(dead_code)]
+    pub fn create_span(
+        &self,
+        operation_name: impl AsRef<str>,
+        service_name: impl AsRef<str>,
+        resource_name: impl AsRef<str>,
+        span_type: impl AsRef<str>,
+    ) -> Span {
+        Span::create(self.test_id, operation_name, service_name, resource_name, span_type)
+    }
+
     /// Sets code coverage data for this test
+    ///
+    /// See `CoverageBuilder` for line bitmaps, or for coverage of a suite or a session.
     #[allow(dead_code)]
     pub fn set_coverage_data(&self, files: &[impl AsRef<str>]) {
-        unsafe {
-            // Allocate memory for an array of topt_TestCoverageFile
-            let layout = Layout::array::<topt_TestCoverageFile>(files.len()).unwrap();
-            let coverage_file_ptr = alloc(layout) as *mut topt_TestCoverageFile;
-            // Create a vector to hold the CString values so they remain valid
-            let mut cstrings = Vec::with_capacity(files.len());
-            for (idx, file) in files.iter().enumerate() {
-                // Create a CString from the file string
-                let cstr = CString::new(file.as_ref()).unwrap();
-                // Store the CString to keep it alive
-                cstrings.push(cstr);
-                // Get the pointer to the stored CString
-                let filename_ptr = cstrings.last().unwrap().as_ptr() as *mut c_char;
-                *coverage_file_ptr.add(idx) = topt_TestCoverageFile {
-                    filename: filename_ptr,
-                    bitmap: null_mut(),
-                    bitmap_len: 0,
-                };
-            }
-
-            let mut coverage_data = topt_TestCoverage {
-                session_id: self.session_id,
-                suite_id: self.suite_id,
-                test_id: self.test_id,
-                files: coverage_file_ptr,
-                files_len: files.len(),
-            };
+        CoverageBuilder::for_test(self).files(files).send();
+    }
 
+    /// Sets code coverage data for this test when it covers a single file
+    ///
+    /// Equivalent to `set_coverage_data(&[file])`, but the payload is built on the stack
+    /// instead of allocating the array of files. Nothing is sent once the test is closed.
+    #[allow(dead_code)]
+    pub fn set_coverage_file(&self, file: &str) {
+        if self.closed.is_closed() || !mock_tracer_options().capture_coverage {
+            return;
+        }
+        let file_cstring = CString::new(file).unwrap();
+        let mut coverage_files = [topt_TestCoverageFile {
+            filename: file_cstring.as_ptr() as *mut c_char,
+            bitmap: null_mut(),
+            bitmap_len: 0,
+        }];
+        let mut coverage_data = topt_TestCoverage {
+            session_id: self.session_id,
+            suite_id: self.suite_id,
+            test_id: self.test_id,
+            files: coverage_files.as_mut_ptr(),
+            files_len: coverage_files.len(),
+        };
+        sdk_metrics::time_ffi_call("topt_send_code_coverage_payload", || unsafe {
             // Send the code coverage payload
             topt_send_code_coverage_payload(&mut coverage_data, 1);
-
-            // Deallocate the memory for the array of topt_TestCoverageFile
-            dealloc(coverage_file_ptr as *mut u8, layout);
-            // The CString objects in `cstrings` are automatically freed when they go out of scope.
-        }
+        });
+        stats::record_coverage_payload();
     }
 
     /// Sets benchmark string data for this test
     #[allow(dead_code)]
     pub fn set_benchmark_string_data<K: AsRef<str>, V: AsRef<str>>(
         &self,
-        measure_type: impl AsRef<str>,
+        measure_type: impl Into<BenchmarkMeasureType>,
         data: &HashMap<K, V>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         // If there is no data, we return success.
         let num_pairs = data.len();
         if num_pairs == 0 {
@@ -238,7 +908,7 @@ impl Test {
             data: kv_array_ptr,
             len: num_pairs,
         };
-        let measure_type_c = CString::new(measure_type.as_ref()).unwrap();
+        let measure_type_c = CString::new(measure_type.into().as_str()).unwrap();
         // Call the FFI function.
         let result = unsafe {
             Bool_to_bool(topt_test_set_benchmark_string_data(
@@ -256,9 +926,12 @@ impl Test {
     #[allow(dead_code)]
     pub fn set_benchmark_number_data<K: AsRef<str>>(
         &self,
-        measure_type: impl AsRef<str>,
+        measure_type: impl Into<BenchmarkMeasureType>,
         data: &HashMap<K, f64>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let num_pairs = data.len();
         if num_pairs == 0 {
             return true;
@@ -286,7 +959,7 @@ impl Test {
             data: kn_array_ptr,
             len: num_pairs,
         };
-        let measure_type_c = CString::new(measure_type.as_ref()).unwrap();
+        let measure_type_c = CString::new(measure_type.into().as_str()).unwrap();
         let result = unsafe {
             Bool_to_bool(topt_test_set_benchmark_number_data(
                 self.test_id,
@@ -299,8 +972,14 @@ impl Test {
     }
 
     /// Write a log message for this test
+    ///
+    /// Returns `false` without sending anything when logs aren't captured (see
+    /// `MockTracerOptions::capture_logs`), or when the test is closed.
     #[allow(dead_code)]
     pub fn log(&self, message: impl AsRef<str>, tags: Option<impl AsRef<str>>) -> bool {
+        if self.closed.is_closed() || !mock_tracer_options().capture_logs {
+            return false;
+        }
         let message_cstring = CString::new(message.as_ref()).unwrap();
         let tags_cstring = tags.map(|wd| CString::new(wd.as_ref()).unwrap());
         unsafe {
@@ -0,0 +1,105 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Test builder module for creating a test with its source, tags and parameters
+//!
+//! This module provides a builder that collects the creation-time options of a test,
+//! e.g. during discovery, and applies them when the test is started.
+use crate::test_optimization::*;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone)]
+/// Builds a test of a suite with its source, tags and parameters, see `TestSuite::test_builder`
+///
+/// The native library has no call creating a test with options, so `start` still creates the
+/// test and then sets its source and each tag, one native call apiece. What the builder saves
+/// is the calls that would be overwritten: a key is only set once, with its last value, even
+/// when it is also inherited from the suite (`TestSuite::set_inherited_tag`). A test with a
+/// source and `n` distinct tags, including the inherited ones, takes `n + 2` native calls
+/// (plus one when `NamingScheme` renames the test spans, and one when early flake detection
+/// tags the test as new).
+///
+/// ```ignore
+/// let test = suite
+///     .test_builder("my_test")
+///     .source("src/lib.rs", 10..=20)
+///     .tag("test.category", "integration")
+///     .number_tag("test.weight", 3.0)
+///     .parameters(r#"{"arguments":{"a":"1"},"metadata":{}}"#)
+///     .start();
+/// ```
+pub struct TestBuilder<'a> {
+    /// The suite the test is created in
+    suite: &'a TestSuite,
+    /// The name of the test
+    name: String,
+    /// The source file of the test and its lines
+    source: Option<(String, RangeInclusive<i32>)>,
+    /// The tags set on creation, by key
+    tags: BTreeMap<String, TagValue>,
+}
+
+impl<'a> TestBuilder<'a> {
+    /// Creates a builder for a test of the given suite
+    fn new(suite: &'a TestSuite, name: impl AsRef<str>) -> Self {
+        Self {
+            suite,
+            name: name.as_ref().to_string(),
+            source: None,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the source file of the test and the lines of its definition, like
+    /// `Test::set_test_source`
+    #[allow(dead_code)]
+    pub fn source(mut self, file: impl AsRef<str>, lines: RangeInclusive<i32>) -> Self {
+        self.source = Some((file.as_ref().to_string(), lines));
+        self
+    }
+
+    /// Adds a typed tag, replacing a tag of the same key
+    #[allow(dead_code)]
+    pub fn tag(mut self, key: impl AsRef<str>, value: impl Into<TagValue>) -> Self {
+        self.tags.insert(key.as_ref().to_string(), value.into());
+        self
+    }
+
+    /// Adds a numeric tag, replacing a tag of the same key
+    #[allow(dead_code)]
+    pub fn number_tag(self, key: impl AsRef<str>, value: f64) -> Self {
+        self.tag(key, TagValue::Num(value))
+    }
+
+    /// Sets the parameters of the test as the `test.parameters` tag, like
+    /// `TestSuite::create_parameterized_test`
+    ///
+    /// The JSON is passed through verbatim, like `create_parameterized_test`.
+    #[allow(dead_code)]
+    pub fn parameters(self, parameters_json: impl AsRef<str>) -> Self {
+        self.tag(tags::TEST_PARAMETERS, parameters_json.as_ref())
+    }
+
+    /// Creates the test with the options set
+    #[allow(dead_code)]
+    pub fn start(self) -> Test {
+        let test = self.suite.create_test_with_tags(&self.name, self.tags);
+        if let Some((file, lines)) = self.source {
+            test.set_test_source(file, lines.start(), lines.end());
+        }
+        test
+    }
+}
+
+impl TestSuite {
+    /// Gets a builder for a test of this suite with its source, tags and parameters
+    ///
+    /// Use `create_test` when the test has no options.
+    #[allow(dead_code)]
+    pub fn test_builder(&self, name: impl AsRef<str>) -> TestBuilder<'_> {
+        TestBuilder::new(self, name)
+    }
+}
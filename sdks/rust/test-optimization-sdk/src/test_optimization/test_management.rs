@@ -0,0 +1,136 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Test management module for iterating over the managed tests
+//!
+//! This module provides lazy iterators over the test management tests that
+//! yield flattened entries filtered by one of the test management flags.
+use crate::test_optimization::lib::*;
+use crate::test_optimization::utils::*;
+use crate::test_optimization::*;
+use std::ffi::CStr;
+use std::mem;
+use std::ptr::null_mut;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents one of the flags of a test management test
+pub enum TestManagementFlag {
+    /// The test is quarantined
+    Quarantined,
+    /// The test is disabled
+    Disabled,
+    /// The test is attempt-to-fix
+    AttemptToFix,
+}
+
+impl TestManagementFlag {
+    /// Whether the given test has this flag set
+    #[allow(dead_code)]
+    pub fn matches(&self, test: &TestManagementTest) -> bool {
+        self.matches_flags(test.quarantined, test.disabled, test.attempt_to_fix)
+    }
+
+    /// Whether this flag is set among the given flag values
+    fn matches_flags(&self, quarantined: bool, disabled: bool, attempt_to_fix: bool) -> bool {
+        match self {
+            TestManagementFlag::Quarantined => quarantined,
+            TestManagementFlag::Disabled => disabled,
+            TestManagementFlag::AttemptToFix => attempt_to_fix,
+        }
+    }
+}
+
+/// Iterates over the test management tests with a flag set, as
+/// `(module, suite, test, TestManagementTest)` tuples
+///
+/// The native array is only converted for the matching entries, and freed when
+/// the iterator is dropped.
+pub struct TestManagementTestsIter {
+    /// The native array of tests
+    tests: topt_TestManagementTestPropertiesArray,
+    /// The index of the next element to read
+    index: usize,
+    /// The flag the yielded tests have set
+    flag: TestManagementFlag,
+}
+
+impl Iterator for TestManagementTestsIter {
+    type Item = (String, String, String, TestManagementTest);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let elements = unsafe { native_slice(self.tests.data, self.tests.len) };
+        while let Some(element) = elements.get(self.index) {
+            self.index += 1;
+
+            let quarantined = Bool_to_bool(element.quarantined);
+            let disabled = Bool_to_bool(element.disabled);
+            let attempt_to_fix = Bool_to_bool(element.attempt_to_fix);
+            if !self.flag.matches_flags(quarantined, disabled, attempt_to_fix) {
+                continue;
+            }
+
+            let (module_name, suite_name, test_name) = unsafe {
+                (
+                    CStr::from_ptr(element.module_name).to_string_lossy().into_owned(),
+                    CStr::from_ptr(element.suite_name).to_string_lossy().into_owned(),
+                    CStr::from_ptr(element.test_name).to_string_lossy().into_owned(),
+                )
+            };
+            let test = TestManagementTest {
+                module_name: module_name.clone(),
+                suite_name: suite_name.clone(),
+                test_name: test_name.clone(),
+                quarantined,
+                disabled,
+                attempt_to_fix,
+            };
+            return Some((module_name, suite_name, test_name, test));
+        }
+        None
+    }
+}
+
+impl Drop for TestManagementTestsIter {
+    fn drop(&mut self) {
+        let tests = mem::replace(
+            &mut self.tests,
+            topt_TestManagementTestPropertiesArray {
+                data: null_mut(),
+                len: 0,
+            },
+        );
+        unsafe { topt_free_test_management_tests(tests) }
+    }
+}
+
+impl TestOptimization {
+    /// Iterate over the test management tests with the given flag set
+    #[allow(dead_code)]
+    pub fn iter_test_management_tests(flag: TestManagementFlag) -> TestManagementTestsIter {
+        TestManagementTestsIter {
+            tests: unsafe { topt_get_test_management_tests() },
+            index: 0,
+            flag,
+        }
+    }
+
+    /// Iterate over the quarantined tests
+    #[allow(dead_code)]
+    pub fn iter_quarantined() -> TestManagementTestsIter {
+        Self::iter_test_management_tests(TestManagementFlag::Quarantined)
+    }
+
+    /// Iterate over the disabled tests
+    #[allow(dead_code)]
+    pub fn iter_disabled() -> TestManagementTestsIter {
+        Self::iter_test_management_tests(TestManagementFlag::Disabled)
+    }
+
+    /// Iterate over the attempt-to-fix tests
+    #[allow(dead_code)]
+    pub fn iter_attempt_to_fix() -> TestManagementTestsIter {
+        Self::iter_test_management_tests(TestManagementFlag::AttemptToFix)
+    }
+}
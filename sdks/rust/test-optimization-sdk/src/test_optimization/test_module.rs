@@ -8,9 +8,17 @@
 //! This module provides functionality for creating and managing test modules,
 //! setting tags, error information, and closing modules.
 use crate::test_optimization::lib::*;
+use crate::test_optimization::naming;
+use crate::test_optimization::registry;
 use crate::test_optimization::utils::*;
 use crate::test_optimization::*;
+use std::collections::HashMap;
 use std::ffi::{c_char, CString};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Serializes `get_or_create_test_suite` so concurrent callers share the same suite
+static SUITE_CREATION: Mutex<()> = Mutex::new(());
 
 #[derive(Debug, Clone)]
 /// Represents a test module within a session
@@ -19,11 +27,24 @@ pub struct TestModule {
     pub(in crate::test_optimization) session_id: u64,
     /// The unique identifier for this module
     pub module_id: u64,
+    /// Closed flag shared by the clones of this module
+    pub(in crate::test_optimization) closed: CloseFlag,
+    /// Name of the module, empty if unknown
+    pub(in crate::test_optimization) name: Arc<str>,
+}
+impl fmt::Display for TestModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        registry::fmt_handle(f, HandleKind::Module, &self.name, self.module_id)
+    }
 }
+
 impl TestModule {
     /// Sets a string tag for this module
     #[allow(dead_code)]
     pub fn set_string_tag(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let key_cstring = CString::new(key.as_ref()).unwrap();
         let value_cstring = CString::new(value.as_ref()).unwrap();
         unsafe {
@@ -38,7 +59,12 @@ impl TestModule {
     /// Sets a numeric tag for this module
     #[allow(dead_code)]
     pub fn set_number_tag(&self, key: impl AsRef<str>, value: f64) -> bool {
-        let key_cstring = CString::new(key.as_ref()).unwrap();
+        !self.closed.is_closed() && self.set_number_tag_unchecked(key.as_ref(), value)
+    }
+
+    /// Sets a numeric tag for this module even once closed, e.g. the clock skew on close
+    fn set_number_tag_unchecked(&self, key: &str, value: f64) -> bool {
+        let key_cstring = CString::new(key).unwrap();
         unsafe {
             Bool_to_bool(topt_module_set_number_tag(
                 self.module_id,
@@ -48,6 +74,16 @@ impl TestModule {
         }
     }
 
+    /// Sets a typed tag for this module, dispatching to the string or numeric setter
+    #[allow(dead_code)]
+    pub fn set_tag(&self, key: impl AsRef<str>, value: impl Into<TagValue>) -> bool {
+        let key = key.as_ref();
+        value.into().apply(
+            |value| self.set_string_tag(key, value),
+            |value| self.set_number_tag(key, value),
+        )
+    }
+
     /// Sets error information for this module
     #[allow(dead_code)]
     pub fn set_error_info(
@@ -56,6 +92,9 @@ impl TestModule {
         error_message: impl AsRef<str>,
         error_stacktrace: impl AsRef<str>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let error_type_cstring = CString::new(error_type.as_ref()).unwrap();
         let error_message_cstring = CString::new(error_message.as_ref()).unwrap();
         let error_stacktrace_cstring = CString::new(error_stacktrace.as_ref()).unwrap();
@@ -71,11 +110,79 @@ impl TestModule {
     }
 
     /// Closes this module
+    ///
+    /// The native library infers the module status from its suites (and from the error info
+    /// set on the module); use `close_with_status` to report it explicitly. Closing a module
+    /// that is already closed, including through a clone, does nothing and returns `false`.
     #[allow(dead_code)]
     pub fn close(&self) -> bool {
-        let mut now = get_now();
-        unsafe {
-            Bool_to_bool(topt_module_close(self.module_id, &mut now))
+        if !self.closed.close() {
+            return false;
+        }
+        let (_, closed) = close_handle(
+            self.module_id,
+            "topt_module_close",
+            |key, value| self.set_number_tag_unchecked(key, value),
+            |now| unsafe { Bool_to_bool(topt_module_close(self.module_id, now)) },
+        );
+        closed
+    }
+
+    /// Closes this module with an explicit status, e.g. when importing results of an external runner
+    ///
+    /// The status is set as the `test.status` tag before closing, and nothing else: no error
+    /// info is set for `Fail`. The tag overrides the status the native library would infer
+    /// (see `close`), in both directions.
+    #[allow(dead_code)]
+    pub fn close_with_status(&self, status: TestStatus) -> bool {
+        close_with_status_tag(
+            &self.closed,
+            status,
+            |key, value| self.set_string_tag(key, value),
+            || self.close(),
+        )
+    }
+
+    /// Creates a custom span under this module, e.g. for setup or I/O done for it
+    #[allow(dead_code)]
+    pub fn create_span(
+        &self,
+        operation_name: impl AsRef<str>,
+        service_name: impl AsRef<str>,
+        resource_name: impl AsRef<str>,
+        span_type: impl AsRef<str>,
+    ) -> Span {
+        Span::create(self.module_id, operation_name, service_name, resource_name, span_type)
+    }
+
+    /// Gets the open suites of this module by name
+    #[allow(dead_code)]
+    pub fn suites(&self) -> HashMap<String, TestSuite> {
+        registry::open_children(self.module_id, HandleKind::Suite)
+            .into_iter()
+            .map(|(suite_id, handle)| {
+                let suite = TestSuite {
+                    suite_id,
+                    module_id: self.module_id,
+                    session_id: self.session_id,
+                    closed: handle.closed.clone(),
+                    name: handle.name.as_str().into(),
+                };
+                (handle.name, suite)
+            })
+            .collect()
+    }
+
+    /// Gets the open suite with the given name, or creates it if there is none
+    ///
+    /// Like `TestSession::get_or_create_module`, concurrent callers get the same suite, e.g.
+    /// the tests of a Rust module running on parallel threads.
+    #[allow(dead_code)]
+    pub fn get_or_create_test_suite(&self, name: impl AsRef<str>) -> TestSuite {
+        let _creation = SUITE_CREATION.lock().unwrap_or_else(|e| e.into_inner());
+        match self.suites().remove(name.as_ref()) {
+            Some(suite) => suite,
+            None => self.create_test_suite(name),
         }
     }
 
@@ -91,10 +198,23 @@ impl TestModule {
                 &mut now,
             )
         };
-        TestSuite {
+        let closed = registry::register(
+            suite_result.suite_id,
+            HandleKind::Suite,
+            name.as_ref(),
+            self.module_id,
+            now,
+        );
+        let suite = TestSuite {
             suite_id: suite_result.suite_id,
             module_id: self.module_id,
             session_id: self.session_id,
+            closed,
+            name: name.as_ref().into(),
+        };
+        if let Some(operation_name) = naming::operation_name(HandleKind::Suite) {
+            suite.set_string_tag(tags::SPAN_NAME, operation_name);
         }
+        suite
     }
 }
@@ -9,12 +9,34 @@
 //! Also access to the backend features.
 
 use crate::test_optimization::lib::*;
+use crate::test_optimization::mock_tracer::{
+    mock_tracer_options, set_mock_tracer_options, MockTracerOptions,
+};
+use crate::test_optimization::logging;
+use crate::test_optimization::registry;
+use crate::test_optimization::stats;
+use crate::test_optimization::test_suite::SKIPPABLE_TESTS;
 use crate::test_optimization::utils::*;
+use crate::test_optimization::{
+    tags, CiProvider, HandleKind, LogLevel, Span, Test, TestModule, TestSession, TestStatus,
+    TestSuite,
+};
 use std::collections::HashMap;
+use std::env;
 use std::ffi::{c_char, CStr, CString};
+use std::fmt;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::panic::{self, UnwindSafe};
+use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, Once};
+use std::thread;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Represents the settings for a test session
 pub struct Settings {
     /// Whether code coverage is enabled
@@ -44,6 +66,7 @@ pub struct Settings {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Settings for early flake detection
 pub struct EfDSettings {
     /// Whether early flake detection is enabled
@@ -58,6 +81,7 @@ pub struct EfDSettings {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Settings for slow test retries in early flake detection
 pub struct EfdSlowTestRetriesSettings {
     /// Number of retries for 5-minute tests
@@ -74,7 +98,34 @@ pub struct EfdSlowTestRetriesSettings {
     pub five_s: i32,
 }
 
+impl EfdSlowTestRetriesSettings {
+    /// Gets the duration thresholds paired with their retry counts, in ascending order
+    ///
+    /// A test gets the retries of the first bucket whose threshold its duration is under.
+    #[allow(dead_code)]
+    pub fn as_buckets(&self) -> [(Duration, i32); 4] {
+        [
+            (Duration::from_secs(5), self.five_s),
+            (Duration::from_secs(10), self.ten_s),
+            (Duration::from_secs(30), self.thirty_s),
+            (Duration::from_secs(5 * 60), self.five_m),
+        ]
+    }
+
+    /// Gets the number of retries for a test that took the given duration
+    ///
+    /// Tests taking 5 minutes or more are not retried.
+    #[allow(dead_code)]
+    pub fn retries_for(&self, duration: Duration) -> i32 {
+        self.as_buckets()
+            .into_iter()
+            .find(|(threshold, _)| duration < *threshold)
+            .map_or(0, |(_, retries)| retries)
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Settings for flaky test retries
 pub struct FlakyTestRetriesSettings {
     /// Number of retries for flaky tests
@@ -86,6 +137,7 @@ pub struct FlakyTestRetriesSettings {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Settings for test management
 pub struct TestManagementSettings {
     /// Whether test management is enabled
@@ -136,10 +188,251 @@ pub struct TestManagementTest {
     pub attempt_to_fix: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents the state of the git metadata upload the Intelligent Test Runner relies on
+pub struct GitUploadStatus {
+    /// Whether the backend still requires git metadata to be uploaded
+    #[allow(dead_code)]
+    pub required: bool,
+    /// Whether the native library is past its git upload step, `None` when unknown
+    ///
+    /// The native library exports no upload status, so this is only `Some(false)` when the
+    /// library isn't initialized, and `None` otherwise.
+    #[allow(dead_code)]
+    pub completed: Option<bool>,
+    /// Why the upload can't be relied on, if known
+    #[allow(dead_code)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// Represents the version information of the SDK and the linked native library
+pub struct VersionInfo {
+    /// Version of this crate
+    #[allow(dead_code)]
+    pub sdk_version: String,
+    /// Version of the native library the crate was built for, see `TestOptimization::native_version`
+    #[allow(dead_code)]
+    pub native_version: String,
+    /// Version reported by the linked native library
+    #[allow(dead_code)]
+    pub linked_native_version: String,
+    /// Commit of the linked native library, if known at build time
+    #[allow(dead_code)]
+    pub native_commit: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// Represents a handle created through the SDK that hasn't been closed yet
+pub struct OpenSpanInfo {
+    /// The ID of the open handle
+    #[allow(dead_code)]
+    pub handle_id: u64,
+    /// The kind of the open handle
+    #[allow(dead_code)]
+    pub kind: HandleKind,
+    /// The operation name for spans, otherwise the session framework, module, suite or test name
+    #[allow(dead_code)]
+    pub name: String,
+    /// The ID of the parent handle, 0 for sessions
+    #[allow(dead_code)]
+    pub parent_id: u64,
+    /// Time elapsed since the handle was created
+    #[allow(dead_code)]
+    pub age: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents an open handle and the open handles created under it
+pub struct HierarchyNode {
+    /// The ID of the open handle
+    #[allow(dead_code)]
+    pub handle_id: u64,
+    /// The kind of the open handle
+    #[allow(dead_code)]
+    pub kind: HandleKind,
+    /// The operation name for spans, otherwise the session framework, module, suite or test name
+    #[allow(dead_code)]
+    pub name: String,
+    /// The open handles created under this one, ordered by ID
+    #[allow(dead_code)]
+    pub children: Vec<HierarchyNode>,
+}
+
+impl HierarchyNode {
+    /// Builds the nodes of the given handles whose parent is `parent_id`, recursively
+    fn children_of(parent_id: u64, handles: &[(u64, registry::OpenHandle)]) -> Vec<Self> {
+        handles
+            .iter()
+            .filter(|(_, handle)| handle.parent_id == parent_id)
+            .map(|(handle_id, handle)| HierarchyNode {
+                handle_id: *handle_id,
+                kind: handle.kind,
+                name: handle.name.clone(),
+                children: Self::children_of(*handle_id, handles),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for HierarchyNode {
+    /// Writes the node and its children one per line, children indented by two spaces
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let depth = f.width().unwrap_or(0);
+        write!(f, "{:depth$}", "")?;
+        registry::fmt_handle(f, self.kind, &self.name, self.handle_id)?;
+        for child in &self.children {
+            write!(f, "\n{:width$}", child, width = depth + 2)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Represents the counters of what has been reported through the SDK
+pub struct FlushStats {
+    /// Spans (sessions, modules, suites, tests and custom spans) closed successfully
+    #[allow(dead_code)]
+    pub spans_sent: u64,
+    /// Code coverage payloads sent
+    #[allow(dead_code)]
+    pub coverage_payloads_sent: u64,
+    /// Closes rejected by the native library (e.g. unknown or already closed handles)
+    #[allow(dead_code)]
+    pub errors: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Represents an error preventing the library from being initialized
+pub enum InitError {
+    /// The working directory can't be passed to the native library (it contains a nul
+    /// byte, or is not valid Unicode on Windows)
+    InvalidWorkingDirectory(PathBuf),
+    /// Agentless mode was requested without an API key
+    MissingApiKey,
+    /// An environment variable can't be passed to the native library (it contains a nul
+    /// byte); only the name is kept, so values such as API keys never end up in logs
+    InvalidEnvironmentVariable(String),
+    /// The library was shut down, and the native library can't be initialized again in the
+    /// same process
+    ShutDown,
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::InvalidWorkingDirectory(path) => {
+                write!(f, "invalid working directory: {}", path.display())
+            }
+            InitError::MissingApiKey => write!(f, "agentless mode requires a non-empty API key"),
+            InitError::InvalidEnvironmentVariable(key) => {
+                write!(f, "invalid value for environment variable {}", key)
+            }
+            InitError::ShutDown => write!(
+                f,
+                "the native library can't be initialized again after shutdown in the same process"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+/// Represents a borrowed key/value pair of C strings passed to the native library as is
+///
+/// The layout matches the native key/value pair, so slices of pairs are handed over
+/// without copying or allocating.
+pub struct CStrPair<'a> {
+    /// Pointer to the key
+    key: *const c_char,
+    /// Pointer to the value
+    value: *const c_char,
+    /// Ties the pointers to the lifetime of the borrowed strings
+    _marker: PhantomData<&'a CStr>,
+}
+
+impl<'a> CStrPair<'a> {
+    /// Creates a pair from borrowed C strings
+    #[allow(dead_code)]
+    pub const fn new(key: &'a CStr, value: &'a CStr) -> Self {
+        Self {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the key of the pair
+    #[allow(dead_code)]
+    pub fn key(&self) -> &'a CStr {
+        unsafe { CStr::from_ptr(self.key) }
+    }
+
+    /// Gets the value of the pair
+    #[allow(dead_code)]
+    pub fn value(&self) -> &'a CStr {
+        unsafe { CStr::from_ptr(self.value) }
+    }
+}
+
+const _: () = assert!(size_of::<CStrPair>() == size_of::<topt_KeyValuePair>());
+
+/// Whether the native library has been initialized and not shut down yet
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the native library was shut down, after which it can't be initialized again
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether `await_skippable_tests` started fetching the skippable tests in the background
+static SKIPPABLE_TESTS_FETCH_STARTED: Mutex<bool> = Mutex::new(false);
+
+/// Notified once the skippable tests fetched by `await_skippable_tests` are loaded
+static SKIPPABLE_TESTS_LOADED: Condvar = Condvar::new();
+
+/// Warns when the linked native library isn't the pinned release the crate was built for
+///
+/// The bindings are written against that release, so another one may not match their ABI.
+/// `dev` and `custom` libraries are chosen by the user and aren't checked.
+fn check_native_version() {
+    let expected = TestOptimization::native_version();
+    if expected == "dev" || expected == "custom" {
+        return;
+    }
+    let linked = TestOptimization::linked_native_version();
+    if linked != expected {
+        logging::write(
+            LogLevel::Warn,
+            format_args!(
+                "the linked native library is {} but this SDK version expects {}, \
+                 the bindings may not match it: delete the build output to download {} again",
+                linked, expected, expected
+            ),
+        );
+    }
+}
+
+/// Directory the native library was initialized in, see `resolved_working_directory`
+static INIT_WORKING_DIRECTORY: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Default service set with `TestOptimization::set_service`
+static SERVICE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Environment variable holding the default service
+const DD_SERVICE_ENV: &str = "DD_SERVICE";
+
 /// Language name for the test session
 pub(in crate::test_optimization) static LANGUAGE_NAME: &str = "rust";
 /// Runtime name for the test session
 pub(in crate::test_optimization) static RUNTIME_NAME: &str = "rustc";
+/// Version of the rustc that built the crate, the fallback of the runtime version
+const BUILD_RUSTC_VERSION: &str = env!("TEST_OPTIMIZATION_SDK_RUSTC_VERSION");
+
+/// Runs a version detection, falling back to `BUILD_RUSTC_VERSION` if it panics
+fn detect_or_build_version(detect: impl FnOnce() -> String + UnwindSafe) -> String {
+    panic::catch_unwind(detect).unwrap_or_else(|_| BUILD_RUSTC_VERSION.to_string())
+}
 
 #[derive(Debug, Clone)]
 /// Represents a test session
@@ -147,9 +440,110 @@ pub struct TestOptimization;
 
 impl TestOptimization {
     /// Get the runtime version
+    ///
+    /// If detecting the version of the running toolchain fails, this falls back to the version
+    /// of the rustc that built the crate (`"unknown"` if it couldn't be read either) instead of
+    /// panicking, so `init` still works. The failure can't be caught with `panic = "abort"`.
     #[allow(dead_code)]
     pub fn runtime_version() -> String {
-        rustc_version_runtime::version().to_string()
+        detect_or_build_version(|| rustc_version_runtime::version().to_string())
+    }
+
+    /// Get the version of the native library the crate was built for
+    ///
+    /// This is the version resolved by the build script: the pinned release when the library
+    /// is downloaded, `dev` in dev mode, and `custom` when it comes from
+    /// `TEST_OPTIMIZATION_SDK_NATIVE_SEARCH_PATH`.
+    #[allow(dead_code)]
+    pub fn native_version() -> String {
+        env!("TEST_OPTIMIZATION_NATIVE_VERSION").to_string()
+    }
+
+    /// Get the version reported by the linked native library
+    ///
+    /// The release tag the library was built from, or `dev` for a local build.
+    #[allow(dead_code)]
+    pub fn linked_native_version() -> String {
+        let version = unsafe { topt_get_version() };
+        if version.is_null() {
+            return "unknown".to_string();
+        }
+        unsafe { CStr::from_ptr(version) }.to_string_lossy().into_owned()
+    }
+
+    /// Get the version of this crate together with the linked native library version and commit
+    #[allow(dead_code)]
+    pub fn version_info() -> VersionInfo {
+        let native_commit = env!("TEST_OPTIMIZATION_NATIVE_COMMIT");
+        VersionInfo {
+            sdk_version: env!("CARGO_PKG_VERSION").to_string(),
+            native_version: Self::native_version(),
+            linked_native_version: Self::linked_native_version(),
+            native_commit: if native_commit.is_empty() {
+                None
+            } else {
+                Some(native_commit.to_string())
+            },
+        }
+    }
+
+    /// Set the default service of all the spans, overriding `DD_SERVICE`
+    ///
+    /// Call it before initializing: the service is handed to the native library as
+    /// `DD_SERVICE` on init (except with `init_with_cstr_values`). Sessions, modules, suites
+    /// and tests are reported under the default service, as are spans created with an
+    /// empty service name; a non-empty service name passed to `Span::create` overrides it.
+    #[allow(dead_code)]
+    pub fn set_service(service: impl AsRef<str>) {
+        *SERVICE.lock().unwrap_or_else(|e| e.into_inner()) = Some(service.as_ref().to_string());
+    }
+
+    /// Get the default service, set with `set_service` or else from `DD_SERVICE`
+    #[allow(dead_code)]
+    pub fn service() -> Option<String> {
+        SERVICE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .or_else(|| env::var(DD_SERVICE_ENV).ok().filter(|service| !service.is_empty()))
+    }
+
+    /// Set the level of the SDK and native library logs (`Info` by default)
+    ///
+    /// Call it before initializing: the native library reads its log configuration on init,
+    /// as `DD_TRACE_DEBUG` and `DD_TRACE_STARTUP_LOGS` (except with `init_with_cstr_values`).
+    /// Below `Info`, the native library still logs its warnings and errors, see
+    /// `set_log_directory`. Unless a level is set, those variables are read from the
+    /// environment as is.
+    #[allow(dead_code)]
+    pub fn set_log_level(level: LogLevel) {
+        logging::set_level(level);
+    }
+
+    /// Get the level of the SDK logs, `Info` unless set with `set_log_level`
+    #[allow(dead_code)]
+    pub fn log_level() -> LogLevel {
+        logging::level()
+    }
+
+    /// Set the directory the native library writes its logs to, instead of stderr
+    ///
+    /// Call it before initializing: the directory is handed to the native library as
+    /// `DD_TRACE_LOG_DIRECTORY` on init (except with `init_with_cstr_values`), and its logs
+    /// go to the `ddtrace.log` file in it. The native library has no log callback, so its
+    /// logs can't be captured in-process.
+    #[allow(dead_code)]
+    pub fn set_log_directory(directory: impl AsRef<Path>) {
+        logging::set_directory(directory.as_ref().to_path_buf());
+    }
+
+    /// Set the writer of the SDK messages, e.g. a file or a buffer, instead of stderr
+    ///
+    /// The messages are still filtered by the log level. Only the SDK messages go through the
+    /// writer, see `set_log_directory` for the native library logs.
+    #[allow(dead_code)]
+    pub fn set_log_writer(writer: impl Write + Send + 'static) {
+        logging::set_writer(Box::new(writer));
     }
 
     /// Initialize the test optimization library
@@ -166,7 +560,7 @@ impl TestOptimization {
 
     /// Initialize the test optimization library with a working directory
     #[allow(dead_code)]
-    pub fn init_with_working_dir(working_dir: &str) -> bool {
+    pub fn init_with_working_dir(working_dir: impl AsRef<Path>) -> bool {
         Self::init_with_values(
             LANGUAGE_NAME,
             RUNTIME_NAME,
@@ -188,9 +582,16 @@ impl TestOptimization {
         )
     }
 
+    /// Initialize the test optimization library with a mock tracer capturing what the options select
+    #[allow(dead_code)]
+    pub fn init_mock_with_options(options: MockTracerOptions) -> bool {
+        set_mock_tracer_options(options);
+        Self::init_mock()
+    }
+
     /// Initialize the test optimization library with a mock tracer and a working directory
     #[allow(dead_code)]
-    pub fn init_mock_with_working_dir(working_dir: &str) -> bool {
+    pub fn init_mock_with_working_dir(working_dir: impl AsRef<Path>) -> bool {
         Self::init_with_values(
             LANGUAGE_NAME,
             RUNTIME_NAME,
@@ -201,40 +602,168 @@ impl TestOptimization {
     }
 
     /// Initialize the test optimization library with specific values
+    ///
+    /// Returns `false` without initializing if the working directory is invalid; use
+    /// `try_init_with_values` to get the reason.
     #[allow(dead_code)]
     pub fn init_with_values(
         language_name: impl AsRef<str>,
         runtime_name: impl AsRef<str>,
         runtime_version: impl AsRef<str>,
-        working_directory: Option<impl AsRef<str>>,
+        working_directory: Option<impl AsRef<Path>>,
         use_mock_tracer: bool,
     ) -> bool {
-        #[cfg(target_os = "windows")]
-        unsafe {
-            // On Windows, call the platform-specific initialization
-            // this is required on static libraries compiled by the go toolchain
-            // just to start the go runtime
-            _rt0_amd64_windows_lib()
-        }
+        Self::try_init_with_values(
+            language_name,
+            runtime_name,
+            runtime_version,
+            working_directory,
+            use_mock_tracer,
+        )
+        .unwrap_or_else(|e| {
+            logging::write(LogLevel::Error, format_args!("{}", e));
+            false
+        })
+    }
+
+    /// Initialize the test optimization library with specific values, reporting invalid input
+    ///
+    /// Returns `Ok` with the result of the native initialization, or an error if the working
+    /// directory can't be represented as a C string or the library was shut down. The
+    /// `DD_TAGS` environment variable is parsed with `tags::parse_dd_tags` and passed as
+    /// global tags, added to every span.
+    #[allow(dead_code)]
+    pub fn try_init_with_values(
+        language_name: impl AsRef<str>,
+        runtime_name: impl AsRef<str>,
+        runtime_version: impl AsRef<str>,
+        working_directory: Option<impl AsRef<Path>>,
+        use_mock_tracer: bool,
+    ) -> Result<bool, InitError> {
+        Self::try_init_with_environment(
+            language_name,
+            runtime_name,
+            runtime_version,
+            working_directory,
+            use_mock_tracer,
+            &[],
+        )
+    }
+
+    /// Initialize the test optimization library like `try_init_with_values`, setting the
+    /// given environment variables before initializing (after `DD_SERVICE` and the log
+    /// configuration, so they win)
+    pub(in crate::test_optimization) fn try_init_with_environment(
+        language_name: impl AsRef<str>,
+        runtime_name: impl AsRef<str>,
+        runtime_version: impl AsRef<str>,
+        working_directory: Option<impl AsRef<Path>>,
+        use_mock_tracer: bool,
+        environment: &[(String, String)],
+    ) -> Result<bool, InitError> {
+        // Create an optional CString for working_directory if provided
+        let working_directory_cstring = match working_directory {
+            Some(wd) => Some(path_to_cstring(wd.as_ref()).ok_or_else(|| {
+                InitError::InvalidWorkingDirectory(wd.as_ref().to_path_buf())
+            })?),
+            None => None,
+        };
 
         // Create CStrings for the required parameters
         let language_name_cstring = CString::new(language_name.as_ref()).unwrap();
         let runtime_name_cstring = CString::new(runtime_name.as_ref()).unwrap();
         let runtime_version_cstring = CString::new(runtime_version.as_ref()).unwrap();
-        // Create an optional CString for working_directory if provided
-        let working_directory_cstring =
-            working_directory.map(|wd| CString::new(wd.as_ref()).unwrap());
 
-        // Build the initialization options struct, using as_ptr() so the memory is managed automatically
+        // Tags with a nul byte can't be passed to the native library and are skipped
+        let global_tags_cstrings: Vec<(CString, CString)> = env::var(tags::DD_TAGS_ENV)
+            .map(|value| tags::parse_dd_tags(&value))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, value)| Some((CString::new(key).ok()?, CString::new(value).ok()?)))
+            .collect();
+        let global_tags: Vec<CStrPair> = global_tags_cstrings
+            .iter()
+            .map(|(key, value)| CStrPair::new(key, value))
+            .collect();
+
+        // The default service reaches the native tracer as DD_SERVICE
+        let service_cstring = SERVICE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_deref()
+            .and_then(|service| CString::new(service).ok());
+        let dd_service_env = CString::new(DD_SERVICE_ENV).unwrap();
+        let environment_cstrings = logging::environment()
+            .iter()
+            .chain(environment)
+            .map(|(key, value)| match (CString::new(key.as_str()), CString::new(value.as_str())) {
+                (Ok(key), Ok(value)) => Ok((key, value)),
+                _ => Err(InitError::InvalidEnvironmentVariable(key.clone())),
+            })
+            .collect::<Result<Vec<(CString, CString)>, InitError>>()?;
+        let environment_variables: Vec<CStrPair> = service_cstring
+            .iter()
+            .map(|service| CStrPair::new(&dd_service_env, service))
+            .chain(environment_cstrings.iter().map(|(key, value)| CStrPair::new(key, value)))
+            .collect();
+
+        // Checked once the arguments are validated, so invalid ones are reported the same way
+        // whether or not the library was shut down
+        if SHUT_DOWN.load(Ordering::Acquire) {
+            return Err(InitError::ShutDown);
+        }
+        Ok(Self::init_with_cstr_values(
+            &language_name_cstring,
+            &runtime_name_cstring,
+            &runtime_version_cstring,
+            working_directory_cstring.as_deref(),
+            &environment_variables,
+            &global_tags,
+            use_mock_tracer,
+        ))
+    }
+
+    /// Initialize the test optimization library from borrowed C strings, without allocating
+    ///
+    /// For short-lived processes on constrained runners that already hold nul-terminated data.
+    /// `environment_variables` are set in the process environment before initializing, and
+    /// `global_tags` are added to every span. Empty slices are not passed to the native library.
+    #[allow(dead_code)]
+    pub fn init_with_cstr_values(
+        language_name: &CStr,
+        runtime_name: &CStr,
+        runtime_version: &CStr,
+        working_directory: Option<&CStr>,
+        environment_variables: &[CStrPair],
+        global_tags: &[CStrPair],
+        use_mock_tracer: bool,
+    ) -> bool {
+        // The pairs share the layout of topt_KeyValuePair, so the slices are passed as is
+        let mut environment_variables_array = topt_KeyValueArray {
+            data: environment_variables.as_ptr() as *mut topt_KeyValuePair,
+            len: environment_variables.len(),
+        };
+        let mut global_tags_array = topt_KeyValueArray {
+            data: global_tags.as_ptr() as *mut topt_KeyValuePair,
+            len: global_tags.len(),
+        };
+
+        // Build the initialization options struct, the borrowed strings outlive the call
         let init_options = topt_InitOptions {
-            language: language_name_cstring.as_ptr() as *mut c_char,
-            runtime_name: runtime_name_cstring.as_ptr() as *mut c_char,
-            runtime_version: runtime_version_cstring.as_ptr() as *mut c_char,
-            working_directory: working_directory_cstring
-                .as_ref()
-                .map_or(null_mut(), |s| s.as_ptr() as *mut c_char),
-            environment_variables: null_mut(),
-            global_tags: null_mut(),
+            language: language_name.as_ptr() as *mut c_char,
+            runtime_name: runtime_name.as_ptr() as *mut c_char,
+            runtime_version: runtime_version.as_ptr() as *mut c_char,
+            working_directory: working_directory.map_or(null_mut(), |s| s.as_ptr() as *mut c_char),
+            environment_variables: if environment_variables.is_empty() {
+                null_mut()
+            } else {
+                &mut environment_variables_array
+            },
+            global_tags: if global_tags.is_empty() {
+                null_mut()
+            } else {
+                &mut global_tags_array
+            },
             use_mock_tracer: if use_mock_tracer { 1 } else { 0 },
             unused01: null_mut(),
             unused02: null_mut(),
@@ -243,21 +772,288 @@ impl TestOptimization {
             unused05: null_mut(),
         };
 
+        // The native library can't be initialized again after shutdown
+        if SHUT_DOWN.load(Ordering::Acquire) {
+            return false;
+        }
         // Initialize the library with the provided options
-        unsafe { Bool_to_bool(topt_initialize(init_options)) }
+        let initialized = unsafe { Bool_to_bool(topt_initialize(init_options)) };
+        if initialized {
+            INITIALIZED.store(true, Ordering::Relaxed);
+            check_native_version();
+            // The native library resolves the repository from the working directory, or from
+            // the current one when none is passed
+            let init_working_directory = working_directory
+                .and_then(cstr_to_path)
+                .filter(|path| !path.as_os_str().is_empty())
+                .or_else(|| env::current_dir().ok());
+            *INIT_WORKING_DIRECTORY.lock().unwrap_or_else(|e| e.into_inner()) =
+                init_working_directory;
+        }
+        initialized
     }
 
     /// Shutdown the test optimization library
+    ///
+    /// Handles created through the SDK that are still open are reported on stderr,
+    /// since they usually point to a `close` skipped on an error path.
+    ///
+    /// The native library doesn't support initializing again after shutdown: `topt_initialize`
+    /// never resets its initialization guard, so a second call is a no-op on a shut down
+    /// tracer. The init functions fail with `InitError::ShutDown` afterwards instead, without
+    /// calling into the native library. Long-running tools, e.g. a watch-mode test runner,
+    /// should keep the library initialized and create a new `TestSession` per run, shutting
+    /// down only when the process exits.
     #[allow(dead_code)]
     pub fn shutdown() -> bool {
-        unsafe { Bool_to_bool(topt_shutdown()) }
+        let open_spans = Self::open_span_report();
+        if !open_spans.is_empty() {
+            let mut report = format!("{} handle(s) still open at shutdown:", open_spans.len());
+            for open_span in &open_spans {
+                report += &format!(
+                    "\n  {} {} {:?} (open for {:?})",
+                    open_span.kind, open_span.handle_id, open_span.name, open_span.age
+                );
+            }
+            logging::write(LogLevel::Warn, format_args!("{}", report));
+        }
+        INITIALIZED.store(false, Ordering::Relaxed);
+        let shut_down = unsafe { Bool_to_bool(topt_shutdown()) };
+        if shut_down {
+            SHUT_DOWN.store(true, Ordering::Release);
+        }
+        shut_down
+    }
+
+    /// Get the directory the native library uses as the repository root, for debugging
+    ///
+    /// When the known or skippable tests are unexpectedly empty, the repository root is often
+    /// wrong. Like the native library, this prefers the workspace path of the detected CI
+    /// provider (e.g. `GITHUB_WORKSPACE`), and otherwise discovers the git repository from the
+    /// working directory passed at init (or the current one): the closest directory with a
+    /// `.git` entry, or that directory itself outside of a repository. The native library
+    /// doesn't expose its resolution, so this replays it on the SDK side. Returns `None` if
+    /// the library was never initialized.
+    #[allow(dead_code)]
+    pub fn resolved_working_directory() -> Option<PathBuf> {
+        let init_working_directory = INIT_WORKING_DIRECTORY
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()?;
+        if let Some(workspace_path) = CiProvider::detect_tags().remove(tags::CI_WORKSPACE_PATH) {
+            return Some(PathBuf::from(workspace_path));
+        }
+        let repository_root = init_working_directory
+            .ancestors()
+            .find(|directory| directory.join(".git").exists())
+            .map(Path::to_path_buf);
+        Some(repository_root.unwrap_or(init_working_directory))
+    }
+
+    /// Whether the library is initialized and not shut down
+    #[allow(dead_code)]
+    pub fn is_initialized() -> bool {
+        INITIALIZED.load(Ordering::Relaxed)
+    }
+
+    /// Whether code coverage is effectively collected and sent
+    ///
+    /// `Settings.code_coverage` only says the backend wants coverage. This also requires the
+    /// library to be initialized and not shut down, and coverage to be captured when using
+    /// the mock tracer, so adapters can skip gathering line coverage that wouldn't be sent.
+    #[allow(dead_code)]
+    pub fn is_coverage_active() -> bool {
+        INITIALIZED.load(Ordering::Relaxed)
+            && mock_tracer_options().capture_coverage
+            && Self::get_settings().code_coverage
+    }
+
+    /// Force the data reported so far towards Datadog, e.g. between modules, and report
+    /// whether the coverage payloads were sent
+    ///
+    /// `set_coverage_data`, `set_coverage_file` and `CoverageBatch::send` hand the payloads to
+    /// the native library, which sends them right away without returning send errors. This
+    /// is where those errors surface: returns `false` if a coverage payload sent since the
+    /// previous flush failed, or if the library isn't initialized. The finished spans, which
+    /// the native library otherwise sends in the background, are flushed too.
+    #[allow(dead_code)]
+    pub fn flush_coverage() -> bool {
+        if !INITIALIZED.load(Ordering::Relaxed) {
+            return false;
+        }
+        unsafe { Bool_to_bool(topt_flush()) }
+    }
+
+    /// Get the counters of what has been reported through the SDK so far
+    ///
+    /// The native library doesn't expose its own counters, so these count what the SDK
+    /// handed over to it; spans are actually sent to Datadog when the library flushes
+    /// them, at the latest on `shutdown`.
+    #[allow(dead_code)]
+    pub fn stats() -> FlushStats {
+        let (spans_sent, coverage_payloads_sent, errors) = stats::snapshot();
+        FlushStats {
+            spans_sent,
+            coverage_payloads_sent,
+            errors,
+        }
+    }
+
+    /// Get the handles created through the SDK that are still open
+    #[allow(dead_code)]
+    pub fn open_span_report() -> Vec<OpenSpanInfo> {
+        registry::open_handles()
+            .into_iter()
+            .map(|(handle_id, handle)| OpenSpanInfo {
+                handle_id,
+                kind: handle.kind,
+                name: handle.name,
+                parent_id: handle.parent_id,
+                age: handle.opened_at.elapsed(),
+            })
+            .collect()
+    }
+
+    /// Get the open sessions, modules, suites, tests and custom spans as a tree
+    ///
+    /// Roots are the open handles whose parent isn't open (sessions, or handles left open
+    /// after their parent was closed). Unlike the mock tracer, this works in production,
+    /// which helps spotting a test created under the wrong suite.
+    #[allow(dead_code)]
+    pub fn active_hierarchy() -> Vec<HierarchyNode> {
+        let handles = registry::open_handles();
+        handles
+            .iter()
+            .filter(|(_, handle)| {
+                handle.parent_id == 0
+                    || !handles.iter().any(|(id, _)| *id == handle.parent_id)
+            })
+            .map(|(handle_id, handle)| HierarchyNode {
+                handle_id: *handle_id,
+                kind: handle.kind,
+                name: handle.name.clone(),
+                children: HierarchyNode::children_of(*handle_id, &handles),
+            })
+            .collect()
+    }
+
+    /// Close every handle created through the SDK that is still open, e.g. from a ctrl-c handler
+    ///
+    /// Closes bottom-up: custom spans first (latest first, so nested spans close before their
+    /// parent), then tests with `default_status`, then suites and modules with
+    /// `default_status` as their explicit status, and the sessions last with the exit code of
+    /// `default_status`. A parent is therefore never closed before its open children. The
+    /// library isn't shut down. Handles closed this way are removed from the registry, so
+    /// calling this again closes nothing. The handles held by the caller share their closed
+    /// flag with the registry, so they are marked closed too: closing them again returns
+    /// `false` and their setters are refused without reaching the native library. Returns the
+    /// number of handles closed.
+    #[allow(dead_code)]
+    pub fn close_all_open(default_status: TestStatus) -> usize {
+        let handles: HashMap<u64, registry::OpenHandle> =
+            registry::open_handles().into_iter().collect();
+        let parent_of = |id: u64| handles.get(&id).map_or(0, |handle| handle.parent_id);
+        let name_of =
+            |id: u64| handles.get(&id).map_or("".into(), |handle| handle.name.as_str().into());
+        let mut closed = 0;
+        for kind in [
+            HandleKind::Span,
+            HandleKind::Test,
+            HandleKind::Suite,
+            HandleKind::Module,
+            HandleKind::Session,
+        ] {
+            let mut ids: Vec<u64> = handles
+                .iter()
+                .filter(|(_, handle)| handle.kind == kind)
+                .map(|(id, _)| *id)
+                .collect();
+            ids.sort_unstable_by(|a, b| b.cmp(a));
+            for id in ids {
+                let parent_id = handles[&id].parent_id;
+                let closed_now = match kind {
+                    HandleKind::Span => Span {
+                        span_id: id,
+                        parent_id,
+                        closed: handles[&id].closed.clone(),
+                        name: name_of(id),
+                    }
+                    .close(),
+                    HandleKind::Test => {
+                        let suite = TestSuite {
+                            session_id: parent_of(parent_of(parent_id)),
+                            module_id: parent_of(parent_id),
+                            suite_id: parent_id,
+                            closed: CloseFlag::default(),
+                            name: name_of(parent_id),
+                        };
+                        Test::new(
+                            &suite,
+                            id,
+                            &handles[&id].name,
+                            handles[&id].closed.clone(),
+                            handles[&id].opened_at,
+                        )
+                        .close(default_status)
+                    }
+                    HandleKind::Suite => TestSuite {
+                        session_id: parent_of(parent_id),
+                        module_id: parent_id,
+                        suite_id: id,
+                        closed: handles[&id].closed.clone(),
+                        name: name_of(id),
+                    }
+                    .close_with_status(default_status),
+                    HandleKind::Module => TestModule {
+                        session_id: parent_id,
+                        module_id: id,
+                        closed: handles[&id].closed.clone(),
+                        name: name_of(id),
+                    }
+                    .close_with_status(default_status),
+                    HandleKind::Session => TestSession {
+                        session_id: id,
+                        closed: handles[&id].closed.clone(),
+                        retry_budget: Default::default(),
+                    }
+                    .close_raw(default_status.exit_code()),
+                };
+                if closed_now {
+                    closed += 1;
+                }
+            }
+        }
+        closed
     }
 
     /// Get the current settings
+    ///
+    /// The size of the settings struct is asserted at compile time against the sizes written
+    /// in the bindings, which are kept in sync with the C header by hand; nothing reads the
+    /// header itself. At runtime, data in the reserved fields means the native library is newer
+    /// than these bindings; the known fields are still read, and a warning is printed once.
     #[allow(dead_code)]
     pub fn get_settings() -> Settings {
         unsafe {
             let settings_response = topt_get_settings();
+            if !settings_response.unused01.is_null()
+                || !settings_response.unused02.is_null()
+                || !settings_response.unused03.is_null()
+                || !settings_response.unused04.is_null()
+                || !settings_response.unused05.is_null()
+            {
+                static WARN_UNKNOWN_SETTINGS: Once = Once::new();
+                WARN_UNKNOWN_SETTINGS.call_once(|| {
+                    logging::write(
+                        LogLevel::Warn,
+                        format_args!(
+                            "the native library returned settings unknown to this SDK version ({}), \
+                             consider updating the test-optimization-sdk crate",
+                            Self::native_version()
+                        ),
+                    );
+                });
+            }
             Settings {
                 code_coverage: Bool_to_bool(settings_response.code_coverage),
                 early_flake_detection: EfDSettings {
@@ -301,6 +1097,36 @@ impl TestOptimization {
         }
     }
 
+    /// Get the effective settings as JSON, e.g. to attach to CI logs or diff across runs
+    ///
+    /// The shape is stable: an object with the `settings` (`Settings`) and the
+    /// `flaky_test_retries` (`FlakyTestRetriesSettings`), whose keys are the field names of
+    /// these structs, in declaration order, without whitespace:
+    ///
+    /// ```json
+    /// {"settings":{"code_coverage":false,"early_flake_detection":{"enabled":false,
+    /// "slow_test_retries":{"five_m":0,"thirty_s":0,"ten_s":0,"five_s":0},
+    /// "faulty_session_threshold":0},"flaky_test_retries_enabled":false,"itr_enabled":false,
+    /// "require_git":false,"tests_skipping":false,"known_tests_enabled":false,
+    /// "test_management":{"enabled":false,"attempt_to_fix_retries":0}},
+    /// "flaky_test_retries":{"retry_count":0,"total_retry_count":0}}
+    /// ```
+    #[cfg(feature = "serde")]
+    #[allow(dead_code)]
+    pub fn settings_json() -> String {
+        #[derive(serde::Serialize)]
+        struct EffectiveSettings {
+            settings: Settings,
+            flaky_test_retries: FlakyTestRetriesSettings,
+        }
+
+        serde_json::to_string(&EffectiveSettings {
+            settings: Self::get_settings(),
+            flaky_test_retries: Self::get_flaky_test_retries_settings(),
+        })
+        .unwrap()
+    }
+
     /// Get the flaky test retries settings
     #[allow(dead_code)]
     pub fn get_flaky_test_retries_settings() -> FlakyTestRetriesSettings {
@@ -319,9 +1145,7 @@ impl TestOptimization {
         unsafe {
             let mut modules_map: HashMap<String, HashMap<String, Vec<String>>> = HashMap::new();
             let known_tests = topt_get_known_tests();
-            for i in 0..known_tests.len {
-                let element = &*known_tests.data.add(i);
-
+            for element in native_slice(known_tests.data, known_tests.len) {
                 let module_name_c = CStr::from_ptr(element.module_name);
                 let suite_name_c = CStr::from_ptr(element.suite_name);
                 let test_name_c = CStr::from_ptr(element.test_name);
@@ -341,6 +1165,37 @@ impl TestOptimization {
         }
     }
 
+    /// Get the state of the git metadata upload
+    ///
+    /// When the backend requires git metadata, the native library uploads it while resolving
+    /// its settings, waits for the upload, and fetches the settings again before any skippable
+    /// test is requested. So the expected sequencing is: initialize, call this (it blocks on
+    /// the first call until the settings are resolved), then call `get_skippable_tests`.
+    /// Skippable tests fetched without the git metadata are empty.
+    ///
+    /// The native library neither reports whether the upload completed nor its failures, which
+    /// it only logs: `completed` is unknown once initialized, and `error` is set when the
+    /// library isn't initialized, or when the settings still require git metadata.
+    #[allow(dead_code)]
+    pub fn git_upload_status() -> GitUploadStatus {
+        if !INITIALIZED.load(Ordering::Relaxed) {
+            return GitUploadStatus {
+                required: false,
+                completed: Some(false),
+                error: Some("the test optimization library is not initialized".to_string()),
+            };
+        }
+        let required = Self::get_settings().require_git;
+        GitUploadStatus {
+            required,
+            completed: None,
+            error: required.then(|| {
+                "the backend still requires git metadata, check the native library logs for the upload"
+                    .to_string()
+            }),
+        }
+    }
+
     /// Get the skippable tests
     #[allow(dead_code)]
     pub fn get_skippable_tests() -> HashMap<String, HashMap<String, Vec<SkippableTest>>> {
@@ -348,9 +1203,7 @@ impl TestOptimization {
             let mut suites_map: HashMap<String, HashMap<String, Vec<SkippableTest>>> =
                 HashMap::new();
             let skippable_tests = topt_get_skippable_tests();
-            for i in 0..skippable_tests.len {
-                let element = &*skippable_tests.data.add(i);
-
+            for element in native_slice(skippable_tests.data, skippable_tests.len) {
                 let suite_name_c = CStr::from_ptr(element.suite_name);
                 let test_name_c = CStr::from_ptr(element.test_name);
                 let parameters_c = CStr::from_ptr(element.parameters);
@@ -382,6 +1235,47 @@ impl TestOptimization {
         }
     }
 
+    /// Wait until the skippable tests are loaded, for at most `timeout`
+    ///
+    /// The native library fetches the skippable tests on the first request for them, after
+    /// the settings and the git metadata upload (see `git_upload_status`), and that request
+    /// blocks until the backend answers. This runs the request on a background thread, so
+    /// adapters can bound the wait and fall back to running every test. Call it after
+    /// initializing and before the first skip decision: once it returns `true`,
+    /// `get_skippable_tests` and `TestSuite::is_skippable` answer from the loaded list
+    /// without waiting, and an empty list means there is nothing to skip.
+    ///
+    /// Returns `false` if the library isn't initialized or the timeout elapsed first. The
+    /// request is only made once: on timeout it keeps running in the background, and later
+    /// calls wait on that same request instead of starting another one.
+    #[allow(dead_code)]
+    pub fn await_skippable_tests(timeout: Duration) -> bool {
+        if !INITIALIZED.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut fetch_started =
+            SKIPPABLE_TESTS_FETCH_STARTED.lock().unwrap_or_else(|e| e.into_inner());
+        if !*fetch_started {
+            let spawned = thread::Builder::new()
+                .name("dd-skippable-tests".to_string())
+                .spawn(|| {
+                    SKIPPABLE_TESTS.get_or_init(Self::get_skippable_tests);
+                    // Locking orders the notification after the waiter's check of the list
+                    let _fetch_started =
+                        SKIPPABLE_TESTS_FETCH_STARTED.lock().unwrap_or_else(|e| e.into_inner());
+                    SKIPPABLE_TESTS_LOADED.notify_all();
+                });
+            if spawned.is_err() {
+                return false;
+            }
+            *fetch_started = true;
+        }
+        let _fetch_started = SKIPPABLE_TESTS_LOADED
+            .wait_timeout_while(fetch_started, timeout, |_| SKIPPABLE_TESTS.get().is_none())
+            .unwrap_or_else(|e| e.into_inner());
+        SKIPPABLE_TESTS.get().is_some()
+    }
+
     /// Get the test management tests
     #[allow(dead_code)]
     pub fn get_test_management_tests(
@@ -392,9 +1286,9 @@ impl TestOptimization {
                 HashMap<String, HashMap<String, TestManagementTest>>,
             > = HashMap::new();
             let test_management_tests = topt_get_test_management_tests();
-            for i in 0..test_management_tests.len {
-                let element = &*test_management_tests.data.add(i);
-
+            for element in
+                native_slice(test_management_tests.data, test_management_tests.len)
+            {
                 let module_name_c = CStr::from_ptr(element.module_name);
                 let suite_name_c = CStr::from_ptr(element.suite_name);
                 let test_name_c = CStr::from_ptr(element.test_name);
@@ -425,3 +1319,17 @@ impl TestOptimization {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runtime_version_falls_back_to_the_build_version_on_a_panic() {
+        assert_eq!(detect_or_build_version(|| "1.80.0".to_string()), "1.80.0");
+        assert_eq!(
+            detect_or_build_version(|| panic!("no version metadata")),
+            BUILD_RUSTC_VERSION
+        );
+    }
+}
@@ -7,13 +7,151 @@
 //!
 //! This module provides functionality for creating and managing test sessions,
 //! setting tags, error information, and closing sessions.
+use crate::test_optimization::analysis;
+use crate::test_optimization::efd;
 use crate::test_optimization::lib::*;
+use crate::test_optimization::naming;
+use crate::test_optimization::registry;
+use crate::test_optimization::stats;
 use crate::test_optimization::test_optimization::*;
 use crate::test_optimization::utils::*;
 use crate::test_optimization::*;
+use std::collections::HashMap;
 use std::ffi::{c_char, CString};
+use std::fmt;
 use std::ptr::null_mut;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread::panicking;
+use std::time::Duration;
+
+/// Serializes `get_or_create_module` so concurrent callers share the same module
+static MODULE_CREATION: Mutex<()> = Mutex::new(());
+
+/// The session of the process, see `TestSession::global`
+static GLOBAL_SESSION: OnceLock<TestSession> = OnceLock::new();
+
+#[derive(Debug, Clone, Default)]
+/// Represents the git metadata of the checkout under test
+///
+/// `repository_url` and `commit_sha` are required for the Intelligent Test Runner and known
+/// tests to work; `branch` is recommended (it is absent on detached-HEAD checkouts), and
+/// `commit_message` and `author` are optional.
+pub struct GitInfo {
+    /// The repository URL
+    #[allow(dead_code)]
+    pub repository_url: String,
+    /// The branch name, if any
+    #[allow(dead_code)]
+    pub branch: Option<String>,
+    /// The full commit SHA
+    #[allow(dead_code)]
+    pub commit_sha: String,
+    /// The commit message, if known
+    #[allow(dead_code)]
+    pub commit_message: Option<String>,
+    /// The commit author name, if known
+    #[allow(dead_code)]
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Represents the test counts of a session, reported as the standard session metrics
+pub struct SessionSummary {
+    /// Total number of tests
+    #[allow(dead_code)]
+    pub tests_total: u64,
+    /// Number of passed tests
+    #[allow(dead_code)]
+    pub tests_passed: u64,
+    /// Number of failed tests
+    #[allow(dead_code)]
+    pub tests_failed: u64,
+    /// Number of skipped tests, including the ones skipped by ITR
+    #[allow(dead_code)]
+    pub tests_skipped: u64,
+    /// Number of tests skipped by the Intelligent Test Runner
+    #[allow(dead_code)]
+    pub tests_skipped_by_itr: u64,
+    /// Number of tests detected as flaky (e.g. passed after a retry)
+    #[allow(dead_code)]
+    pub tests_flaky: u64,
+}
+
+#[derive(Debug, Default)]
+/// Accumulates the worst test status of a session to derive its exit code
+///
+/// Any `Fail` makes the outcome `Fail`. Skipped tests don't make a session fail: the outcome
+/// is `Pass` if at least one test passed, and `Skip` only if every recorded test was skipped,
+/// which still maps to exit code 0. Recording is thread-safe, so an outcome can be shared
+/// across the threads running the tests.
+pub struct SessionOutcome {
+    /// Bitmask of the recorded statuses, indexed by their discriminant
+    seen: AtomicU8,
+}
+impl SessionOutcome {
+    /// Creates an empty outcome
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the status of a test
+    #[allow(dead_code)]
+    pub fn record(&self, status: TestStatus) {
+        self.seen.fetch_or(1 << status as u8, Ordering::Relaxed);
+    }
+
+    /// Gets the worst status recorded so far, `None` if no test was recorded
+    #[allow(dead_code)]
+    pub fn status(&self) -> Option<TestStatus> {
+        let seen = self.seen.load(Ordering::Relaxed);
+        [TestStatus::Fail, TestStatus::Pass, TestStatus::Skip]
+            .into_iter()
+            .find(|status| seen & (1 << *status as u8) != 0)
+    }
+
+    /// Gets the exit code to pass to `TestSession::close`, 0 if no test was recorded
+    #[allow(dead_code)]
+    pub fn exit_code(&self) -> i32 {
+        self.status().map_or(0, |status| status.exit_code())
+    }
+}
+
+#[derive(Debug)]
+/// Tracks the retries left in a session-wide budget, shared by flaky test retries and early
+/// flake detection
+///
+/// Consuming is thread-safe, so the budget can be shared across the threads running the tests.
+pub struct RetryBudget {
+    /// Number of retries left
+    remaining: AtomicU32,
+}
+impl RetryBudget {
+    /// Creates a budget of `total` retries
+    #[allow(dead_code)]
+    pub fn new(total: u32) -> Self {
+        Self {
+            remaining: AtomicU32::new(total),
+        }
+    }
+
+    /// Consumes `n` retries, returning `false` without consuming any if fewer are left
+    #[allow(dead_code)]
+    pub fn try_consume(&self, n: u32) -> bool {
+        self.remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |remaining| {
+                remaining.checked_sub(n)
+            })
+            .is_ok()
+    }
+
+    /// Gets the number of retries left
+    #[allow(dead_code)]
+    pub fn remaining(&self) -> u32 {
+        self.remaining.load(Ordering::Acquire)
+    }
+}
 
 #[derive(Debug, Clone)]
 /// Represents a test session
@@ -21,7 +159,20 @@ pub struct TestSession {
     /// Session ID
     #[allow(dead_code)]
     pub session_id: u64,
+    /// Closed flag shared by the clones of this session
+    pub(in crate::test_optimization) closed: CloseFlag,
+    /// Retry budget shared by the clones of this session, seeded on first use
+    pub(in crate::test_optimization) retry_budget: Arc<OnceLock<RetryBudget>>,
+}
+impl fmt::Display for TestSession {
+    /// Displays the session by its id, e.g. `Session [id=1]`
+    ///
+    /// The session has no name of its own: its command is only known to the native library.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        registry::fmt_handle(f, HandleKind::Session, "", self.session_id)
+    }
 }
+
 impl TestSession {
     /// Creates a new test session
     #[allow(dead_code)]
@@ -44,14 +195,138 @@ impl TestSession {
                 &mut now,
             )
         };
-        Self {
+        let closed = registry::register(
+            session_result.session_id,
+            HandleKind::Session,
+            "",
+            0,
+            now,
+        );
+        let session = Self {
             session_id: session_result.session_id,
+            closed,
+            retry_budget: Arc::default(),
+        };
+        if let Some(operation_name) = naming::operation_name(HandleKind::Session) {
+            session.set_string_tag(tags::SPAN_NAME, operation_name);
+        }
+        session
+    }
+
+    /// Get the session of this process, creating it on first access
+    ///
+    /// Lets `#[test]` functions reach the session without passing it around. Unless a session
+    /// was installed with `set_global`, the first access initializes the library with
+    /// `TestOptimization::init` (if it isn't already) and creates a session without framework.
+    ///
+    /// The global session is never closed nor dropped by the SDK: close it and call
+    /// `TestOptimization::shutdown` explicitly at the end of the process. After that, it
+    /// keeps returning the closed session, whose modules are no longer reported.
+    #[allow(dead_code)]
+    pub fn global() -> &'static TestSession {
+        GLOBAL_SESSION.get_or_init(|| {
+            if !TestOptimization::is_initialized() {
+                TestOptimization::init();
+            }
+            TestSession::create(None::<&str>, None::<&str>)
+        })
+    }
+
+    /// Initialize the library in analysis mode and create a session previewing what test
+    /// optimization would do, without reporting anything
+    ///
+    /// The library runs on the mock tracer with coverage and logs disabled, so no spans,
+    /// coverage or logs are sent to the backend: closing the handles only records the spans in
+    /// memory. The settings, known tests, skippable tests and test management tests are
+    /// fetched from the backend as in a real run (the native library may also upload git
+    /// metadata, which the skippable tests are computed from) and stay available through the
+    /// usual query methods, e.g. `TestOptimization::get_skippable_tests` or
+    /// `TestSuite::is_skippable`. Every test closed afterwards is matched against them, see
+    /// `TestSession::analysis_summary`.
+    ///
+    /// Returns `None` if the library was already initialized, since the mode can't change.
+    #[allow(dead_code)]
+    pub fn init_analysis() -> Option<TestSession> {
+        if !TestOptimization::init_mock_with_options(MockTracerOptions {
+            capture_tags: false,
+            capture_coverage: false,
+            capture_logs: false,
+            deterministic_ids: false,
+        }) {
+            return None;
+        }
+        analysis::start();
+        Some(TestSession::create(None::<&str>, None::<&str>))
+    }
+
+    /// Get what test optimization would have done with the tests closed so far, `None` unless
+    /// a session was created with `init_analysis`
+    #[allow(dead_code)]
+    pub fn analysis_summary() -> Option<AnalysisSummary> {
+        analysis::summary()
+    }
+
+    /// Get the suite of the global session with the given module and suite names, creating
+    /// them if needed
+    ///
+    /// The module is reported with the `cargo-test` framework and the version of rustc. This
+    /// is where the `#[dd_test]` attribute of the `macros` feature reports its tests, with the
+    /// crate name as the module and the Rust module path as the suite.
+    #[allow(dead_code)]
+    pub fn global_suite(module_name: impl AsRef<str>, suite_name: impl AsRef<str>) -> TestSuite {
+        Self::global()
+            .get_or_create_module(module_name, "cargo-test", TestOptimization::runtime_version())
+            .get_or_create_test_suite(suite_name)
+    }
+
+    /// Install a pre-configured session as the session of this process, see `global`
+    ///
+    /// Returns the session back if the global session was already installed or created.
+    #[allow(dead_code)]
+    pub fn set_global(session: TestSession) -> Result<(), TestSession> {
+        GLOBAL_SESSION.set(session)
+    }
+
+    /// Get the number of known tests, without building the map of `get_known_tests`
+    #[allow(dead_code)]
+    pub fn known_tests_count() -> usize {
+        unsafe {
+            let known_tests = topt_get_known_tests();
+            let count = native_slice(known_tests.data, known_tests.len).len();
+            topt_free_known_tests(known_tests);
+            count
+        }
+    }
+
+    /// Get the number of skippable tests, without building the map of `get_skippable_tests`
+    #[allow(dead_code)]
+    pub fn skippable_tests_count() -> usize {
+        unsafe {
+            let skippable_tests = topt_get_skippable_tests();
+            let count = native_slice(skippable_tests.data, skippable_tests.len).len();
+            topt_free_skippable_tests(skippable_tests);
+            count
+        }
+    }
+
+    /// Get the number of test management tests, without building the map of
+    /// `get_test_management_tests`
+    #[allow(dead_code)]
+    pub fn test_management_tests_count() -> usize {
+        unsafe {
+            let test_management_tests = topt_get_test_management_tests();
+            let count = native_slice(test_management_tests.data, test_management_tests.len).len();
+            topt_free_test_management_tests(test_management_tests);
+            count
         }
     }
 
     /// Set a string tag for the test session
     #[allow(dead_code)]
     pub fn set_string_tag(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let key_cstring = CString::new(key.as_ref()).unwrap();
         let value_cstring = CString::new(value.as_ref()).unwrap();
         unsafe {
@@ -63,10 +338,25 @@ impl TestSession {
         }
     }
 
+    /// Set multiple string tags for the test session, e.g. the ones from `CiProvider::detect_tags`
+    #[allow(dead_code)]
+    pub fn set_string_tags<K: AsRef<str>, V: AsRef<str>>(&self, tags: &HashMap<K, V>) -> bool {
+        let mut result = true;
+        for (key, value) in tags {
+            result &= self.set_string_tag(key, value);
+        }
+        result
+    }
+
     /// Set a numeric tag for the test session
     #[allow(dead_code)]
     pub fn set_number_tag(&self, key: impl AsRef<str>, value: f64) -> bool {
-        let key_cstring = CString::new(key.as_ref()).unwrap();
+        !self.closed.is_closed() && self.set_number_tag_unchecked(key.as_ref(), value)
+    }
+
+    /// Sets a numeric tag for this session even once closed, e.g. the clock skew on close
+    fn set_number_tag_unchecked(&self, key: &str, value: f64) -> bool {
+        let key_cstring = CString::new(key).unwrap();
         unsafe {
             Bool_to_bool(topt_session_set_number_tag(
                 self.session_id,
@@ -76,6 +366,84 @@ impl TestSession {
         }
     }
 
+    /// Set a typed tag for the test session, dispatching to the string or numeric setter
+    #[allow(dead_code)]
+    pub fn set_tag(&self, key: impl AsRef<str>, value: impl Into<TagValue>) -> bool {
+        let key = key.as_ref();
+        value.into().apply(
+            |value| self.set_string_tag(key, value),
+            |value| self.set_number_tag(key, value),
+        )
+    }
+
+    /// Set the standard summary metrics (`test.session.tests_*`) for the test session
+    #[allow(dead_code)]
+    pub fn record_summary(&self, summary: &SessionSummary) -> bool {
+        let metrics = [
+            (tags::SESSION_TESTS_TOTAL, summary.tests_total),
+            (tags::SESSION_TESTS_PASSED, summary.tests_passed),
+            (tags::SESSION_TESTS_FAILED, summary.tests_failed),
+            (tags::SESSION_TESTS_SKIPPED, summary.tests_skipped),
+            (tags::SESSION_TESTS_SKIPPED_BY_ITR, summary.tests_skipped_by_itr),
+            (tags::SESSION_TESTS_FLAKY, summary.tests_flaky),
+        ];
+        let mut result = true;
+        for (key, value) in metrics {
+            result &= self.set_number_tag(key, value as f64);
+        }
+        result
+    }
+
+    /// Get the number of tests of the session skipped by ITR through `Test::skip_by_itr`
+    #[allow(dead_code)]
+    pub fn itr_skipped_count(&self) -> u64 {
+        stats::itr_skipped(self.session_id).0
+    }
+
+    /// Get the estimated CI time saved by the tests of the session skipped by ITR
+    ///
+    /// This is the sum of the durations passed to `Test::skip_by_itr_with_estimate`; the
+    /// native library doesn't provide historical durations, so skips without an estimate
+    /// don't add to it.
+    #[allow(dead_code)]
+    pub fn itr_estimated_time_saved(&self) -> Duration {
+        stats::itr_skipped(self.session_id).1
+    }
+
+    /// Get the retry budget of the test session
+    ///
+    /// It is seeded on first use with `FlakyTestRetriesSettings::total_retry_count` (so after
+    /// initializing), and shared by the clones of the session. `TestSuite::create_retry`
+    /// consumes it.
+    #[allow(dead_code)]
+    pub fn retry_budget(&self) -> &RetryBudget {
+        self.retry_budget.get_or_init(|| {
+            let total_retry_count = TestOptimization::get_flaky_test_retries_settings().total_retry_count;
+            RetryBudget::new(total_retry_count.max(0) as u32)
+        })
+    }
+
+    /// Get the live state of early flake detection in the test session
+    ///
+    /// The first call starts tracking the session: it fetches the settings and the known
+    /// tests, and from then on the new tests are counted as the tests are created, so call it
+    /// once when the session starts. Once `faulty` turns `true` (see
+    /// `EfDSettings::is_faulty_session`), `enabled` is `false` for the rest of the session
+    /// and adapters should stop retrying new tests. The SDK doesn't tag the new tests.
+    #[allow(dead_code)]
+    pub fn efd_status(&self) -> EfdStatus {
+        efd::status(self.session_id, &self.closed)
+    }
+
+    /// Keep the trace of the test session regardless of the sampling configuration
+    ///
+    /// Sets the `manual.keep` tag, i.e. the `SAMPLING_PRIORITY_USER_KEEP` priority. This only
+    /// applies to the trace of the session; use `Span::set_sampling_priority` for spans.
+    #[allow(dead_code)]
+    pub fn keep(&self) -> bool {
+        self.set_string_tag(tags::MANUAL_KEEP, "true")
+    }
+
     /// Set error information for the test session
     #[allow(dead_code)]
     pub fn set_error_info(
@@ -84,6 +452,9 @@ impl TestSession {
         error_message: impl AsRef<str>,
         error_stacktrace: impl AsRef<str>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let error_type_cstring = CString::new(error_type.as_ref()).unwrap();
         let error_message_cstring = CString::new(error_message.as_ref()).unwrap();
         let error_stacktrace_cstring = CString::new(error_stacktrace.as_ref()).unwrap();
@@ -98,17 +469,123 @@ impl TestSession {
         }
     }
 
+    /// Set the top-level test framework (e.g. the runner: `cargo-test`, `nextest`) of the test session
+    ///
+    /// This sets the same tags as the framework passed to `create`, for when the runner is only
+    /// known after the session was created. Modules keep their own framework.
+    #[allow(dead_code)]
+    pub fn set_framework(&self, name: impl AsRef<str>, version: impl AsRef<str>) -> bool {
+        self.set_string_tag(tags::TEST_FRAMEWORK, name)
+            & self.set_string_tag(tags::TEST_FRAMEWORK_VERSION, version)
+    }
+
+    /// Set the git metadata for the test session, overriding the autodetected values
+    ///
+    /// This only tags the session span. The backend requests made at init (settings, known and
+    /// skippable tests) use the values detected at that time, so for shallow or detached-HEAD
+    /// checkouts the `DD_GIT_REPOSITORY_URL`, `DD_GIT_BRANCH` and `DD_GIT_COMMIT_SHA`
+    /// environment variables must also be set before `TestOptimization::init`.
+    #[allow(dead_code)]
+    pub fn set_git_info(&self, git_info: &GitInfo) -> bool {
+        let mut result = self.set_string_tag(tags::GIT_REPOSITORY_URL, &git_info.repository_url);
+        result &= self.set_string_tag(tags::GIT_COMMIT_SHA, &git_info.commit_sha);
+        if let Some(branch) = &git_info.branch {
+            result &= self.set_string_tag(tags::GIT_BRANCH, branch);
+        }
+        if let Some(commit_message) = &git_info.commit_message {
+            result &= self.set_string_tag(tags::GIT_COMMIT_MESSAGE, commit_message);
+        }
+        if let Some(author) = &git_info.author {
+            result &= self.set_string_tag(tags::GIT_COMMIT_AUTHOR_NAME, author);
+        }
+        result
+    }
+
     /// Close the test session
+    ///
+    /// If the current thread is panicking, `exit_code` is overridden with 1 and the library
+    /// is shut down right away, since the process is not expected to reach its own shutdown.
+    /// Use `close_raw` to report `exit_code` as is. Closing a session that is already closed,
+    /// including through a clone, does nothing.
     #[allow(dead_code)]
     pub fn close(&self, exit_code: i32) {
-        let mut now = get_now();
-        unsafe {
-            if panicking() {
-                topt_session_close(self.session_id, 1, &mut now);
-                TestOptimization::shutdown();
-            } else {
-                topt_session_close(self.session_id, exit_code, &mut now);
-            }
+        if self.closed.is_closed() {
+            return;
+        }
+        if panicking() {
+            self.close_raw(1);
+            TestOptimization::shutdown();
+        } else {
+            self.close_raw(exit_code);
+        }
+    }
+
+    /// Close the test session with exactly the given exit code
+    ///
+    /// Unlike `close`, this never consults `std::thread::panicking()` nor shuts the library
+    /// down, for runners (e.g. one process per test) where the harness decides the outcome.
+    /// Returns false if the session was already closed or the native library failed to close it.
+    #[allow(dead_code)]
+    pub fn close_raw(&self, exit_code: i32) -> bool {
+        if !self.closed.close() {
+            return false;
+        }
+        efd::forget_session(self.session_id);
+        let (_, closed) = close_handle(
+            self.session_id,
+            "topt_session_close",
+            |key, value| self.set_number_tag_unchecked(key, value),
+            |now| unsafe { Bool_to_bool(topt_session_close(self.session_id, exit_code, now)) },
+        );
+        closed
+    }
+
+    /// Creates a custom span under this session, e.g. for setup or I/O done for it
+    #[allow(dead_code)]
+    pub fn create_span(
+        &self,
+        operation_name: impl AsRef<str>,
+        service_name: impl AsRef<str>,
+        resource_name: impl AsRef<str>,
+        span_type: impl AsRef<str>,
+    ) -> Span {
+        Span::create(self.session_id, operation_name, service_name, resource_name, span_type)
+    }
+
+    /// Get the open modules of this session by name
+    #[allow(dead_code)]
+    pub fn modules(&self) -> HashMap<String, TestModule> {
+        registry::open_children(self.session_id, HandleKind::Module)
+            .into_iter()
+            .map(|(module_id, handle)| {
+                let module = TestModule {
+                    module_id,
+                    session_id: self.session_id,
+                    closed: handle.closed.clone(),
+                    name: handle.name.as_str().into(),
+                };
+                (handle.name, module)
+            })
+            .collect()
+    }
+
+    /// Get the open module with the given name, or create it if there is none
+    ///
+    /// This lets re-entrant discovery (e.g. several crates of a workspace reporting under one
+    /// session) share a module instead of creating duplicate module spans. It is safe to call
+    /// from multiple threads: concurrent callers get the same module. Modules are plain ID
+    /// handles, so they can be cloned and used from parallel threads once created.
+    #[allow(dead_code)]
+    pub fn get_or_create_module(
+        &self,
+        name: impl AsRef<str>,
+        framework_name: impl AsRef<str>,
+        framework_version: impl AsRef<str>,
+    ) -> TestModule {
+        let _creation = MODULE_CREATION.lock().unwrap_or_else(|e| e.into_inner());
+        match self.modules().remove(name.as_ref()) {
+            Some(module) => module,
+            None => self.create_module(name, framework_name, framework_version),
         }
     }
 
@@ -135,9 +612,22 @@ impl TestSession {
             )
         };
 
-        TestModule {
+        let closed = registry::register(
+            module_result.module_id,
+            HandleKind::Module,
+            name.as_ref(),
+            self.session_id,
+            now,
+        );
+        let module = TestModule {
             session_id: self.session_id,
             module_id: module_result.module_id,
+            closed,
+            name: name.as_ref().into(),
+        };
+        if let Some(operation_name) = naming::operation_name(HandleKind::Module) {
+            module.set_string_tag(tags::SPAN_NAME, operation_name);
         }
+        module
     }
 }
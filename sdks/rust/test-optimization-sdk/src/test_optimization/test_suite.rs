@@ -7,10 +7,37 @@
 //!
 //! This module provides functionality for creating and managing test suites,
 //! setting tags, error information, source code, and closing suites.
+use crate::test_optimization::efd;
 use crate::test_optimization::lib::*;
+use crate::test_optimization::naming;
+use crate::test_optimization::registry;
+use crate::test_optimization::sdk_metrics;
+use crate::test_optimization::time::system_time_to_unix;
 use crate::test_optimization::utils::*;
 use crate::test_optimization::*;
-use std::ffi::{c_char, CString};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_char, CStr, CString};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Skippable tests by suite and test name, fetched once for `TestSuite::is_skippable` (or
+/// ahead of it by `TestOptimization::await_skippable_tests`)
+pub(in crate::test_optimization) static SKIPPABLE_TESTS: OnceLock<HashMap<String, HashMap<String, Vec<SkippableTest>>>> =
+    OnceLock::new();
+
+/// Whether a test is in the skippable tests, by suite and test name, with matching parameters
+fn is_listed_skippable(
+    skippable_tests: &HashMap<String, HashMap<String, Vec<SkippableTest>>>,
+    suite_name: &str,
+    test_name: &str,
+    parameters: &TestParameters,
+) -> bool {
+    skippable_tests
+        .get(suite_name)
+        .and_then(|tests| tests.get(test_name))
+        .is_some_and(|tests| tests.iter().any(|test| test.matches(test_name, parameters)))
+}
 
 #[derive(Debug, Clone)]
 /// Represents a test suite within a module
@@ -21,17 +48,60 @@ pub struct TestSuite {
     pub(in crate::test_optimization) module_id: u64,
     /// The unique identifier for this suite
     pub suite_id: u64,
+    /// Closed flag shared by the clones of this suite
+    pub(in crate::test_optimization) closed: CloseFlag,
+    /// Name of the suite, empty if unknown
+    pub(in crate::test_optimization) name: Arc<str>,
+}
+impl fmt::Display for TestSuite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        registry::fmt_handle(f, HandleKind::Suite, &self.name, self.suite_id)
+    }
 }
+
 impl TestSuite {
+    /// Creates a suite under a module known only by its IDs, e.g. received from another process
+    ///
+    /// The caller is responsible for passing the ID of the session the module belongs to; a
+    /// mismatch reports the suite under the wrong session. Returns `None` if an ID is zero.
+    #[allow(dead_code)]
+    pub fn create_under(
+        session_id: u64,
+        module_id: u64,
+        name: impl AsRef<str>,
+    ) -> Option<TestSuite> {
+        if session_id == 0 || module_id == 0 {
+            return None;
+        }
+        let module = TestModule {
+            session_id,
+            module_id,
+            closed: CloseFlag::default(),
+            name: registry::name(module_id).unwrap_or_default().into(),
+        };
+        Some(module.create_test_suite(name))
+    }
+
     /// Gets the parent module of this suite
+    ///
+    /// The returned handle doesn't share the closed flag of the module it was created from,
+    /// so only close one of them.
     #[allow(dead_code)]
     pub fn get_module(&self) -> TestModule {
-        TestModule { module_id: self.module_id, session_id: self.session_id }
+        TestModule {
+            module_id: self.module_id,
+            session_id: self.session_id,
+            closed: CloseFlag::default(),
+            name: registry::name(self.module_id).unwrap_or_default().into(),
+        }
     }
 
     /// Sets a string tag for this suite
     #[allow(dead_code)]
     pub fn set_string_tag(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let key_cstring = CString::new(key.as_ref()).unwrap();
         let value_cstring = CString::new(value.as_ref()).unwrap();
         unsafe {
@@ -46,7 +116,12 @@ impl TestSuite {
     /// Sets a numeric tag for this suite
     #[allow(dead_code)]
     pub fn set_number_tag(&self, key: impl AsRef<str>, value: f64) -> bool {
-        let key_cstring = CString::new(key.as_ref()).unwrap();
+        !self.closed.is_closed() && self.set_number_tag_unchecked(key.as_ref(), value)
+    }
+
+    /// Sets a numeric tag for this suite even once closed, e.g. the clock skew on close
+    fn set_number_tag_unchecked(&self, key: &str, value: f64) -> bool {
+        let key_cstring = CString::new(key).unwrap();
         unsafe {
             Bool_to_bool(topt_suite_set_number_tag(
                 self.suite_id,
@@ -56,6 +131,16 @@ impl TestSuite {
         }
     }
 
+    /// Sets a typed tag for this suite, dispatching to the string or numeric setter
+    #[allow(dead_code)]
+    pub fn set_tag(&self, key: impl AsRef<str>, value: impl Into<TagValue>) -> bool {
+        let key = key.as_ref();
+        value.into().apply(
+            |value| self.set_string_tag(key, value),
+            |value| self.set_number_tag(key, value),
+        )
+    }
+
     /// Sets error information for this suite
     #[allow(dead_code)]
     pub fn set_error_info(
@@ -64,6 +149,9 @@ impl TestSuite {
         error_message: impl AsRef<str>,
         error_stacktrace: impl AsRef<str>,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let error_type_cstring = CString::new(error_type.as_ref()).unwrap();
         let error_message_cstring = CString::new(error_message.as_ref()).unwrap();
         let error_stacktrace_cstring = CString::new(error_stacktrace.as_ref()).unwrap();
@@ -85,6 +173,9 @@ impl TestSuite {
         start_line: *const i32,
         end_line: *const i32,
     ) -> bool {
+        if self.closed.is_closed() {
+            return false;
+        }
         let file_cstring = CString::new(file.as_ref()).unwrap();
         unsafe {
             Bool_to_bool(topt_suite_set_source(
@@ -97,31 +188,271 @@ impl TestSuite {
     }
 
     /// Closes this suite
+    ///
+    /// The native library infers the suite status from its tests (and from the error info
+    /// set on the suite); use `close_with_status` to report it explicitly. Closing a suite
+    /// that is already closed, including through a clone, does nothing and returns `false`.
     #[allow(dead_code)]
     pub fn close(&self) -> bool {
-        let mut now = get_now();
-        unsafe {
-            Bool_to_bool(topt_suite_close(self.suite_id, &mut now))
+        if !self.closed.close() {
+            return false;
+        }
+        let (_, closed) = close_handle(
+            self.suite_id,
+            "topt_suite_close",
+            |key, value| self.set_number_tag_unchecked(key, value),
+            |now| unsafe { Bool_to_bool(topt_suite_close(self.suite_id, now)) },
+        );
+        closed
+    }
+
+    /// Closes this suite with an explicit status, e.g. when importing results of an external runner
+    ///
+    /// The status is set as the `test.status` tag before closing, and nothing else: no error
+    /// info is set for `Fail`. The tag overrides the status the native library would infer
+    /// (see `close`), in both directions.
+    #[allow(dead_code)]
+    pub fn close_with_status(&self, status: TestStatus) -> bool {
+        close_with_status_tag(
+            &self.closed,
+            status,
+            |key, value| self.set_string_tag(key, value),
+            || self.close(),
+        )
+    }
+
+    /// Creates a custom span under this suite, e.g. for setup or I/O done for it
+    #[allow(dead_code)]
+    pub fn create_span(
+        &self,
+        operation_name: impl AsRef<str>,
+        service_name: impl AsRef<str>,
+        resource_name: impl AsRef<str>,
+        span_type: impl AsRef<str>,
+    ) -> Span {
+        Span::create(self.suite_id, operation_name, service_name, resource_name, span_type)
+    }
+
+    /// Whether the test with the given name and parameters can be skipped by the Intelligent
+    /// Test Runner
+    ///
+    /// The skippable tests are fetched with `TestOptimization::get_skippable_tests` on the
+    /// first call and kept for the rest of the process.
+    #[allow(dead_code)]
+    pub fn is_skippable(&self, test_name: &str, parameters: &TestParameters) -> bool {
+        let Some(suite_name) = registry::name(self.suite_id) else {
+            return false;
+        };
+        let skippable_tests = SKIPPABLE_TESTS.get_or_init(TestOptimization::get_skippable_tests);
+        is_listed_skippable(skippable_tests, &suite_name, test_name, parameters)
+    }
+
+    /// Runs a test body as a new test within this suite, see `Test::run`
+    ///
+    /// The test is created with the parameters, if any. When it can be skipped by the
+    /// Intelligent Test Runner, it is closed with `Test::skip_by_itr` without running `body`.
+    /// This is what the `#[dd_test]` attribute of the `macros` feature expands to.
+    #[allow(dead_code)]
+    pub fn run_test<R: TestOutcome>(
+        &self,
+        name: impl AsRef<str>,
+        parameters: &TestParameters,
+        body: impl FnOnce() -> R,
+    ) -> R {
+        let test = if parameters.is_empty() {
+            self.create_test(name.as_ref())
+        } else {
+            self.create_test_with_parameters(name.as_ref(), parameters)
+        };
+        if self.is_skippable(name.as_ref(), parameters) {
+            test.skip_by_itr();
+            return R::skipped();
         }
+        test.run(body)
     }
 
     /// Creates a new test within this suite
     #[allow(dead_code)]
     pub fn create_test(&self, name: impl AsRef<str>) -> Test {
-        let test_name_cstring = CString::new(name.as_ref()).unwrap();
-        let mut now = get_now();
-        let test_result = unsafe {
-            topt_test_create(
-                self.suite_id,
-                test_name_cstring.as_ptr() as *mut c_char,
-                &mut now,
-            )
-        };
-        Test {
-            test_id: test_result.test_id,
-            suite_id: self.suite_id,
-            module_id: self.module_id,
-            session_id: self.session_id,
+        self.create_test_with_tags(name, BTreeMap::new())
+    }
+
+    /// Creates a new test within this suite with tags, replacing the inherited tags of the
+    /// same keys so each key is only set once
+    pub(in crate::test_optimization) fn create_test_with_tags(
+        &self,
+        name: impl AsRef<str>,
+        tags: BTreeMap<String, TagValue>,
+    ) -> Test {
+        self.create_tests_started_at(&[name], get_now(), Instant::now(), tags)
+            .remove(0)
+    }
+
+    /// Sets a tag applied to every test created under this suite from now on
+    ///
+    /// The tag isn't set on the suite itself. The SDK applies it when a test is created
+    /// (`create_test`, `create_tests` and the helpers built on them), so tests created
+    /// before the call don't get it; setting a key again replaces its value for the next
+    /// tests. Returns `false` if the suite is closed.
+    #[allow(dead_code)]
+    pub fn set_inherited_tag(&self, key: impl AsRef<str>, value: impl Into<TagValue>) -> bool {
+        !self.closed.is_closed()
+            && registry::record_inherited_tag(self.suite_id, key.as_ref(), value.into())
+    }
+
+    /// Creates a retry of a test within this suite, if the retry budget allows it
+    ///
+    /// Consumes one retry from `budget` (usually `TestSession::retry_budget`) and creates the
+    /// test marked with `Test::mark_retry`. Returns `None` without creating the test once the
+    /// budget is exhausted.
+    #[allow(dead_code)]
+    pub fn create_retry(
+        &self,
+        name: impl AsRef<str>,
+        attempt: i32,
+        original_status: TestStatus,
+        budget: &RetryBudget,
+    ) -> Option<Test> {
+        if !budget.try_consume(1) {
+            return None;
         }
+        let test = self.create_test(name);
+        test.mark_retry(attempt, original_status);
+        Some(test)
+    }
+
+    /// Creates a batch of tests within this suite, e.g. the ones discovered up front
+    ///
+    /// The native library has no batch call, so this still creates the tests one by one,
+    /// but shares a single start time and reuses one buffer for the C strings of all names.
+    #[allow(dead_code)]
+    pub fn create_tests(&self, names: &[impl AsRef<str>]) -> Vec<Test> {
+        self.create_tests_started_at(names, get_now(), Instant::now(), BTreeMap::new())
+    }
+
+    /// Reports tests that already ran, e.g. results collected by a meta-runner after the fact
+    ///
+    /// Each test is created and closed right away with its status, in order. With a duration,
+    /// the test is backdated to start that long before it is closed, so the reported duration
+    /// matches; without one it is reported with a zero duration. Returns the closed tests,
+    /// e.g. to read their IDs.
+    #[allow(dead_code)]
+    pub fn report_results(&self, results: &[(String, TestStatus, Option<Duration>)]) -> Vec<Test> {
+        results
+            .iter()
+            .map(|(name, status, duration)| {
+                let duration = duration.unwrap_or_default();
+                let start_time = SystemTime::now()
+                    .checked_sub(duration)
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                let started_at = Instant::now()
+                    .checked_sub(duration)
+                    .unwrap_or_else(Instant::now);
+                let test = self
+                    .create_tests_started_at(
+                        &[name],
+                        system_time_to_unix(start_time),
+                        started_at,
+                        BTreeMap::new(),
+                    )
+                    .remove(0);
+                test.close(*status);
+                test
+            })
+            .collect()
+    }
+
+    /// Creates a batch of tests within this suite sharing the given start time
+    ///
+    /// Each test gets the inherited tags of the suite, replaced by `tags` for the same keys.
+    fn create_tests_started_at(
+        &self,
+        names: &[impl AsRef<str>],
+        mut now: topt_UnixTime,
+        started_at: Instant,
+        tags: BTreeMap<String, TagValue>,
+    ) -> Vec<Test> {
+        let operation_name = naming::operation_name(HandleKind::Test);
+        let mut creation_tags: BTreeMap<String, TagValue> =
+            registry::inherited_tags(self.suite_id).into_iter().collect();
+        creation_tags.extend(tags);
+        let mut name_buffer: Vec<u8> = Vec::new();
+        names
+            .iter()
+            .map(|name| {
+                name_buffer.clear();
+                name_buffer.extend_from_slice(name.as_ref().as_bytes());
+                name_buffer.push(0);
+                let test_name_cstr = CStr::from_bytes_with_nul(&name_buffer).unwrap();
+                let test_result = sdk_metrics::time_ffi_call("topt_test_create", || unsafe {
+                    topt_test_create(
+                        self.suite_id,
+                        test_name_cstr.as_ptr() as *mut c_char,
+                        &mut now,
+                    )
+                });
+                let closed = registry::register(
+                    test_result.test_id,
+                    HandleKind::Test,
+                    name.as_ref(),
+                    self.suite_id,
+                    now,
+                );
+                let test = Test::new(self, test_result.test_id, name.as_ref(), closed, started_at);
+                if let Some(operation_name) = &operation_name {
+                    test.set_string_tag(tags::SPAN_NAME, operation_name);
+                }
+                for (key, value) in &creation_tags {
+                    test.set_tag(key, value.clone());
+                }
+                efd::record_test_created(&test);
+                test
+            })
+            .collect()
+    }
+
+    /// Creates a new parameterized test within this suite
+    ///
+    /// `parameters_json` is set as the `test.parameters` tag. It should use the same JSON
+    /// shape as `SkippableTest::parameters` (e.g. `{"arguments":{"a":"1"},"metadata":{}}`).
+    /// The JSON is passed through verbatim, whatever the enabled features, since the
+    /// parameters are part of the test identity. Prefer `create_test_with_parameters` when the
+    /// parameters are known as values, which sets them in the canonical form.
+    #[allow(dead_code)]
+    pub fn create_parameterized_test(
+        &self,
+        name: impl AsRef<str>,
+        parameters_json: impl AsRef<str>,
+    ) -> Test {
+        let test = self.create_test(name);
+        test.set_string_tag(tags::TEST_PARAMETERS, parameters_json);
+        test
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itr_skips_only_the_listed_tests_with_matching_parameters() {
+        let parameters = TestParameters::new().with_argument("a", "1");
+        let skippable_test = SkippableTest {
+            suite_name: "my_crate::tests".to_string(),
+            test_name: "adds".to_string(),
+            parameters: parameters.to_json(),
+            custom_configurations_json: String::new(),
+        };
+        let skippable_tests = HashMap::from([(
+            "my_crate::tests".to_string(),
+            HashMap::from([("adds".to_string(), vec![skippable_test])]),
+        )]);
+
+        assert!(is_listed_skippable(&skippable_tests, "my_crate::tests", "adds", &parameters));
+        let other_parameters = TestParameters::new().with_argument("a", "2");
+        assert!(!is_listed_skippable(&skippable_tests, "my_crate::tests", "adds", &other_parameters));
+        assert!(!is_listed_skippable(&skippable_tests, "my_crate::tests", "subtracts", &parameters));
+        assert!(!is_listed_skippable(&skippable_tests, "my_crate::other", "adds", &parameters));
+        assert!(!is_listed_skippable(&HashMap::new(), "my_crate::tests", "adds", &parameters));
     }
 }
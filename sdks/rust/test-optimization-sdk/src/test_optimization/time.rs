@@ -0,0 +1,65 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Time module for converting between `SystemTime` and the native timestamps
+//!
+//! The native library takes timestamps as unsigned seconds and nanoseconds since
+//! the Unix epoch, so the conversions clamp the times it can't represent instead
+//! of panicking.
+pub use crate::test_optimization::lib::topt_UnixTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Converts a `SystemTime` to a native timestamp
+///
+/// Times before the Unix epoch are clamped to the epoch.
+#[allow(dead_code)]
+pub fn system_time_to_unix(time: SystemTime) -> topt_UnixTime {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    topt_UnixTime {
+        sec: since_epoch.as_secs(),
+        nsec: since_epoch.subsec_nanos() as u64,
+    }
+}
+
+/// Clamps a finish time to the start time when the clock went backwards in between
+///
+/// Returns the finish time to report, and the clock skew (how much earlier than the start
+/// the finish time was) when it had to be clamped, so the duration is 0 instead of negative.
+#[allow(dead_code)]
+pub fn clamp_finish_time(
+    start_time: topt_UnixTime,
+    finish_time: topt_UnixTime,
+) -> (topt_UnixTime, Option<Duration>) {
+    match unix_to_system_time(start_time).duration_since(unix_to_system_time(finish_time)) {
+        Ok(skew) if !skew.is_zero() => (start_time, Some(skew)),
+        _ => (finish_time, None),
+    }
+}
+
+/// Converts a native timestamp to a `SystemTime`
+///
+/// Nanoseconds above one second are carried into the seconds, and timestamps beyond
+/// the latest `SystemTime` of the platform saturate to it.
+#[allow(dead_code)]
+pub fn unix_to_system_time(time: topt_UnixTime) -> SystemTime {
+    let since_epoch = Duration::from_secs(time.sec)
+        .checked_add(Duration::from_nanos(time.nsec))
+        .unwrap_or(Duration::MAX);
+    // add the largest steps that fit, halving them on overflow
+    let mut result = UNIX_EPOCH;
+    let mut remaining = since_epoch;
+    let mut step = since_epoch;
+    while !remaining.is_zero() && !step.is_zero() {
+        let next_step = step.min(remaining);
+        match result.checked_add(next_step) {
+            Some(next) => {
+                result = next;
+                remaining -= next_step;
+            }
+            None => step /= 2,
+        }
+    }
+    result
+}
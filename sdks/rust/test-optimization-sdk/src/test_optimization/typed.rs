@@ -0,0 +1,191 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Typed module for handles that borrow their parent
+//!
+//! The handles of the crate root are `Clone` IDs, so nothing stops creating a test
+//! under a suite that has already been closed. The handles of this module borrow
+//! their parent and are closed by value, so the borrow checker rejects closing a
+//! parent while one of its children is alive, and creating a child once the parent
+//! is closed:
+//!
+//! ```no_run
+//! use test_optimization_sdk::typed::Session;
+//! use test_optimization_sdk::TestStatus;
+//!
+//! let session = Session::create(Some("my-framework"), None::<&str>);
+//! let module = session.create_module("my_module", "", "");
+//! let suite = module.create_test_suite("my_suite");
+//! let test = suite.create_test("my_test");
+//! test.untyped().set_string_tag("my-tag", "my-value");
+//! test.close(TestStatus::Pass);
+//! suite.close();
+//! module.close();
+//! session.close(0);
+//! ```
+//!
+//! ```compile_fail,E0382
+//! use test_optimization_sdk::typed::Session;
+//! use test_optimization_sdk::TestStatus;
+//!
+//! let session = Session::create(Some("my-framework"), None::<&str>);
+//! let module = session.create_module("my_module", "", "");
+//! let suite = module.create_test_suite("my_suite");
+//! suite.close();
+//! // error[E0382]: borrow of moved value: `suite`
+//! let test = suite.create_test("my_test");
+//! test.close(TestStatus::Pass);
+//! ```
+//!
+//! ```compile_fail,E0505
+//! use test_optimization_sdk::typed::Session;
+//! use test_optimization_sdk::TestStatus;
+//!
+//! let session = Session::create(Some("my-framework"), None::<&str>);
+//! let module = session.create_module("my_module", "", "");
+//! let suite = module.create_test_suite("my_suite");
+//! let test = suite.create_test("my_test");
+//! // error[E0505]: cannot move out of `suite` because it is borrowed
+//! suite.close();
+//! test.close(TestStatus::Pass);
+//! ```
+//!
+//! The untyped handle is available through `untyped` for the setters; closing it
+//! directly isn't tracked by the borrow checker.
+use crate::test_optimization::{TestModule, TestSession, TestStatus, TestSuite};
+use std::marker::PhantomData;
+
+#[derive(Debug)]
+/// Represents a test session whose modules borrow it
+pub struct Session {
+    /// The untyped session
+    session: TestSession,
+}
+impl Session {
+    /// Creates a new test session, see `TestSession::create`
+    #[allow(dead_code)]
+    pub fn create(
+        framework_name: Option<impl AsRef<str>>,
+        framework_version: Option<impl AsRef<str>>,
+    ) -> Self {
+        Self::new(TestSession::create(framework_name, framework_version))
+    }
+
+    /// Wraps an untyped session
+    #[allow(dead_code)]
+    pub fn new(session: TestSession) -> Self {
+        Self { session }
+    }
+
+    /// Gets the untyped session, e.g. to set tags
+    #[allow(dead_code)]
+    pub fn untyped(&self) -> &TestSession {
+        &self.session
+    }
+
+    /// Creates a module borrowing this session
+    #[allow(dead_code)]
+    pub fn create_module(
+        &self,
+        name: impl AsRef<str>,
+        framework_name: impl AsRef<str>,
+        framework_version: impl AsRef<str>,
+    ) -> Module<'_> {
+        Module {
+            module: self.session.create_module(name, framework_name, framework_version),
+            _session: PhantomData,
+        }
+    }
+
+    /// Closes the session, once none of its modules is alive, see `TestSession::close`
+    #[allow(dead_code)]
+    pub fn close(self, exit_code: i32) {
+        self.session.close(exit_code)
+    }
+}
+
+#[derive(Debug)]
+/// Represents a test module borrowing its session
+pub struct Module<'session> {
+    /// The untyped module
+    module: TestModule,
+    /// The session the module borrows
+    _session: PhantomData<&'session Session>,
+}
+impl Module<'_> {
+    /// Gets the untyped module, e.g. to set tags
+    #[allow(dead_code)]
+    pub fn untyped(&self) -> &TestModule {
+        &self.module
+    }
+
+    /// Creates a test suite borrowing this module
+    #[allow(dead_code)]
+    pub fn create_test_suite(&self, name: impl AsRef<str>) -> Suite<'_> {
+        Suite {
+            suite: self.module.create_test_suite(name),
+            _module: PhantomData,
+        }
+    }
+
+    /// Closes the module, once none of its suites is alive, see `TestModule::close`
+    #[allow(dead_code)]
+    pub fn close(self) -> bool {
+        self.module.close()
+    }
+}
+
+#[derive(Debug)]
+/// Represents a test suite borrowing its module
+pub struct Suite<'module> {
+    /// The untyped suite
+    suite: TestSuite,
+    /// The module the suite borrows
+    _module: PhantomData<&'module TestModule>,
+}
+impl Suite<'_> {
+    /// Gets the untyped suite, e.g. to set tags
+    #[allow(dead_code)]
+    pub fn untyped(&self) -> &TestSuite {
+        &self.suite
+    }
+
+    /// Creates a test borrowing this suite
+    #[allow(dead_code)]
+    pub fn create_test(&self, name: impl AsRef<str>) -> Test<'_> {
+        Test {
+            test: self.suite.create_test(name),
+            _suite: PhantomData,
+        }
+    }
+
+    /// Closes the suite, once none of its tests is alive, see `TestSuite::close`
+    #[allow(dead_code)]
+    pub fn close(self) -> bool {
+        self.suite.close()
+    }
+}
+
+#[derive(Debug)]
+/// Represents a test borrowing its suite
+pub struct Test<'suite> {
+    /// The untyped test
+    test: crate::test_optimization::Test,
+    /// The suite the test borrows
+    _suite: PhantomData<&'suite TestSuite>,
+}
+impl Test<'_> {
+    /// Gets the untyped test, e.g. to set tags or coverage
+    #[allow(dead_code)]
+    pub fn untyped(&self) -> &crate::test_optimization::Test {
+        &self.test
+    }
+
+    /// Closes the test with the given status, see `Test::close`
+    #[allow(dead_code)]
+    pub fn close(self, status: TestStatus) -> bool {
+        self.test.close(status)
+    }
+}
@@ -6,25 +6,221 @@
 //! Utility functions for the test optimization library
 //!
 //! This module provides utility functions for the test optimization library,
-//! including converting between Rust booleans and C-style booleans, and getting
-//! the current time in nanoseconds since the Unix epoch.
+//! including converting between Rust booleans and C-style booleans, getting the
+//! current time as a native timestamp, encoding JSON strings and line ranges,
+//! converting paths to and from C strings, reading panic messages, viewing native arrays as slices,
+//! tracking whether a handle has been closed and closing suites and modules with a status.
 #![allow(non_snake_case)]
 
 use crate::test_optimization::lib::{topt_UnixTime, Bool};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::test_optimization::registry::{self, OpenHandle};
+use crate::test_optimization::sdk_metrics;
+use crate::test_optimization::stats;
+use crate::test_optimization::time::{clamp_finish_time, system_time_to_unix};
+use crate::test_optimization::{tags, TestStatus};
+use std::any::Any;
+use std::collections::BTreeSet;
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-/// Gets the current time in nanoseconds since the Unix epoch
+#[derive(Debug, Clone, Default)]
+/// Closed flag of a handle, shared by all its clones
+///
+/// Closing a native handle frees it, so a second close of the same ID (e.g. through a
+/// clone) must not reach the native library, and neither must the setters called after it.
+pub(in crate::test_optimization) struct CloseFlag(Arc<AtomicBool>);
+
+impl CloseFlag {
+    /// Marks the handle as closed, returning `false` if it already was
+    pub(in crate::test_optimization) fn close(&self) -> bool {
+        !self.0.swap(true, Ordering::AcqRel)
+    }
+
+    /// Whether the handle has been closed
+    pub(in crate::test_optimization) fn is_closed(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Gets the current time as a native timestamp
 pub(in crate::test_optimization) fn get_now() -> topt_UnixTime {
-    let now =SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap();
-    topt_UnixTime {
-        sec: now.as_secs(),
-        nsec: now.subsec_nanos() as u64,
+    system_time_to_unix(SystemTime::now())
+}
+
+/// Gets the finish time of a handle being closed, clamped to its start time
+///
+/// Returns the clock skew when the clock went backwards since the handle was created;
+/// handles unknown to the registry are never clamped.
+pub(in crate::test_optimization) fn get_finish_time(
+    handle: Option<&OpenHandle>,
+) -> (topt_UnixTime, Option<Duration>) {
+    let now = get_now();
+    match handle {
+        Some(handle) => clamp_finish_time(handle.start_time, now),
+        None => (now, None),
+    }
+}
+
+/// Closes a handle whose closed flag was just set: unregisters it and makes the native call
+///
+/// If the clock went backwards since the handle was created, the finish time passed to
+/// `close` is clamped to the start time and the skew is first set with `set_number_tag` as
+/// the `_dd.clock_skew` tag. The native call is timed as `function` and counted in the
+/// stats. Returns the registry record of the handle and the result of the call.
+pub(in crate::test_optimization) fn close_handle(
+    id: u64,
+    function: &'static str,
+    set_number_tag: impl FnOnce(&str, f64) -> bool,
+    close: impl FnOnce(&mut topt_UnixTime) -> bool,
+) -> (Option<OpenHandle>, bool) {
+    let handle = registry::unregister(id);
+    let (mut now, clock_skew) = get_finish_time(handle.as_ref());
+    if let Some(clock_skew) = clock_skew {
+        set_number_tag(tags::CLOCK_SKEW, clock_skew.as_secs_f64());
+    }
+    let result = stats::record_close(sdk_metrics::time_ffi_call(function, || close(&mut now)));
+    (handle, result)
+}
+
+/// Closes a suite or a module with an explicit status, set as its `test.status` tag
+///
+/// Only the tag is set: no error info is made up for `Fail`. The tag takes precedence over
+/// the status the native library infers from the children, in both directions, so `Pass`
+/// reports a suite with failed tests as passed. Returns `false` if the handle is closed.
+pub(in crate::test_optimization) fn close_with_status_tag(
+    closed: &CloseFlag,
+    status: TestStatus,
+    set_string_tag: impl FnOnce(&str, &str) -> bool,
+    close: impl FnOnce() -> bool,
+) -> bool {
+    if closed.is_closed() {
+        return false;
     }
+    set_string_tag(tags::TEST_STATUS, status.as_str());
+    close()
+}
+
+/// Gets the message of a panic payload, for the `&str` and `String` payloads of `panic!`
+///
+/// Other payloads (e.g. from `std::panic::panic_any`) are described as `Box<dyn Any>`, as the
+/// default panic hook does.
+pub(in crate::test_optimization) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
 }
 
 /// Converts a C-style boolean (0 or 1) to a Rust bool
 pub(in crate::test_optimization) fn Bool_to_bool(value: Bool) -> bool {
     value != 0
 }
+
+/// Converts a path to a C string for the native library
+///
+/// On Unix the raw bytes of the path are passed through, so non-UTF-8 paths are kept as is.
+/// Elsewhere (Windows) the native library expects UTF-8, so the path must be valid Unicode.
+/// Returns `None` for paths that can't be represented, including ones with a nul byte.
+pub(in crate::test_optimization) fn path_to_cstring(path: &Path) -> Option<CString> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes()
+    };
+    #[cfg(not(unix))]
+    let bytes = path.to_str()?.as_bytes();
+    CString::new(bytes).ok()
+}
+
+/// Converts a C string received for a path back to a path, the reverse of `path_to_cstring`
+///
+/// Returns `None` for non-UTF-8 data outside of Unix.
+pub(in crate::test_optimization) fn cstr_to_path(value: &CStr) -> Option<PathBuf> {
+    #[cfg(unix)]
+    let path = {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(value.to_bytes()))
+    };
+    #[cfg(not(unix))]
+    let path = PathBuf::from(value.to_str().ok()?);
+    Some(path)
+}
+
+/// Views a native array as a slice, empty when the data pointer is null or the length is zero
+///
+/// The native library returns a null data pointer on its error paths, possibly with a
+/// non-zero length, so the length alone can't be trusted.
+///
+/// # Safety
+///
+/// A non-null `data` must point to `len` initialized elements that outlive the slice.
+pub(in crate::test_optimization) unsafe fn native_slice<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+/// Encodes a string as a quoted JSON string literal
+#[allow(dead_code)]
+pub(in crate::test_optimization) fn json_string(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() + 2);
+    encoded.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if (c as u32) < 0x20 => encoded.push_str(&format!("\\u{:04x}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+/// Formats sorted line numbers as comma-separated ranges (e.g. `3-5,9`)
+pub(in crate::test_optimization) fn line_ranges(lines: &BTreeSet<u32>) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| match start == end {
+            true => start.to_string(),
+            false => format!("{}-{}", start, end),
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::null;
+
+    #[test]
+    fn native_slice_is_empty_for_a_null_pointer_with_a_length() {
+        let slice = unsafe { native_slice(null::<u64>(), 3) };
+        assert!(slice.is_empty());
+    }
+
+    #[test]
+    fn native_slice_is_empty_for_a_zero_length() {
+        let data = [1u64, 2, 3];
+        assert!(unsafe { native_slice(data.as_ptr(), 0) }.is_empty());
+        assert_eq!(unsafe { native_slice(data.as_ptr(), 2) }, &[1, 2]);
+    }
+}
@@ -5,41 +5,114 @@
 
 use crate::test_optimization::*;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 #[test]
 fn complete() {
     // Initialize library
-    TestOptimization::init_mock();
+    TestOptimization::set_service("rust-sdk-tests");
+    assert_eq!(TestOptimization::service().as_deref(), Some("rust-sdk-tests"));
+    // the Go runtime is started when the library is loaded, so it can be called before init
+    let linked_native_version = TestOptimization::linked_native_version();
+    assert!(TestOptimization::init_mock_with_options(MockTracerOptions {
+        deterministic_ids: true,
+        ..MockTracerOptions::default()
+    }));
+    // initializing again is a no-op, and doesn't start the Go runtime again
+    assert!(!TestOptimization::init_mock());
+    assert!(TestOptimization::is_initialized());
+    assert_eq!(TestOptimization::linked_native_version(), linked_native_version);
+
+    // collect the closed tests
+    let closed_tests: Arc<Mutex<Vec<ClosedTestInfo>>> = Arc::default();
+    let closed_tests_sink = Arc::clone(&closed_tests);
+    TestOptimization::on_test_closed(move |info| closed_tests_sink.lock().unwrap().push(info.clone()));
 
     // session
     let session = TestSession::create(Some("cargo test"), None::<&str>);
+    assert!(TestSession::set_global(session.clone()).is_ok());
+    assert_eq!(TestSession::global().session_id, session.session_id);
+    assert!(TestSession::set_global(session.clone()).is_err());
+    assert!(TestSession::init_analysis().is_none());
+    assert!(TestSession::analysis_summary().is_none());
     println!("Hello, world!");
 
+    println!("{:?}", TestOptimization::version_info());
+    let resolved_working_directory = TestOptimization::resolved_working_directory().unwrap();
+    println!("resolved working directory: {}", resolved_working_directory.display());
     println!("{:?}", TestOptimization::get_settings());
     println!("{:?}", TestOptimization::get_flaky_test_retries_settings());
+    #[cfg(feature = "serde")]
+    {
+        let settings_json: serde_json::Value = serde_json::from_str(&TestOptimization::settings_json()).unwrap();
+        assert_eq!(settings_json["settings"]["itr_enabled"], TestOptimization::get_settings().itr_enabled);
+        assert!(settings_json["flaky_test_retries"]["retry_count"].is_number());
+    }
     println!("{:?}", TestOptimization::get_known_tests());
+    assert!(TestOptimization::await_skippable_tests(Duration::from_secs(30)));
+    assert!(TestOptimization::await_skippable_tests(Duration::ZERO));
     println!("{:?}", TestOptimization::get_skippable_tests());
+    assert_eq!(
+        TestOptimization::git_upload_status(),
+        GitUploadStatus { required: false, completed: None, error: None }
+    );
     println!("{:?}", TestOptimization::get_test_management_tests());
+    println!("{:?}", TestOptimization::iter_quarantined().collect::<Vec<_>>());
+    assert_eq!(
+        TestSession::known_tests_count(),
+        TestOptimization::get_known_tests().values().flat_map(|suites| suites.values()).map(Vec::len).sum::<usize>()
+    );
+    assert_eq!(
+        TestSession::skippable_tests_count(),
+        TestOptimization::get_skippable_tests().values().flat_map(|tests| tests.values()).map(Vec::len).sum::<usize>()
+    );
+    println!("test management tests: {}", TestSession::test_management_tests_count());
 
     println!("session id: {:?}", session.session_id);
 
     session.set_string_tag("Session-KeyFromRust", "Hello world");
     session.set_number_tag("Session-NumberFromRust", 42f64);
+    // CI tags from a fixture environment, set before the git info so that it takes precedence
+    let ci_env = [("CI", "woodpecker"), ("CI_STEP_NAME", "test"), ("CI_COMMIT_BRANCH", "ci-branch")];
+    session.set_string_tags(&CiProvider::detect_tags_with(CI_PROVIDERS, |name| {
+        ci_env.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    }));
+    session.set_git_info(&GitInfo {
+        repository_url: "https://github.com/DataDog/test-optimization-native.git".to_string(),
+        branch: Some("my-branch".to_string()),
+        commit_sha: "0123456789abcdef0123456789abcdef01234567".to_string(),
+        commit_message: None,
+        author: Some("Rust SDK".to_string()),
+    });
+    session.set_framework("cargo-test", "1.80");
+    assert!(session.keep());
 
     // Session span
     let session_span = Span::create(session.session_id, "my-operation-name", "my-service", "session-resource-name", "span-type");
     println!("span_id (from session): {:?}", session_span.span_id);
     session_span.set_string_tag("Session-KeyFromRust", "Hello world");
     session_span.set_number_tag("Session-NumberFromRust", 42f64);
+    assert!(session_span.set_resource_name("SELECT 1"));
     sleep(Duration::from_millis(500));
     println!("session_span close: {}", session_span.close());
 
+    // span failed by a caught panic
+    let panicked_span = session.create_span("my-panicked-operation", "my-service", "resource", "span-type");
+    let panic_payload = std::panic::catch_unwind(|| panic!("span work failed")).unwrap_err();
+    assert!(panicked_span.set_error_from_panic(panic_payload.as_ref()));
+    println!("panicked_span close: {}", panicked_span.close());
+
     // module
     let module_name = String::from("my-test-module");
     let module = session.create_module(module_name, "Framework Name", "Framework Version");
     println!("module id: {:?}", module.module_id);
+    assert_eq!(
+        session.get_or_create_module("my-test-module", "Framework Name", "Framework Version").module_id,
+        module.module_id
+    );
+    assert_eq!(session.modules()["my-test-module"].module_id, module.module_id);
 
     module.set_string_tag("Module-KeyFromRust", "Hello world");
     module.set_number_tag("Module-NumberFromRust", 42f64);
@@ -51,6 +124,12 @@ fn complete() {
     module_span.set_number_tag("Session-NumberFromRust", 42f64);
     sleep(Duration::from_millis(500));
     println!("module_span close: {}", module_span.close());
+    assert!(!module_span.clone().close());
+
+    // Module span created through the module
+    let module_custom_span = module.create_span("my-module-operation-name", "my-service", "module-resource-name", "span-type");
+    assert_eq!(module_custom_span.parent_id, module.module_id);
+    assert!(module_custom_span.close());
 
     // suite
     let suite = module.create_test_suite("My Suite");
@@ -67,19 +146,59 @@ fn complete() {
     sleep(Duration::from_millis(500));
     println!("suite_span close: {}", suite_span.close());
 
+    // Suite span created through the suite, with the default service
+    let suite_custom_span = suite.create_span("my-suite-operation-name", "", "suite-resource-name", "span-type");
+    let suite_span_id = suite_custom_span.span_id;
+    assert!(suite_custom_span.close());
+
     // pass test
     let pass_test = suite.create_test("My PassTest");
     pass_test.set_string_tag("Pass-KeyFromRust", "Hello world");
     pass_test.set_number_tag("Pass-NumberFromRust", 42f64);
+    pass_test.set_tag("Pass-BoolFromRust", true);
+    pass_test.set_tag("Pass-IntFromRust", 7);
+    assert_eq!(pass_test.get_string_tag("Pass-KeyFromRust").as_deref(), Some("Hello world"));
+    assert_eq!(pass_test.get_string_tag("Pass-BoolFromRust").as_deref(), Some("true"));
+    assert_eq!(pass_test.get_number_tag("Pass-IntFromRust"), Some(7f64));
+    assert_eq!(pass_test.get_number_tag("Pass-KeyFromRust"), None);
     pass_test.set_test_source("test.rs", &6, &58);
     pass_test.set_coverage_data(&["file.rs"]);
+    #[cfg(feature = "lcov")]
+    {
+        let lcov_path = std::env::temp_dir().join("rust-sdk-tests.info");
+        std::fs::write(&lcov_path, "SF:src/lib.rs\nDA:1,1\nend_of_record\nSF:src/db.rs\nDA:2,1\nend_of_record\n").unwrap();
+        assert_eq!(pass_test.set_coverage_from_lcov(&lcov_path, &["src/lib.rs", "src/missing.rs"]).unwrap(), 1);
+        assert_eq!(pass_test.set_coverage_from_lcov(&lcov_path, &["src/missing.rs"]).unwrap(), 0);
+        assert!(pass_test.set_coverage_from_lcov(&lcov_path.with_extension("missing"), &["src/lib.rs"]).is_err());
+        std::fs::remove_file(&lcov_path).unwrap();
+    }
     pass_test.log("Hello world", Some("tag1=value1,tag2=value2"));
     pass_test.log("Hello world", None::<&str>);
+    let hierarchy = TestOptimization::active_hierarchy();
+    let session_node = hierarchy
+        .iter()
+        .find(|node| node.handle_id == session.session_id)
+        .expect("session missing from the active hierarchy");
+    let module_node = &session_node.children[0];
+    assert_eq!((module_node.kind, module_node.name.as_str()), (HandleKind::Module, "my-test-module"));
+    let suite_node = module_node
+        .children
+        .iter()
+        .find(|node| node.handle_id == suite.suite_id)
+        .expect("suite missing from the active hierarchy");
+    assert!(suite_node.children.iter().any(|node| node.handle_id == pass_test.test_id && node.kind == HandleKind::Test));
+    assert_eq!(
+        pass_test.to_string(),
+        format!("Test \"My PassTest\" [id={}] in suite \"My Suite\"", pass_test.test_id)
+    );
+    assert_eq!(session.to_string(), format!("Session [id={}]", session.session_id));
+    assert!(session_node.to_string().starts_with(&format!("Session [id={}]\n  Module \"my-test-module\"", session.session_id)));
 
     let mut measurement_data: HashMap<&str, f64> = HashMap::new();
     measurement_data.insert("data1", 42f64);
     measurement_data.insert("data2", 64f64);
     pass_test.set_benchmark_number_data("my_custom_measurement", &measurement_data);
+    pass_test.set_benchmark_number_data(BenchmarkMeasureType::Duration, &measurement_data);
 
     let mut measurement_strdata: HashMap<&str, String> = HashMap::new();
     measurement_strdata.insert("datastr1", "MyData".to_string());
@@ -92,18 +211,213 @@ fn complete() {
     println!("span_id (from test): {:?}", test_span.span_id);
     test_span.set_string_tag("Session-KeyFromRust", "Hello world");
     test_span.set_number_tag("Session-NumberFromRust", 42f64);
+    assert!(test_span.set_sampling_priority(SAMPLING_PRIORITY_USER_KEEP));
     sleep(Duration::from_millis(500));
     println!("test_span close: {}", test_span.close());
+    assert!(!test_span.set_string_tag("Closed-KeyFromRust", "too late"));
+    assert!(!test_span.clone().set_number_tag("Closed-NumberFromRust", 1f64));
+    assert!(!test_span.set_error_info("late_error", "set after close", ""));
 
+    // Test span created through the test
+    let test_custom_span = pass_test.create_span("my-test-operation-name", "my-service", "test-resource-name", "span-type");
+    let custom_span_id = test_custom_span.span_id;
+    assert!(test_custom_span.close());
+
+    // span created with every start option
+    let options_span = Span::create_with_options(
+        pass_test.test_id,
+        &SpanStartOptions::new("my-options-operation")
+            .service_name("my-options-service")
+            .resource_name("my-options-resource")
+            .span_type("db")
+            .start_time(SystemTime::now() - Duration::from_secs(60))
+            .string_tag("db.system", "postgresql")
+            .number_tag("db.row_count", 3f64),
+    );
+    println!("options span close: {}", options_span.close());
+
+    let pass_test_headers = pass_test.inject_headers();
+    let pass_test_context = PropagationContext::extract(&pass_test_headers).unwrap();
+    assert_eq!(pass_test_context.span_id, pass_test.test_id);
+    assert_eq!(pass_test.context(), Some(pass_test_context));
+    let linked_session = TestSession::create_linked_to_context(&pass_test_context);
+    let linked_session_context = linked_session.context().unwrap();
+    assert_eq!(linked_session_context.span_id, linked_session.session_id);
+    assert_ne!(linked_session_context.trace_id, pass_test_context.trace_id);
+    linked_session.close(0);
+    assert_eq!(linked_session.context(), None);
+
+    let (pass_test_string_tags, pass_test_number_tags) = pass_test.tags();
+    assert_eq!(pass_test_string_tags["Pass-KeyFromRust"], "Hello world");
+    assert!(!pass_test_string_tags.contains_key(tags::TEST_NAME));
     println!("pass test close: {}", pass_test.close(TestStatus::Pass));
+    assert_eq!(pass_test.get_string_tag("Pass-KeyFromRust"), None);
+    assert!(pass_test.tags().0.is_empty());
+
+    // parameterized test
+    let parameterized_test = suite.create_parameterized_test(
+        "My ParameterizedTest",
+        r#"{"arguments":{"a":"1"},"metadata":{}}"#,
+    );
+    println!("parameterized test close: {}", parameterized_test.close(TestStatus::Pass));
+
+    // parameterized cases
+    for (input, expected) in [(1, 2), (2, 4)] {
+        crate::dd_case!(suite, "My CaseTest", [input, expected], {
+            assert_eq!(input * 2, expected);
+        });
+    }
 
     // fail test
     let fail_test = suite.create_test("My FailTest");
+    fail_test.set_coverage_file("file.rs");
+    assert!(CoverageBuilder::for_suite(&suite).file_with_bitmap("fixture.rs", &[0b0000_0101]).send());
+    assert!(CoverageBuilder::for_session(&session).files(&["setup.rs", "teardown.rs"]).send());
+    let mut coverage_batch = CoverageBatch::new();
+    coverage_batch
+        .add(&CoverageBuilder::for_test(&fail_test).files(&["src/lib.rs", "src/db.rs"]))
+        .add(&CoverageBuilder::for_suite(&suite).files(&["src/lib.rs", "src/db.rs"]));
+    assert_eq!(
+        coverage_batch.size(),
+        CoverageBatchSize {
+            interned_bytes: 21,
+            expanded_bytes: 42,
+        }
+    );
+    assert_eq!(coverage_batch.send(), 2);
     fail_test.set_string_tag("Fail-KeyFromRust", "Hello world");
     fail_test.set_number_tag("Fail-NumberFromRust", 42f64);
     fail_test.set_error_info("custom_error_type", "error from rust lib", "...");
+    assert!(fail_test.set_error_frames(
+        "custom_error_type",
+        "error from rust lib",
+        &[StackFrame { function: "tests::complete".to_string(), file: "src/tests.rs".to_string(), line: 180 }],
+    ));
     sleep(Duration::from_millis(1000));
+    assert!(fail_test.elapsed() >= Duration::from_millis(1000));
     println!("fail test close: {}", fail_test.close(TestStatus::Fail));
+    // nothing reaches the native library once the test is closed (not counted in the stats)
+    assert!(!fail_test.log("Hello world", None::<&str>));
+    fail_test.set_coverage_file("file.rs");
+    assert!(!CoverageBuilder::for_test(&fail_test).file("file.rs").send());
+    let mut closed_coverage_batch = CoverageBatch::new();
+    closed_coverage_batch.add(&CoverageBuilder::for_test(&fail_test).file("file.rs"));
+    assert_eq!(closed_coverage_batch.send(), 0);
+
+    // retried test
+    let retried_test = suite.create_test("My RetriedTest");
+    assert!(retried_test.mark_retry(1, TestStatus::Fail));
+    assert!(retried_test.set_retry_reason(RetryReason::AutoTestRetry));
+    println!("retried test close: {}", retried_test.close(TestStatus::Pass));
+
+    // test built with its source, tags and parameters, overriding an inherited tag
+    let built_suite = module.create_test_suite("My BuiltSuite");
+    assert!(built_suite.set_inherited_tag("test.suite.category", "integration"));
+    let built_test = built_suite
+        .test_builder("My BuiltTest")
+        .source("src/tests.rs", 10..=20)
+        .tag("test.suite.category", "unit")
+        .tag("test.flag", true)
+        .number_tag("test.weight", 3.0)
+        .parameters(r#"{"arguments":{"a":"1"},"metadata":{}}"#)
+        .start();
+    assert_eq!(built_test.get_string_tag("test.suite.category").as_deref(), Some("unit"));
+    assert_eq!(built_test.get_number_tag("test.weight"), Some(3.0));
+    println!("built test close: {}", built_test.close(TestStatus::Pass));
+    println!("built suite close: {}", built_suite.close());
+
+    // tests inheriting the tags of their suite
+    let inheriting_suite = module.create_test_suite("My InheritingSuite");
+    assert!(inheriting_suite.set_inherited_tag("test.suite.category", "integration"));
+    assert!(inheriting_suite.set_inherited_tag("test.suite.weight", 3));
+    let inheriting_test = inheriting_suite.create_test("My InheritingTest");
+    let inheriting_batch = inheriting_suite.create_tests(&["My InheritingBatchTest"]);
+    println!("inheriting test close: {}", inheriting_test.close(TestStatus::Pass));
+    println!("inheriting batch test close: {}", inheriting_batch[0].close(TestStatus::Pass));
+    println!("inheriting suite close: {}", inheriting_suite.close());
+    assert!(!inheriting_suite.set_inherited_tag("test.suite.category", "unit"));
+
+    // suite and test created from raw IDs, as received from another process
+    assert!(Test::create_under(session.session_id, module.module_id, 0, "My OrphanTest").is_none());
+    assert!(TestSuite::create_under(0, module.module_id, "My OrphanSuite").is_none());
+    let raw_suite = TestSuite::create_under(session.session_id, module.module_id, "My RawSuite").unwrap();
+    let raw_test =
+        Test::create_under(session.session_id, module.module_id, raw_suite.suite_id, "My RawTest").unwrap();
+    println!("raw test close: {}", raw_test.close(TestStatus::Pass));
+    println!("raw suite close: {}", raw_suite.close());
+
+    // test with code owners
+    let owned_test = suite.create_test("My OwnedTest");
+    assert!(owned_test.set_codeowners(&["@DataDog/ci-app-libraries", "dev@example.com"]));
+    assert!(!owned_test.set_codeowners(&["not an owner"]));
+    println!("owned test close: {}", owned_test.close(TestStatus::Fail));
+
+    // test with the content hashes of its source files
+    let hashed_test = suite.create_test("My HashedTest");
+    assert!(hashed_test.set_source_hash("src/lib.rs", "0123456789ABCDEF0123456789abcdef01234567"));
+    assert!(hashed_test.clone().set_source_hash("src/a.rs", &"f".repeat(64)));
+    assert!(!hashed_test.set_source_hash("src/b.rs", "not-a-sha"));
+    assert!(hashed_test.set_changed_lines("src/lib.rs", &[9, 3, 4]));
+    assert!(hashed_test.set_changed_lines("src/lib.rs", &[5, 12]));
+    assert!(!hashed_test.set_changed_lines("src/a.rs", &[0, 1]));
+    assert!(!hashed_test.set_changed_lines("src/a.rs", &[]));
+    assert!(hashed_test.add_artifact("manifest", std::path::Path::new("Cargo.toml")));
+    assert!(!hashed_test.add_artifact("missing", std::path::Path::new("does-not-exist.png")));
+    assert!(!hashed_test.add_artifact("directory", std::path::Path::new("src")));
+    println!("hashed test close: {}", hashed_test.close(TestStatus::Pass));
+    assert!(!hashed_test.set_source_hash("src/lib.rs", &"0".repeat(40)));
+    assert!(!hashed_test.set_changed_lines("src/lib.rs", &[1]));
+    assert!(!hashed_test.add_artifact("manifest", std::path::Path::new("Cargo.toml")));
+
+    // retries drawn from the session retry budget
+    let retry_budget = session.retry_budget();
+    let remaining = retry_budget.remaining();
+    match suite.create_retry("My BudgetedRetryTest", 1, TestStatus::Fail, retry_budget) {
+        Some(budgeted_test) => {
+            assert_eq!(retry_budget.remaining(), remaining - 1);
+            println!("budgeted retry close: {}", budgeted_test.close(TestStatus::Pass));
+        }
+        None => assert_eq!(remaining, 0),
+    }
+
+    // early flake detection is off in the mock settings, so no test is counted as new
+    let efd_status = session.efd_status();
+    assert!(!efd_status.enabled && !efd_status.faulty);
+    assert_eq!((efd_status.new_tests_seen, efd_status.known_tests), (0, 0));
+    let efd_test = suite.create_test("My EfdTest");
+    assert_eq!(efd_test.get_string_tag(tags::TEST_IS_NEW), None);
+    println!("efd test close: {}", efd_test.close(TestStatus::Pass));
+
+    // test attempted to be fixed, retried twice, and one dropped without a verdict
+    let mut attempts = suite.create_test("My AttemptToFixTest").begin_attempt_to_fix_with_retries(2);
+    assert_eq!(attempts.attempts(), 1);
+    assert!(attempts.next_attempt(TestStatus::Fail).is_some());
+    assert!(attempts.next_attempt(TestStatus::Pass).is_some());
+    assert!(attempts.next_attempt(TestStatus::Pass).is_none());
+    assert_eq!((attempts.attempts(), attempts.retries_left()), (3, 0));
+    let verdict = attempts.finish(TestStatus::Pass);
+    assert_eq!(verdict.statuses, [TestStatus::Fail, TestStatus::Pass, TestStatus::Pass]);
+    assert!(!verdict.passed() && !verdict.failed_all());
+    drop(suite.create_test("My DroppedAttemptToFixTest").begin_attempt_to_fix_with_retries(0));
+
+    // tests run from a closure returning a Result
+    let result_pass_test = suite.create_test("My ResultPassTest");
+    assert!(result_pass_test.run_result(|| "42".parse::<i32>().map(|value| assert_eq!(value, 42))));
+    let result_fail_test = suite.create_test("My ResultFailTest");
+    assert!(!result_fail_test.run_result(|| -> Result<(), std::num::ParseIntError> {
+        "forty-two".parse::<i32>()?;
+        Ok(())
+    }));
+
+    // tests run the way #[dd_test] runs them
+    suite.run_test("My RunPassTest", &TestParameters::new(), || assert_eq!(1 + 1, 2));
+    let run_fail_result: Result<(), String> =
+        suite.run_test("My RunFailTest", &TestParameters::new().with_argument("a", "1"), || Err("boom".to_string()));
+    assert!(run_fail_result.is_err());
+    let run_panic_result = std::panic::catch_unwind(|| {
+        suite.run_test::<()>("My RunPanicTest", &TestParameters::new(), || panic!("panicked in test"))
+    });
+    assert!(run_panic_result.is_err());
 
     // skip test
     let skip_test = suite.create_test("My SkipTest");
@@ -113,16 +427,982 @@ fn complete() {
     let skip_reason = String::from("skip because yes");
     println!("skip test close: {}", skip_test.close_with_skip_reason(skip_reason));
 
+    // itr skipped test
+    let itr_test = suite.create_test("My ItrTest");
+    println!("itr test close: {}", itr_test.skip_by_itr());
+    let estimated_itr_test = suite.create_test("My EstimatedItrTest");
+    assert!(estimated_itr_test.skip_by_itr_with_estimate(Some(Duration::from_secs(3))));
+    assert!(!estimated_itr_test.skip_by_itr());
+    assert_eq!(session.itr_skipped_count(), 2);
+    assert_eq!(session.itr_estimated_time_saved(), Duration::from_secs(3));
+
+    // batch of tests
+    let batch_tests = suite.create_tests(&["My BatchTest 1", "My BatchTest 2", "My BatchTest 3"]);
+    assert_eq!(batch_tests.len(), 3);
+    for batch_test in &batch_tests {
+        println!("batch test close: {}", batch_test.close(TestStatus::Pass));
+    }
+
+    // results reported after the fact
+    let reported_tests = suite.report_results(&[
+        ("My ReportedPassTest".to_string(), TestStatus::Pass, Some(Duration::from_secs(2))),
+        ("My ReportedFailTest".to_string(), TestStatus::Fail, None),
+    ]);
+    assert_eq!(reported_tests.len(), 2);
+    assert!(!reported_tests[0].close(TestStatus::Pass));
+
+    // guarded suite and tests
+    let guarded_suite = module.create_test_suite_guard("My GuardedSuite");
+    let guarded_test = guarded_suite.create_test_guard("My GuardedPassTest");
+    guarded_test.set_string_tag("Guarded-KeyFromRust", "Hello world");
+    println!("guarded test finish: {}", guarded_test.finish(TestStatus::Pass));
+    drop(guarded_suite.create_test_guard("My GuardedDroppedTest"));
+    println!("guarded suite finish: {}", guarded_suite.finish(TestStatus::Pass));
+
+    // test filtered out after creation
+    let discarded_test = suite.create_test("My DiscardedTest");
+    assert!(discarded_test.clone().discard());
+    assert!(!discarded_test.close(TestStatus::Pass));
+    assert!(!discarded_test.discard());
+
+    // test closed twice through a clone
+    let double_closed_test = suite.create_test("My DoubleClosedTest");
+    let double_closed_test_clone = double_closed_test.clone();
+    assert!(double_closed_test.close(TestStatus::Pass));
+    assert!(!double_closed_test_clone.close(TestStatus::Fail));
+    assert!(!double_closed_test.close_with_skip_reason("closed twice"));
+    assert!(!double_closed_test_clone.set_string_tag("Closed-KeyFromRust", "too late"));
+    assert!(!double_closed_test.set_number_tag("Closed-NumberFromRust", 1f64));
+    assert!(!double_closed_test.set_error_info("late_error", "set after close", ""));
+    assert!(!double_closed_test.set_test_source("src/tests.rs", &1, &2));
+    let late_benchmark = HashMap::from([("mean", 1.5)]);
+    assert!(!double_closed_test.set_benchmark_number_data(BenchmarkMeasureType::Duration, &late_benchmark));
+
+    // imported suite with an explicit status
+    let imported_suite = module.create_test_suite("My ImportedSuite");
+    println!("imported suite close: {}", imported_suite.close_with_status(TestStatus::Fail));
+    assert!(!imported_suite.clone().close());
+    assert!(!imported_suite.close_with_status(TestStatus::Pass));
+    assert!(!imported_suite.set_string_tag("Closed-KeyFromRust", "too late"));
+    assert!(!imported_suite.set_error_info("late_error", "set after close", ""));
+
     // close everything
     println!("suite closed: {}", suite.close());
     println!("module closed: {}", module.close());
+    assert!(!module.set_string_tag("Closed-KeyFromRust", "too late"));
+    assert!(!module.set_number_tag("Closed-NumberFromRust", 1f64));
+    // without an agent, the coverage payloads of the mock tracer may fail to be sent
+    println!("coverage flushed: {}", TestOptimization::flush_coverage());
+    session.record_summary(&SessionSummary {
+        tests_total: 16,
+        tests_passed: 12,
+        tests_failed: 2,
+        tests_skipped: 2,
+        tests_skipped_by_itr: 1,
+        tests_flaky: 0,
+    });
     session.close(0);
-    
-    // shutdown the library
-    TestOptimization::shutdown();
+    assert!(!session.set_string_tag("Closed-KeyFromRust", "too late"));
+    assert!(!session.set_tag("Closed-NumberFromRust", 1));
+
+    // everything created above has been closed
+    assert!(TestOptimization::open_span_report().is_empty());
+
+    // handles abandoned on abnormal termination are closed bottom-up, once
+    let abandoned_session = TestSession::create(Some("abandoned"), None::<&str>);
+    let abandoned_module = abandoned_session.create_module("My AbandonedModule", "", "");
+    let abandoned_suite = abandoned_module.create_test_suite("My AbandonedSuite");
+    let abandoned_test = abandoned_suite.create_test("My AbandonedTest");
+    abandoned_test.create_span("my-abandoned-operation", "", "", "");
+    assert_eq!(TestOptimization::close_all_open(TestStatus::Fail), 5);
+    assert_eq!(TestOptimization::close_all_open(TestStatus::Fail), 0);
+    assert!(TestOptimization::open_span_report().is_empty());
+    // the handles held here share the closed flag, so they no longer reach the native library
+    assert!(!abandoned_test.set_string_tag("Closed-KeyFromRust", "too late"));
+    assert!(!abandoned_test.close(TestStatus::Pass));
+    assert!(!abandoned_suite.close());
+    assert!(!abandoned_module.close());
+    assert!(!abandoned_session.set_string_tag("Closed-KeyFromRust", "too late"));
+
+    // the library isn't shut down here: it can't be initialized again in this process, which
+    // the other tests of this binary share (see tests/shutdown.rs)
+
+    let stats = TestOptimization::stats();
+    println!("reported {} spans to Datadog ({:?})", stats.spans_sent, stats);
+    assert!(stats.spans_sent > 0);
+    assert_eq!(stats.coverage_payloads_sent, if cfg!(feature = "lcov") { 7 } else { 6 });
 
     let spans = MockTracer::get_finished_spans();
-    for span in spans {
+    for span in &spans {
         println!("span: {:?}", span);
     }
+    let test_span = |name: &str| {
+        spans
+            .iter()
+            .find(|span| span.string_tags.get(tags::TEST_NAME).map(String::as_str) == Some(name))
+            .unwrap_or_else(|| panic!("no span for test {:?}", name))
+    };
+
+    let session_span = spans
+        .iter()
+        .find(|span| span.span_id == 1)
+        .expect("no span for the session");
+    assert_eq!(MockTracer::span_id_of(session.session_id), 1);
+    assert_eq!(session_span.string_tags[tags::TEST_FRAMEWORK], "cargo-test");
+    assert_eq!(session_span.string_tags[tags::TEST_FRAMEWORK_VERSION], "1.80");
+    assert_eq!(session_span.string_tags[tags::GIT_BRANCH], "my-branch");
+    assert_eq!(session_span.string_tags[tags::CI_PROVIDER_NAME], "woodpecker");
+    assert_eq!(session_span.string_tags[tags::CI_JOB_NAME], "test");
+    assert_eq!(session_span.number_tags[tags::SESSION_TESTS_TOTAL], 16f64);
+    assert_eq!(session_span.number_tags[tags::SESSION_TESTS_SKIPPED_BY_ITR], 1f64);
+    assert_eq!(
+        session_span.string_tags[tags::GIT_COMMIT_SHA],
+        "0123456789abcdef0123456789abcdef01234567"
+    );
+
+    assert_eq!(test_span("My RetriedTest").string_tags[tags::TEST_RETRY_REASON], "auto_test_retry");
+    let built_span = test_span("My BuiltTest");
+    assert_eq!(built_span.string_tags["test.suite.category"], "unit");
+    assert_eq!(built_span.string_tags["test.flag"], "true");
+    assert_eq!(built_span.number_tags["test.weight"], 3.0);
+    assert!(built_span.string_tags[tags::TEST_PARAMETERS].contains(r#""a":"1""#));
+    assert!(built_span.string_tags["test.source.file"].ends_with("tests.rs"));
+    assert_eq!(built_span.number_tags["test.source.start"], 10.0);
+    assert_eq!(built_span.number_tags["test.source.end"], 20.0);
+    let attempt_spans: Vec<_> = spans
+        .iter()
+        .filter(|span| span.is_test() && span.string_tags[tags::TEST_NAME] == "My AttemptToFixTest")
+        .collect();
+    assert_eq!(attempt_spans.len(), 3);
+    for span in &attempt_spans {
+        assert_eq!(span.string_tags[tags::TEST_MANAGEMENT_IS_ATTEMPT_TO_FIX], "true");
+        assert_eq!(span.string_tags[tags::TEST_SUITE], "My Suite");
+        assert!(attempt_spans.iter().all(|other| other.span_id != span.parent_span_id));
+    }
+    let attempt_retries: Vec<_> = attempt_spans
+        .iter()
+        .filter(|span| span.string_tags.contains_key(tags::TEST_RETRY_REASON))
+        .collect();
+    assert_eq!(attempt_retries.len(), 2);
+    for span in &attempt_retries {
+        assert_eq!(span.string_tags[tags::TEST_RETRY_REASON], "attempt_to_fix");
+        assert_eq!(span.string_tags[tags::TEST_RETRY_ORIGINAL_STATUS], "fail");
+    }
+    let attempt_verdicts: Vec<_> = attempt_spans
+        .iter()
+        .filter_map(|span| span.string_tags.get(tags::TEST_MANAGEMENT_ATTEMPT_TO_FIX_PASSED))
+        .collect();
+    assert_eq!(attempt_verdicts, ["false"]);
+    assert!(!attempt_spans.iter().any(|span| span.string_tags.contains_key(tags::TEST_HAS_FAILED_ALL_RETRIES)));
+    let dropped_attempt_span = test_span("My DroppedAttemptToFixTest");
+    assert_eq!(dropped_attempt_span.test_status(), Some(TestStatus::Fail));
+    assert_eq!(dropped_attempt_span.string_tags[tags::TEST_HAS_FAILED_ALL_RETRIES], "true");
+    assert_eq!(dropped_attempt_span.string_tags[tags::TEST_MANAGEMENT_ATTEMPT_TO_FIX_PASSED], "false");
+
+    let closed_tests = closed_tests.lock().unwrap();
+    let closed_pass_test = closed_tests.iter().find(|info| info.name == "My PassTest").unwrap();
+    assert_eq!(closed_pass_test.status, TestStatus::Pass);
+    assert_eq!(closed_pass_test.suite_name.as_deref(), Some("My Suite"));
+    assert!(closed_pass_test.duration >= Duration::from_millis(1500));
+    assert_eq!(closed_pass_test.string_tags["Pass-BoolFromRust"], "true");
+    let closed_itr_test = closed_tests.iter().find(|info| info.name == "My ItrTest").unwrap();
+    assert_eq!(closed_itr_test.status, TestStatus::Skip);
+    assert_eq!(closed_itr_test.skip_reason.as_deref(), Some(tags::SKIPPED_BY_ITR_REASON));
+    assert!(!closed_tests.iter().any(|info| info.name == "My DiscardedTest"));
+    assert_eq!(closed_tests.iter().filter(|info| info.name == "My DoubleClosedTest").count(), 1);
+
+    let junit_xml = MockTracer::to_junit_xml();
+    assert!(junit_xml.contains(r#"<testcase name="My PassTest" classname="My Suite""#));
+
+    let pass_span = test_span("My PassTest");
+    assert_eq!(pass_span.string_tags["Pass-BoolFromRust"], "true");
+    assert_eq!(pass_span.number_tags["Pass-IntFromRust"], 7f64);
+    for (key, value) in &pass_test_string_tags {
+        assert_eq!(pass_span.string_tags.get(key), Some(value), "{}", key);
+    }
+    for (key, value) in &pass_test_number_tags {
+        assert_eq!(pass_span.number_tags.get(key), Some(value), "{}", key);
+    }
+    assert_eq!(pass_span.span_id, MockTracer::span_id_of(pass_test_context.span_id));
+    assert_eq!(pass_span.trace_id, MockTracer::span_id_of(pass_test_context.trace_id as u64));
+
+    let mut case_parameters: Vec<&String> = spans
+        .iter()
+        .filter(|span| span.string_tags.get(tags::TEST_NAME).map(String::as_str) == Some("My CaseTest"))
+        .map(|span| &span.string_tags[tags::TEST_PARAMETERS])
+        .collect();
+    case_parameters.sort();
+    assert_eq!(
+        case_parameters,
+        [
+            r#"{"arguments":{"expected":"2","input":"1"},"metadata":{}}"#,
+            r#"{"arguments":{"expected":"4","input":"2"},"metadata":{}}"#,
+        ]
+    );
+
+    let itr_span = test_span("My ItrTest");
+    assert_eq!(itr_span.string_tags[tags::TEST_SKIPPED_BY_ITR], "true");
+    assert_eq!(itr_span.string_tags[tags::TEST_SKIP_REASON], tags::SKIPPED_BY_ITR_REASON);
+
+    assert_eq!(test_span("My BatchTest 2").string_tags[tags::TEST_STATUS], "pass");
+    let reported_pass_span = test_span("My ReportedPassTest");
+    let reported_fail_span = test_span("My ReportedFailTest");
+    assert_ne!(reported_pass_span.span_id, reported_fail_span.span_id);
+    assert_eq!(reported_pass_span.test_status(), Some(TestStatus::Pass));
+    assert_eq!(reported_fail_span.test_status(), Some(TestStatus::Fail));
+    let reported_duration = reported_pass_span.finish_time.duration_since(reported_pass_span.start_time).unwrap();
+    assert!(reported_duration >= Duration::from_millis(1990), "{:?}", reported_duration);
+    assert_eq!(test_span("My GuardedPassTest").string_tags[tags::TEST_STATUS], "pass");
+    assert_eq!(test_span("My GuardedDroppedTest").string_tags[tags::TEST_STATUS], "fail");
+    assert_eq!(test_span("My FailTest").test_status(), Some(TestStatus::Fail));
+    let retried_span = spans
+        .iter()
+        .find(|span| span.span_id == MockTracer::span_id_of(retried_test.test_id))
+        .expect("no span for the retried test");
+    assert_eq!(retried_span.string_tags[tags::TEST_IS_RETRY], "true");
+    assert_eq!(retried_span.number_tags[tags::TEST_RETRY_ATTEMPT], 1f64);
+    assert_eq!(retried_span.string_tags[tags::TEST_RETRY_ORIGINAL_STATUS], "fail");
+    assert_eq!(retried_span.test_status(), Some(TestStatus::Pass));
+    assert_eq!(
+        test_span("My FailTest").string_tags[tags::ERROR_STACK],
+        "   0: tests::complete\n             at src/tests.rs:180\n"
+    );
+    assert!(test_span("My SkipTest").is_test());
+    assert_eq!(test_span("My ResultPassTest").test_status(), Some(TestStatus::Pass));
+    assert_eq!(test_span("My RunPassTest").test_status(), Some(TestStatus::Pass));
+    let run_fail_span = test_span("My RunFailTest");
+    assert_eq!(run_fail_span.test_status(), Some(TestStatus::Fail));
+    assert_eq!(run_fail_span.string_tags[tags::ERROR_MESSAGE], "\"boom\"");
+    assert_eq!(run_fail_span.string_tags[tags::TEST_PARAMETERS], r#"{"arguments":{"a":"1"},"metadata":{}}"#);
+    let run_panic_span = test_span("My RunPanicTest");
+    assert_eq!(run_panic_span.test_status(), Some(TestStatus::Fail));
+    assert_eq!(run_panic_span.string_tags[tags::ERROR_MESSAGE], "panicked in test");
+    let result_fail_span = test_span("My ResultFailTest");
+    assert_eq!(result_fail_span.test_status(), Some(TestStatus::Fail));
+    assert_eq!(result_fail_span.string_tags[tags::ERROR_TYPE], "core::num::error::ParseIntError");
+    assert_eq!(result_fail_span.string_tags[tags::ERROR_MESSAGE], "invalid digit found in string");
+    assert_eq!(test_span("My DoubleClosedTest").string_tags[tags::TEST_STATUS], "pass");
+
+    assert!(!spans
+        .iter()
+        .any(|span| span.string_tags.get(tags::TEST_NAME).map(String::as_str) == Some("My DiscardedTest")));
+
+    let imported_suite_span = spans
+        .iter()
+        .find(|span| span.span_id == MockTracer::span_id_of(imported_suite.suite_id))
+        .expect("no span for the imported suite");
+    assert_eq!(imported_suite_span.string_tags[tags::TEST_STATUS], "fail");
+    assert!(!imported_suite_span.string_tags.contains_key(tags::ERROR_TYPE));
+    assert!(!imported_suite_span.is_test());
+
+    let parameterized_span = test_span("My ParameterizedTest");
+    assert_eq!(
+        parameterized_span.string_tags[tags::TEST_PARAMETERS],
+        r#"{"arguments":{"a":"1"},"metadata":{}}"#
+    );
+
+    // assert on the recorded spans
+    MockTracer::assert_span("my-operation-name")
+        .with_tag("Session-KeyFromRust", "Hello world")
+        .with_number_tag("Session-NumberFromRust", 42f64)
+        .with_tag(tags::RESOURCE_NAME, "SELECT 1")
+        .finished();
+
+    MockTracer::assert_span("my-options-operation")
+        .with_tag("db.system", "postgresql")
+        .with_number_tag("db.row_count", 3f64)
+        .finished();
+    assert_eq!(
+        test_span("My OwnedTest").string_tags[tags::TEST_CODEOWNERS],
+        r#"["@DataDog/ci-app-libraries","dev@example.com"]"#
+    );
+    let manifest_path = std::fs::canonicalize("Cargo.toml").unwrap();
+    assert_eq!(
+        test_span("My HashedTest").string_tags[tags::TEST_ARTIFACTS],
+        format!(
+            r#"[{{"name":"manifest","path":{:?},"size":{}}}]"#,
+            manifest_path.to_string_lossy(),
+            std::fs::metadata(&manifest_path).unwrap().len()
+        )
+    );
+    assert_eq!(
+        test_span("My HashedTest").string_tags[tags::TEST_CHANGED_LINES],
+        r#"{"src/lib.rs":"3-5,9,12"}"#
+    );
+    assert_eq!(
+        test_span("My HashedTest").string_tags[tags::TEST_SOURCE_HASHES],
+        format!(
+            r#"{{"src/a.rs":"{}","src/lib.rs":"0123456789abcdef0123456789abcdef01234567"}}"#,
+            "f".repeat(64)
+        )
+    );
+    assert_eq!(test_span("My RawTest").string_tags[tags::TEST_SUITE], "My RawSuite");
+    for inheriting_test_name in ["My InheritingTest", "My InheritingBatchTest"] {
+        let inheriting_span = test_span(inheriting_test_name);
+        assert_eq!(inheriting_span.string_tags["test.suite.category"], "integration");
+        assert_eq!(inheriting_span.number_tags["test.suite.weight"], 3f64);
+    }
+    let options_span = MockTracer::find_finished_span("my-options-operation").unwrap();
+    assert_eq!(options_span.string_tags[tags::SERVICE_NAME], "my-options-service");
+    let options_span_duration = options_span.finish_time.duration_since(options_span.start_time);
+    assert!(options_span_duration.unwrap() >= Duration::from_secs(60));
+
+    MockTracer::assert_span("my-abandoned-operation").finished();
+    assert_eq!(test_span("My AbandonedTest").string_tags[tags::TEST_STATUS], "fail");
+
+    MockTracer::assert_span("my-panicked-operation")
+        .with_tag(tags::ERROR_TYPE, tags::PANIC_ERROR_TYPE)
+        .with_tag(tags::ERROR_MESSAGE, "span work failed")
+        .finished();
+
+    // deterministic IDs keep the parent/child relationships of the native IDs
+    let custom_span = MockTracer::find_finished_span("my-test-operation-name").unwrap();
+    assert_eq!(custom_span.span_id, MockTracer::span_id_of(custom_span_id));
+    assert_eq!(custom_span.parent_span_id, pass_span.span_id);
+    let suite_custom_span = spans
+        .iter()
+        .find(|span| span.span_id == MockTracer::span_id_of(suite_span_id))
+        .expect("no span for the suite custom span");
+    assert_eq!(suite_custom_span.parent_span_id, MockTracer::span_id_of(suite.suite_id));
+    assert_eq!(suite_custom_span.string_tags[tags::SERVICE_NAME], "rust-sdk-tests");
+    assert_eq!(custom_span.string_tags[tags::SERVICE_NAME], "my-service");
+}
+
+#[test]
+fn session_outcome_tracks_worst_status() {
+    assert_eq!(TestStatus::Pass.exit_code(), 0);
+    assert_eq!(TestStatus::Fail.exit_code(), 1);
+    assert_eq!(TestStatus::Skip.exit_code(), 0);
+
+    let outcome = SessionOutcome::new();
+    assert_eq!(outcome.status(), None);
+    assert_eq!(outcome.exit_code(), 0);
+
+    outcome.record(TestStatus::Skip);
+    assert_eq!(outcome.status(), Some(TestStatus::Skip));
+    outcome.record(TestStatus::Pass);
+    assert_eq!(outcome.status(), Some(TestStatus::Pass));
+    assert_eq!(outcome.exit_code(), 0);
+
+    outcome.record(TestStatus::Fail);
+    outcome.record(TestStatus::Pass);
+    assert_eq!(outcome.status(), Some(TestStatus::Fail));
+    assert_eq!(outcome.exit_code(), 1);
+}
+
+#[test]
+fn retry_budget_is_shared_across_threads() {
+    let budget = RetryBudget::new(5);
+    assert!(budget.try_consume(2));
+    assert!(!budget.try_consume(4));
+    assert_eq!(budget.remaining(), 3);
+
+    let consumed = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8).map(|_| scope.spawn(|| budget.try_consume(1))).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).filter(|consumed| *consumed).count()
+    });
+    assert_eq!(consumed, 3);
+    assert_eq!(budget.remaining(), 0);
+    assert!(!budget.try_consume(1));
+    assert!(budget.try_consume(0));
+}
+
+#[test]
+fn codeowners_are_validated_and_formatted_as_json() {
+    assert_eq!(
+        Test::format_codeowners(&["@user", "@org/team", "dev@example.com"]),
+        Some(r#"["@user","@org/team","dev@example.com"]"#.to_string())
+    );
+    assert_eq!(Test::format_codeowners(&[]), None);
+    assert_eq!(Test::format_codeowners(&["@"]), None);
+    assert_eq!(Test::format_codeowners(&["team"]), None);
+    assert_eq!(Test::format_codeowners(&["@org/my team"]), None);
+    assert_eq!(Test::format_codeowners(&["@org/\"team\""]), None);
+    assert_eq!(Test::format_codeowners(&["dev@localhost"]), None);
+}
+
+#[test]
+fn init_rejects_invalid_working_directory() {
+    assert_eq!(
+        TestOptimization::try_init_with_values("rust", "rustc", "1.0", Some("/tmp/a\0b"), true),
+        Err(InitError::InvalidWorkingDirectory("/tmp/a\0b".into()))
+    );
+    assert!(!TestOptimization::init_with_values("rust", "rustc", "1.0", Some("/tmp/a\0b"), true));
+}
+
+#[test]
+fn agentless_builder_validates_and_redacts_the_api_key() {
+    let builder = TestSessionBuilder::new().agentless("secret-api-key", "datadoghq.eu");
+    let debug = format!("{:?}", builder);
+    assert!(!debug.contains("secret-api-key"));
+    assert!(debug.contains("(\"DD_API_KEY\", \"<redacted>\")"));
+    assert!(debug.contains("(\"DD_SITE\", \"datadoghq.eu\")"));
+    assert!(debug.contains("(\"DD_CIVISIBILITY_AGENTLESS_ENABLED\", \"true\")"));
+
+    let error = TestSessionBuilder::new().agentless("", "datadoghq.com").start().unwrap_err();
+    assert_eq!(error, InitError::MissingApiKey);
+}
+
+#[test]
+fn max_flush_concurrency_sets_gomaxprocs() {
+    let builder = TestSessionBuilder::new().max_flush_concurrency(0).max_flush_concurrency(2);
+    let debug = format!("{:?}", builder);
+    assert!(debug.contains("(\"GOMAXPROCS\", \"2\")"));
+    assert_eq!(debug.matches("GOMAXPROCS").count(), 1);
+}
+
+#[test]
+fn naming_scheme_overrides_only_the_set_names() {
+    let kind_names = NamingScheme::kind_names();
+    assert_eq!(kind_names.operation_name(HandleKind::Session), Some("test_session"));
+    assert_eq!(kind_names.operation_name(HandleKind::Module), Some("test_module"));
+    assert_eq!(kind_names.operation_name(HandleKind::Suite), Some("test_suite"));
+    assert_eq!(kind_names.operation_name(HandleKind::Test), Some("test"));
+    assert_eq!(kind_names.operation_name(HandleKind::Span), None);
+
+    let custom = NamingScheme { test: Some("rust.test".to_string()), ..NamingScheme::default() };
+    assert_eq!(custom.operation_name(HandleKind::Test), Some("rust.test"));
+    assert_eq!(custom.operation_name(HandleKind::Session), None);
+}
+
+#[test]
+fn test_management_flag_filters_mixed_flags() {
+    let test = |name: &str, quarantined: bool, disabled: bool, attempt_to_fix: bool| TestManagementTest {
+        module_name: "module".to_string(),
+        suite_name: "suite".to_string(),
+        test_name: name.to_string(),
+        quarantined,
+        disabled,
+        attempt_to_fix,
+    };
+    let tests = [
+        test("quarantined", true, false, false),
+        test("disabled", false, true, false),
+        test("quarantined_fix", true, false, true),
+        test("none", false, false, false),
+    ];
+    let names = |flag: TestManagementFlag| {
+        tests
+            .iter()
+            .filter(|test| flag.matches(test))
+            .map(|test| test.test_name.as_str())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(TestManagementFlag::Quarantined), ["quarantined", "quarantined_fix"]);
+    assert_eq!(names(TestManagementFlag::Disabled), ["disabled"]);
+    assert_eq!(names(TestManagementFlag::AttemptToFix), ["quarantined_fix"]);
+}
+
+#[test]
+fn cstr_pair_borrows_strings() {
+    const ENVIRONMENT: [CStrPair; 1] = [CStrPair::new(c"DD_ENV", c"ci")];
+    assert_eq!(ENVIRONMENT[0].key(), c"DD_ENV");
+    assert_eq!(ENVIRONMENT[0].value(), c"ci");
+}
+
+#[test]
+fn parameters_json_escapes_arguments() {
+    let name = "a \"quoted\" name";
+    assert_eq!(
+        parameters_json(&[("name", &name), ("count", &3)]),
+        r#"{"arguments":{"count":"3","name":"\"a \\\"quoted\\\" name\""},"metadata":{}}"#
+    );
+    assert_eq!(parameters_json(&[]), r#"{"arguments":{},"metadata":{}}"#);
+}
+
+#[test]
+fn ci_provider_detects_fixtures() {
+    let fixture = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    };
+
+    let buildkite = CiProvider::detect_tags_with(
+        CI_PROVIDERS,
+        fixture(&[
+            ("BUILDKITE", "true"),
+            ("BUILDKITE_BUILD_ID", "0186e0f4-8d2b-4a36-9e7f-3b1f0c2e5a11"),
+            ("BUILDKITE_PIPELINE_SLUG", "test-optimization-native"),
+            ("BUILDKITE_BUILD_NUMBER", "42"),
+            ("BUILDKITE_BRANCH", "main"),
+            ("BUILDKITE_COMMIT", "0123456789abcdef0123456789abcdef01234567"),
+            ("BUILDKITE_LABEL", ""),
+        ]),
+    );
+    assert_eq!(buildkite[tags::CI_PROVIDER_NAME], "buildkite");
+    assert_eq!(buildkite[tags::CI_PIPELINE_NAME], "test-optimization-native");
+    assert_eq!(buildkite[tags::CI_PIPELINE_NUMBER], "42");
+    assert_eq!(buildkite[tags::GIT_BRANCH], "main");
+    assert!(!buildkite.contains_key(tags::CI_JOB_NAME));
+    assert_eq!(buildkite.len(), 6);
+
+    let woodpecker = CiProvider::detect_tags_with(
+        CI_PROVIDERS,
+        fixture(&[
+            ("CI", "woodpecker"),
+            ("CI_REPO", "DataDog/test-optimization-native"),
+            ("CI_PIPELINE_NUMBER", "7"),
+            ("CI_STEP_NAME", "test"),
+            ("CI_COMMIT_SHA", "0123456789abcdef0123456789abcdef01234567"),
+        ]),
+    );
+    assert_eq!(woodpecker[tags::CI_PROVIDER_NAME], "woodpecker");
+    assert_eq!(woodpecker[tags::CI_JOB_NAME], "test");
+    assert_eq!(woodpecker.len(), 5);
+
+    assert!(CiProvider::detect_tags_with(CI_PROVIDERS, fixture(&[("CI", "true")])).is_empty());
+}
+
+#[test]
+fn propagation_context_extracts_supported_headers() {
+    let headers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    let w3c = PropagationContext::extract(&headers(&[(
+        "Traceparent",
+        "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+    )]))
+    .unwrap();
+    assert_eq!(w3c.trace_id, 0x0af7651916cd43dd8448eb211c80319c);
+    assert_eq!(w3c.span_id, 0xb7ad6b7169203331);
+    assert_eq!(w3c.sampling_priority, Some(1));
+
+    let datadog = PropagationContext::extract(&headers(&[
+        ("x-datadog-trace-id", "1234"),
+        ("x-datadog-parent-id", "5678"),
+        ("x-datadog-sampling-priority", "2"),
+        ("x-datadog-tags", "_dd.p.dm=-1,_dd.p.tid=640cfd8d00000000"),
+    ]))
+    .unwrap();
+    assert_eq!(datadog.trace_id, (0x640cfd8d00000000u128 << 64) | 1234);
+    assert_eq!(datadog.span_id, 5678);
+    assert_eq!(datadog.sampling_priority, Some(2));
+
+    assert!(PropagationContext::extract(&headers(&[(
+        "traceparent",
+        "00-00000000000000000000000000000000-b7ad6b7169203331-01"
+    )]))
+    .is_none());
+    assert!(PropagationContext::extract(&headers(&[("x-datadog-trace-id", "1234")])).is_none());
+}
+
+#[test]
+fn propagation_context_round_trips_headers() {
+    for context in [
+        PropagationContext {
+            trace_id: (0x640cfd8d00000000u128 << 64) | 0x1234,
+            span_id: 0x5678,
+            sampling_priority: Some(2),
+        },
+        PropagationContext {
+            trace_id: 42,
+            span_id: 7,
+            sampling_priority: Some(0),
+        },
+    ] {
+        let headers = context.to_headers();
+        assert_eq!(PropagationContext::extract(&headers), Some(context));
+
+        let mut datadog_only = headers.clone();
+        datadog_only.remove(TRACEPARENT_HEADER);
+        assert_eq!(PropagationContext::extract(&datadog_only), Some(context));
+    }
+}
+
+#[test]
+fn time_conversions_round_trip_edge_timestamps() {
+    use crate::time::{system_time_to_unix, topt_UnixTime, unix_to_system_time};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    for time in [
+        UNIX_EPOCH,
+        UNIX_EPOCH + Duration::new(1, 999_999_999),
+        UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789),
+        SystemTime::now(),
+    ] {
+        assert_eq!(unix_to_system_time(system_time_to_unix(time)), time);
+    }
+
+    let pre_epoch = system_time_to_unix(UNIX_EPOCH - Duration::from_secs(10));
+    assert_eq!((pre_epoch.sec, pre_epoch.nsec), (0, 0));
+
+    let carried = unix_to_system_time(topt_UnixTime { sec: 1, nsec: 1_500_000_000 });
+    assert_eq!(carried, UNIX_EPOCH + Duration::from_millis(2500));
+
+    let saturated = unix_to_system_time(topt_UnixTime { sec: u64::MAX, nsec: u64::MAX });
+    assert!(saturated > UNIX_EPOCH + Duration::from_secs(1 << 40));
+    assert!(saturated.checked_add(Duration::from_secs(1)).is_none());
+}
+
+#[test]
+fn finish_time_is_clamped_on_clock_skew() {
+    use crate::time::{clamp_finish_time, topt_UnixTime};
+
+    let start = topt_UnixTime { sec: 100, nsec: 500 };
+    let later = topt_UnixTime { sec: 101, nsec: 0 };
+    let earlier = topt_UnixTime { sec: 99, nsec: 250 };
+
+    let (finish, skew) = clamp_finish_time(start, later);
+    assert_eq!((finish.sec, finish.nsec, skew), (101, 0, None));
+    let (finish, skew) = clamp_finish_time(start, start);
+    assert_eq!((finish.sec, finish.nsec, skew), (100, 500, None));
+    let (finish, skew) = clamp_finish_time(start, earlier);
+    assert_eq!((finish.sec, finish.nsec), (100, 500));
+    assert_eq!(skew, Some(Duration::from_nanos(1_000_000_250)));
+}
+
+#[test]
+fn efd_slow_test_retries_buckets() {
+    let settings = EfdSlowTestRetriesSettings {
+        five_m: 2,
+        thirty_s: 3,
+        ten_s: 5,
+        five_s: 10,
+    };
+    assert_eq!(
+        settings.as_buckets(),
+        [
+            (Duration::from_secs(5), 10),
+            (Duration::from_secs(10), 5),
+            (Duration::from_secs(30), 3),
+            (Duration::from_secs(300), 2),
+        ]
+    );
+    assert_eq!(settings.retries_for(Duration::from_millis(4999)), 10);
+    assert_eq!(settings.retries_for(Duration::from_secs(5)), 5);
+    assert_eq!(settings.retries_for(Duration::from_secs(299)), 2);
+    assert_eq!(settings.retries_for(Duration::from_secs(300)), 0);
+}
+
+#[test]
+fn benchmark_measure_type_names() {
+    assert_eq!(BenchmarkMeasureType::Duration.as_str(), "duration");
+    assert_eq!(BenchmarkMeasureType::MeanHeapAllocations.as_str(), "mean_heap_allocations");
+    let custom: BenchmarkMeasureType = "my_custom_measurement".into();
+    assert_eq!(custom, BenchmarkMeasureType::Custom("my_custom_measurement".to_string()));
+    assert_eq!(BenchmarkMeasureType::from("duration"), BenchmarkMeasureType::Duration);
+    assert_eq!(
+        BenchmarkMeasureType::from("mean_heap_allocations".to_string()),
+        BenchmarkMeasureType::MeanHeapAllocations
+    );
+    assert_eq!(custom.as_str(), "my_custom_measurement");
+}
+
+#[test]
+fn junit_xml_matches_golden_file() {
+    use std::time::UNIX_EPOCH;
+
+    let span = |name: Option<&str>, suite: &str, start_ms: u64, duration_ms: u64, tags: &[(&str, &str)]| {
+        let start_time = UNIX_EPOCH + Duration::from_millis(start_ms);
+        let mut string_tags: HashMap<String, String> = tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        string_tags.insert(tags::TEST_SUITE.to_string(), suite.to_string());
+        if let Some(name) = name {
+            string_tags.insert(tags::TEST_NAME.to_string(), name.to_string());
+        }
+        MockSpan {
+            span_id: start_ms,
+            trace_id: start_ms,
+            parent_span_id: 0,
+            start_time,
+            finish_time: start_time + Duration::from_millis(duration_ms),
+            operation_name: "cargo.test".to_string(),
+            string_tags,
+            number_tags: HashMap::new(),
+        }
+    };
+    let spans = [
+        span(None, "parser", 0, 5000, &[]),
+        span(Some("parses <empty> input"), "parser", 20, 1500, &[(tags::TEST_STATUS, "pass")]),
+        span(
+            Some("rejects \"bad\" input"),
+            "parser",
+            10,
+            250,
+            &[
+                (tags::TEST_STATUS, "fail"),
+                (tags::ERROR_TYPE, "AssertionError"),
+                (tags::ERROR_MESSAGE, "left != right & more"),
+            ],
+        ),
+        span(
+            Some("needs network"),
+            "client",
+            30,
+            0,
+            &[(tags::TEST_STATUS, "skip"), (tags::TEST_SKIP_REASON, "offline")],
+        ),
+    ];
+    assert_eq!(
+        MockTracer::spans_to_junit_xml(&spans),
+        include_str!("../testdata/junit.xml")
+    );
+}
+
+#[test]
+fn dd_tags_parsing_skips_malformed_entries() {
+    let owned = |expected: &[(&str, &str)]| -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+
+    assert_eq!(
+        tags::parse_dd_tags(" team : backend ,env:ci,url:http://host:8080"),
+        owned(&[("team", "backend"), ("env", "ci"), ("url", "http://host:8080")])
+    );
+    assert_eq!(
+        tags::parse_dd_tags(r#"owners:"alice, bob",empty:,tier:1"#),
+        owned(&[("owners", "alice, bob"), ("empty", ""), ("tier", "1")])
+    );
+    assert_eq!(
+        tags::parse_dd_tags(",,novalue,:orphan, :blank,valid:yes,"),
+        owned(&[("valid", "yes")])
+    );
+    assert_eq!(tags::parse_dd_tags(r#"ok:1,broken:"unterminated, rest:2"#), owned(&[("ok", "1")]));
+    assert!(tags::parse_dd_tags("").is_empty());
+}
+
+#[test]
+fn mock_span_reports_test_status() {
+    let span = |tags: &[(&str, &str)]| MockSpan {
+        span_id: 1,
+        trace_id: 1,
+        parent_span_id: 0,
+        start_time: SystemTime::UNIX_EPOCH,
+        finish_time: SystemTime::UNIX_EPOCH,
+        operation_name: "test".to_string(),
+        string_tags: tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        number_tags: HashMap::new(),
+    };
+
+    let failed_test = span(&[(tags::TEST_NAME, "my test"), (tags::TEST_STATUS, "fail")]);
+    assert!(failed_test.is_test());
+    assert_eq!(failed_test.test_status(), Some(TestStatus::Fail));
+
+    let suite = span(&[(tags::TEST_SUITE, "my suite"), (tags::TEST_STATUS, "skip")]);
+    assert!(!suite.is_test());
+    assert_eq!(suite.test_status(), Some(TestStatus::Skip));
+
+    assert_eq!(span(&[(tags::TEST_STATUS, "unknown")]).test_status(), None);
+    assert_eq!(span(&[]).test_status(), None);
+    for status in [TestStatus::Pass, TestStatus::Fail, TestStatus::Skip] {
+        assert_eq!(TestStatus::from_tag(status.as_str()), Some(status));
+    }
+}
+
+#[test]
+fn test_parameters_match_skippable_tests() {
+    let parameters = TestParameters::new()
+        .with_argument("b", "2")
+        .with_argument("a", "1");
+    assert_eq!(parameters.to_json(), r#"{"arguments":{"a":"1","b":"2"},"metadata":{}}"#);
+    assert_eq!(TestParameters::new().to_json(), r#"{"arguments":{},"metadata":{}}"#);
+
+    let skippable = |name: &str, parameters: &str| SkippableTest {
+        suite_name: "suite".to_string(),
+        test_name: name.to_string(),
+        parameters: parameters.to_string(),
+        custom_configurations_json: String::new(),
+    };
+    assert!(skippable("test", &parameters.to_json()).matches("test", &parameters));
+    assert!(!skippable("other", &parameters.to_json()).matches("test", &parameters));
+    assert!(skippable("test", "").matches("test", &TestParameters::new()));
+    assert!(!skippable("test", "").matches("test", &parameters));
+
+    let reordered = skippable("test", r#"{ "metadata": {}, "arguments": { "b": "2", "a": 1 } }"#);
+    #[cfg(feature = "serde")]
+    {
+        assert!(reordered.matches("test", &parameters));
+        assert_eq!(
+            TestParameters::from_json(r#"{"arguments":{"a":"1","b":"2"}}"#),
+            Some(parameters.clone())
+        );
+        assert_eq!(TestParameters::from_json(r#"{"arguments":[]}"#), None);
+        assert_eq!(TestParameters::from_json("not json"), None);
+    }
+    #[cfg(not(feature = "serde"))]
+    assert!(!reordered.matches("test", &parameters));
+}
+
+#[test]
+fn stack_frames_format_like_rust_backtraces() {
+    let frames = [
+        StackFrame {
+            function: "my_crate::parser::parse".to_string(),
+            file: "src/parser.rs".to_string(),
+            line: 42,
+        },
+        StackFrame {
+            function: "my_crate::tests::parses".to_string(),
+            file: "src/tests.rs".to_string(),
+            line: 0,
+        },
+        StackFrame {
+            function: "core::ops::function::FnOnce::call_once".to_string(),
+            file: String::new(),
+            line: 0,
+        },
+    ];
+    assert_eq!(
+        StackFrame::format_stacktrace(&frames),
+        "   0: my_crate::parser::parse\n             at src/parser.rs:42\n   \
+         1: my_crate::tests::parses\n             at src/tests.rs\n   \
+         2: core::ops::function::FnOnce::call_once\n"
+    );
+    assert_eq!(StackFrame::format_stacktrace(&[]), "");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn settings_serialize_to_a_stable_json_shape() {
+    let settings = Settings {
+        code_coverage: true,
+        early_flake_detection: EfDSettings {
+            enabled: true,
+            slow_test_retries: EfdSlowTestRetriesSettings { five_m: 1, thirty_s: 2, ten_s: 3, five_s: 4 },
+            faulty_session_threshold: 30,
+        },
+        flaky_test_retries_enabled: false,
+        itr_enabled: true,
+        require_git: false,
+        tests_skipping: true,
+        known_tests_enabled: true,
+        test_management: TestManagementSettings { enabled: true, attempt_to_fix_retries: 20 },
+    };
+    assert_eq!(
+        serde_json::to_string(&settings).unwrap(),
+        concat!(
+            r#"{"code_coverage":true,"early_flake_detection":{"enabled":true,"#,
+            r#""slow_test_retries":{"five_m":1,"thirty_s":2,"ten_s":3,"five_s":4},"#,
+            r#""faulty_session_threshold":30},"flaky_test_retries_enabled":false,"itr_enabled":true,"#,
+            r#""require_git":false,"tests_skipping":true,"known_tests_enabled":true,"#,
+            r#""test_management":{"enabled":true,"attempt_to_fix_retries":20}}"#
+        )
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn trace_context_round_trips_through_json() {
+    let context = TraceContext {
+        trace_id: (1u128 << 64) | 42,
+        span_id: 7,
+        sampling_priority: Some(2),
+    };
+    let json = serde_json::to_string(&context).unwrap();
+    assert_eq!(
+        json,
+        r#"{"trace_id":"0000000000000001000000000000002a","span_id":7,"sampling_priority":2}"#
+    );
+    assert_eq!(serde_json::from_str::<TraceContext>(&json).unwrap(), context);
+    for trace_id in [r#"18446744073709551658"#, r#""2a""#, r#""+000000000000001000000000000002a""#] {
+        let json = format!(r#"{{"trace_id":{},"span_id":7,"sampling_priority":2}}"#, trace_id);
+        assert!(serde_json::from_str::<TraceContext>(&json).is_err(), "{}", json);
+    }
+}
+
+#[test]
+fn hierarchy_displays_as_an_indented_tree() {
+    let node = |handle_id, kind, name: &str, children| HierarchyNode {
+        handle_id,
+        kind,
+        name: name.to_string(),
+        children,
+    };
+    let test = node(4, HandleKind::Test, "my_test", vec![node(5, HandleKind::Span, "", vec![])]);
+    let suite = node(3, HandleKind::Suite, "my suite", vec![test]);
+    let session = node(1, HandleKind::Session, "", vec![node(2, HandleKind::Module, "my-module", vec![suite])]);
+    assert_eq!(
+        session.to_string(),
+        "Session [id=1]\n  Module \"my-module\" [id=2]\n    Suite \"my suite\" [id=3]\n      \
+         Test \"my_test\" [id=4]\n        Span [id=5]"
+    );
+}
+
+#[test]
+fn faulty_session_threshold_is_a_percentage_of_the_known_tests() {
+    let settings = EfDSettings {
+        enabled: true,
+        slow_test_retries: EfdSlowTestRetriesSettings { five_m: 0, thirty_s: 0, ten_s: 0, five_s: 0 },
+        faulty_session_threshold: 30,
+    };
+    assert!(!settings.is_faulty_session(0, 100));
+    assert!(!settings.is_faulty_session(30, 100));
+    assert!(settings.is_faulty_session(31, 100));
+    assert!(settings.is_faulty_session(1, 0));
+    assert!(!settings.is_faulty_session(0, 0));
+    let negative = EfDSettings { faulty_session_threshold: -5, ..settings };
+    assert!(negative.is_faulty_session(1, 100));
+}
+
+#[test]
+fn runtime_version_is_the_build_toolchain_version() {
+    let runtime_version = TestOptimization::runtime_version();
+    assert_eq!(runtime_version, env!("TEST_OPTIMIZATION_SDK_RUSTC_VERSION"));
+    assert!(runtime_version.split('.').all(|part| !part.is_empty()));
+}
+
+#[cfg(feature = "lcov")]
+#[test]
+fn lcov_reports_parse_into_line_bitmaps() {
+    let report = LcovReport::parse(concat!(
+        "TN:\n",
+        "SF:/repo/src/lib.rs\n",
+        "FN:1,my_crate::parse\n",
+        "DA:1,3\n",
+        "DA:2,0\n",
+        "DA:3,1\n",
+        "DA:10,7,c0ffee\n",
+        "end_of_record\n",
+        "SF:/repo/src/unused.rs\n",
+        "DA:1,0\n",
+        "end_of_record\n",
+        "SF:/repo/src/lib.rs\n",
+        "DA:2,1\n",
+        "DA:not-a-line\n",
+        "end_of_record\n",
+    ));
+    assert_eq!(report.files.keys().collect::<Vec<_>>(), ["/repo/src/lib.rs"]);
+    assert_eq!(report.bitmap("/repo/src/lib.rs"), Some(vec![0b0000_0111, 0b0000_0010]));
+    assert_eq!(report.bitmap("/repo/src/unused.rs"), None);
+    assert_eq!(LcovReport::parse(""), LcovReport::default());
+    assert!(LcovReport::read(std::path::Path::new("/nonexistent/lcov.info")).is_err());
+}
+
+#[cfg(feature = "lcov")]
+#[test]
+fn lcov_report_paths_are_made_relative_to_the_root() {
+    let report = LcovReport::parse(concat!(
+        "SF:/repo/src/lib.rs\n",
+        "DA:1,1\n",
+        "end_of_record\n",
+        "SF:src/lib.rs\n",
+        "DA:2,1\n",
+        "end_of_record\n",
+        "SF:/other/src/main.rs\n",
+        "DA:3,1\n",
+        "end_of_record\n",
+    ));
+    let relative_report = report.relative_to(std::path::Path::new("/repo"));
+    assert_eq!(
+        relative_report.files.keys().collect::<Vec<_>>(),
+        ["/other/src/main.rs", "src/lib.rs"]
+    );
+    assert_eq!(relative_report.bitmap("src/lib.rs"), Some(vec![0b0000_0011]));
+    assert_eq!(report.relative_to(std::path::Path::new("/elsewhere")), report);
 }
@@ -0,0 +1,52 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Integration test of the `#[dd_test]` attribute on real test functions
+//!
+//! The attributed functions are `#[ignore]`d so the harness doesn't run them before the mock
+//! tracer and the global session are set up: `dd_test_reports_the_attributed_functions`
+//! sets them up, then calls the functions itself.
+use test_optimization_sdk::*;
+
+#[dd_test]
+#[test]
+#[ignore]
+fn adds_two_numbers() {
+    assert_eq!(1 + 1, 2);
+}
+
+#[dd_test]
+#[test]
+#[ignore]
+fn returns_an_error() -> Result<(), String> {
+    Err("expected failure".to_string())
+}
+
+#[test]
+fn dd_test_reports_the_attributed_functions() {
+    assert!(TestOptimization::init_mock());
+    let session = TestSession::create(Some("cargo test"), None::<&str>);
+    assert!(TestSession::set_global(session.clone()).is_ok());
+
+    adds_two_numbers();
+    assert_eq!(returns_an_error(), Err("expected failure".to_string()));
+
+    let spans = MockTracer::get_finished_spans();
+    let test_span = |name: &str| {
+        spans
+            .iter()
+            .find(|span| span.string_tags.get(tags::TEST_NAME).map(String::as_str) == Some(name))
+            .unwrap_or_else(|| panic!("no span for test {:?}", name))
+    };
+    let passed = test_span("adds_two_numbers");
+    assert_eq!(passed.test_status(), Some(TestStatus::Pass));
+    assert_eq!(passed.string_tags[tags::TEST_SUITE], "dd_test");
+    let failed = test_span("returns_an_error");
+    assert_eq!(failed.test_status(), Some(TestStatus::Fail));
+    assert_eq!(failed.string_tags[tags::ERROR_MESSAGE], r#""expected failure""#);
+
+    session.close(0);
+    assert!(TestOptimization::shutdown());
+}
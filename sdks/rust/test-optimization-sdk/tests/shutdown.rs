@@ -0,0 +1,35 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the Apache License Version 2.0.
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2025 Datadog, Inc.
+
+//! Integration test of the shutdown of the library
+//!
+//! The native library can't be initialized again after shutdown, and the shutdown is global
+//! to the process, so it runs in its own test binary instead of next to the unit tests.
+use test_optimization_sdk::*;
+
+#[test]
+fn init_fails_after_shutdown() {
+    assert!(TestOptimization::init_mock());
+    let session = TestSession::create(Some("cargo test"), None::<&str>);
+    session.close(0);
+
+    assert!(TestOptimization::shutdown());
+    assert!(!TestOptimization::shutdown());
+    assert!(!TestOptimization::is_initialized());
+    // the arguments are still validated first
+    assert_eq!(
+        TestOptimization::try_init_with_values("rust", "rustc", "1.0.0", Some("/tmp/a\0b"), true),
+        Err(InitError::InvalidWorkingDirectory("/tmp/a\0b".into()))
+    );
+    assert_eq!(
+        TestOptimization::try_init_with_values("rust", "rustc", "1.0.0", None::<&str>, true),
+        Err(InitError::ShutDown)
+    );
+    assert!(!TestOptimization::init_mock());
+
+    assert!(!TestOptimization::is_coverage_active());
+    assert!(!TestOptimization::flush_coverage());
+    assert_eq!(TestOptimization::git_upload_status().completed, Some(false));
+}